@@ -2,8 +2,14 @@
 pub mod arg;
 #[path = "common/config.rs"]
 pub mod config;
+#[path = "common/env.rs"]
+pub mod env;
 #[path = "common/handle.rs"]
 pub mod handle;
+#[path = "common/process.rs"]
+pub mod process;
+#[path = "common/schema.rs"]
+pub mod schema;
 #[path = "common/task.rs"]
 pub mod task;
 #[path = "common/trait_arg.rs"]
@@ -4,8 +4,8 @@ use clap::{Args, Parser, Subcommand};
 #[derive(Debug, Parser, PartialEq)]
 #[command(author, about)]
 pub struct TaskArgs {
-    /// Config file path.
-    /// Default: $HOME/.watchmen/config.toml
+    /// Config file path, replaces /etc/watchmen/config.toml, $HOME/.watchmen/config.toml
+    /// and .watchmen.toml of project
     #[arg(short, long)]
     pub config: Option<String>,
 
@@ -35,7 +35,7 @@ impl TaskArgs {
 #[derive(Debug, Subcommand, PartialEq)]
 pub enum Commands {
     /// Add and run tasks
-    Run(AddArgs),
+    Run(RunArgs),
     /// Add tasks
     Add(AddArgs),
     /// Reload tasks
@@ -54,6 +54,20 @@ pub enum Commands {
     Resume(FlagArgs),
     /// Get tasks list
     List(ListArgs),
+    /// Change number of instances of multi-instance task
+    Scale(ScaleArgs),
+    /// Get run history of task
+    History(HistoryArgs),
+    /// Reconcile tasks with task config files
+    Apply(ApplyArgs),
+    /// Export tasks to task config files
+    Export(ExportArgs),
+    /// Check task config files without daemon
+    Validate(ValidateArgs),
+    /// Print JSON Schema of task config files or config file
+    Schema(SchemaArgs),
+    /// Show config
+    Config(ConfigArgs),
 }
 
 #[derive(Args, Debug, PartialEq)]
@@ -63,7 +77,7 @@ pub struct ListArgs {
     pub path: Option<String>,
 
     /// Task config filename regex pattern
-    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json)$")]
+    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json|yaml|yml)$")]
     pub regex: Option<String>,
 
     /// Task config file
@@ -86,12 +100,20 @@ pub struct ListArgs {
     #[arg(short = 'R', long)]
     pub mat: bool,
 
+    /// Label selector, e.g. `env=prod,tier in (api,worker),!canary`
+    #[arg(short = 'l', long)]
+    pub selector: Option<String>,
+
+    /// Labels shown as columns, e.g. `env,tier`
+    #[arg(short = 'L', long, value_delimiter = ',')]
+    pub labels: Vec<String>,
+
     /// Show more info
     #[arg(short = 'm', long, default_value = "false")]
     pub more: bool,
 
     /// Show less info
-    #[arg(short = 'l', long, default_value = "false")]
+    #[arg(short = 's', long, default_value = "false")]
     pub less: bool,
 }
 
@@ -102,7 +124,7 @@ pub struct FlagArgs {
     pub path: Option<String>,
 
     /// Task config filename regex pattern
-    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json)$")]
+    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json|yaml|yml)$")]
     pub regex: Option<String>,
 
     /// Task config file
@@ -124,6 +146,148 @@ pub struct FlagArgs {
     /// Is match regex pattern by namae
     #[arg(short = 'm', long)]
     pub mat: bool,
+
+    /// Label selector, e.g. `env=prod,tier in (api,worker),!canary`
+    #[arg(short = 'l', long)]
+    pub selector: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ScaleArgs {
+    /// Task name (unique)
+    pub name: String,
+
+    /// Number of instances
+    pub count: u32,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct HistoryArgs {
+    /// Task id or name
+    pub task: String,
+
+    /// Page number
+    #[arg(short = 'p', long, default_value = "1")]
+    pub page: u64,
+
+    /// Number of runs per page
+    #[arg(short = 's', long, default_value = "20")]
+    pub size: u64,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ExportArgs {
+    /// Task file format: toml, ini, json or yaml
+    #[arg(long, default_value = "toml")]
+    pub format: String,
+
+    /// Output directory, print to standard output if not set
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+
+    /// Write one file per group, tasks without group are written to default, requires --output
+    #[arg(long, default_value = "false", requires = "output")]
+    pub split: bool,
+
+    /// Task id (unique)
+    #[arg(short, long)]
+    pub id: Option<i64>,
+
+    /// Task name (unique)
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Task group
+    #[arg(short, long)]
+    pub group: Option<String>,
+
+    /// Is match regex pattern by name
+    #[arg(short = 'm', long)]
+    pub mat: bool,
+
+    /// Label selector, e.g. `env=prod,tier in (api,worker),!canary`
+    #[arg(short = 'l', long)]
+    pub selector: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ApplyArgs {
+    /// Task config directory
+    #[arg(short = 'p', long)]
+    pub path: Option<String>,
+
+    /// Task config filename regex pattern
+    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json|yaml|yml)$")]
+    pub regex: Option<String>,
+
+    /// Task config file
+    #[arg(short = 'f', long)]
+    pub config: Option<String>,
+
+    /// Profile of task config files applied to tasks
+    #[arg(long, env = "WATCHMEN_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Remove tasks that are not in task config files
+    #[arg(long, default_value = "false")]
+    pub prune: bool,
+
+    /// Print the plan without changing tasks
+    #[arg(long, default_value = "false")]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ValidateArgs {
+    /// Task config files or directories
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+
+    /// Task config filename regex pattern, used for directories
+    #[arg(short = 'r', long)]
+    pub regex: Option<String>,
+
+    /// Profile of task config files applied to tasks
+    #[arg(long, env = "WATCHMEN_PROFILE")]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct SchemaArgs {
+    /// Schema of task config files or config file
+    #[arg(default_value = "task", value_parser = ["task", "config"])]
+    pub kind: String,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum ConfigCommands {
+    /// Print effective config and where each value came from
+    Show,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub task: AddArgs,
+
+    /// Remove tasks automatically after they finished
+    #[arg(long, default_value = "false")]
+    pub rm: bool,
+
+    /// Stream task output to terminal and exit with task exit code, daemon must run on
+    /// the same host
+    #[arg(short = 'F', long, default_value = "false")]
+    pub foreground: bool,
+
+    /// Task command and arguments, e.g. `watchmen run -- python -u worker.py`
+    #[arg(last = true)]
+    pub cmd: Vec<String>,
 }
 
 #[derive(Args, Debug, PartialEq)]
@@ -133,13 +297,17 @@ pub struct AddArgs {
     pub path: Option<String>,
 
     /// Task config filename regex pattern
-    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json)$")]
+    #[arg(short = 'r', long, default_value = r"^.*\.(toml|ini|json|yaml|yml)$")]
     pub regex: Option<String>,
 
     /// Task config file
     #[arg(short = 'f', long)]
     pub config: Option<String>,
 
+    /// Profile of task config files applied to tasks
+    #[arg(long, env = "WATCHMEN_PROFILE")]
+    pub profile: Option<String>,
+
     /// Task name (unique)
     #[arg(short, long)]
     pub name: Option<String>,
@@ -156,6 +324,10 @@ pub struct AddArgs {
     #[arg(short, long)]
     pub group: Option<String>,
 
+    /// Task labels, e.g. `--label env=prod --label tier=api`
+    #[arg(long = "label")]
+    pub labels: Option<Vec<String>>,
+
     /// Task working directory
     #[arg(short = 'd', long)]
     pub dir: Option<String>,
@@ -181,8 +353,8 @@ pub struct AddArgs {
 #[derive(Debug, Parser, PartialEq)]
 #[command(author, about)]
 pub struct DaemonArgs {
-    /// Config file path.
-    /// Default: $HOME/.watchmen/config.toml
+    /// Config file path, replaces /etc/watchmen/config.toml, $HOME/.watchmen/config.toml
+    /// and .watchmen.toml of project
     #[arg(short, long)]
     pub config: Option<String>,
 
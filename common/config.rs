@@ -1,43 +1,189 @@
-use std::{error::Error, path::PathBuf};
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
 
+use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Sections of config file
+pub const SECTIONS: [&str; 5] = ["watchmen", "sock", "socket", "http", "redis"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(default)]
 pub struct Config {
     pub watchmen: Watchmen,
     pub sock: Sock,
     pub socket: Socket,
     pub http: Http,
     pub redis: Redis,
+    /// Where each value came from, keyed by `section.key`
+    #[serde(skip)]
+    pub sources: BTreeMap<String, String>,
 }
 
 impl Config {
+    /// Config merged from layers, later layers override earlier ones:
+    ///
+    /// 1. defaults
+    /// 2. `/etc/watchmen/config.toml`
+    /// 3. `$HOME/.watchmen/config.toml`
+    /// 4. `.watchmen.toml` of current directory or its nearest parent
+    /// 5. environment variables `WATCHMEN_<SECTION>_<KEY>`, e.g. `WATCHMEN_SOCK_PATH`
+    ///
+    /// # params
+    ///
+    /// - `path`: config file, replaces config files of layer 2 ~ 4
     pub fn init(path: Option<String>) -> Result<Config, Box<dyn Error>> {
-        let path: PathBuf = if let Some(path) = path {
-            PathBuf::from(path)
+        let files: Vec<PathBuf> = if let Some(path) = path {
+            vec![PathBuf::from(path)]
         } else {
-            let home: PathBuf = match dirs::home_dir() {
-                Some(p) => p,
-                None => return Err("No home dir found".into()),
+            let mut files = vec![PathBuf::from("/etc/watchmen/config.toml")];
+            if let Some(home) = dirs::home_dir() {
+                files.push(home.join(".watchmen/config.toml"));
+            }
+            // 项目配置文件，从当前目录向上查找
+            if let Ok(dir) = std::env::current_dir() {
+                if let Some(project) = dir
+                    .ancestors()
+                    .map(|d| d.join(".watchmen.toml"))
+                    .find(|f| f.is_file())
+                {
+                    files.push(project);
+                }
+            }
+            files.into_iter().filter(|f| f.is_file()).collect()
+        };
+
+        let mut sources = BTreeMap::new();
+        let mut table = toml::Table::new();
+        let defaults = match toml::Value::try_from(Config::default())? {
+            toml::Value::Table(defaults) => defaults,
+            _ => toml::Table::new(),
+        };
+        merge(&mut table, defaults, "default", &mut sources);
+        for file in files {
+            let origin = file.display().to_string();
+            let text = std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", origin, e))?;
+            let layer: toml::Table =
+                toml::from_str(&text).map_err(|e| format!("{}: {}", origin, e))?;
+            merge(&mut table, layer, &origin, &mut sources);
+        }
+        for Key {
+            section,
+            name: key,
+            string,
+        } in keys()?
+        {
+            let var = format!("WATCHMEN_{}_{}", section, key).to_uppercase();
+            let Ok(raw) = std::env::var(&var) else {
+                continue;
             };
-            let config_path = home.join(".watchmen/config.toml");
-            if config_path.exists() {
-                config_path
+            // 字符串原样使用，其他类型按 TOML 值解析，如 ["sock", "http"]
+            let value = if string {
+                toml::Value::String(raw)
             } else {
-                // /etc/watchmen/config.toml
-                let config_path = PathBuf::from("/etc/watchmen/config.toml");
-                if config_path.exists() {
-                    config_path
-                } else {
-                    return Err("No config file found".into());
+                toml::from_str::<toml::Table>(&format!("value = {}", raw))
+                    .ok()
+                    .and_then(|mut t| t.remove("value"))
+                    .ok_or_else(|| format!("Invalid value of {}: {}", var, raw))?
+            };
+            let mut layer = toml::Table::new();
+            layer.insert(key, value);
+            let mut root = toml::Table::new();
+            root.insert(section, toml::Value::Table(layer));
+            merge(&mut table, root, &format!("env {}", var), &mut sources);
+        }
+
+        let mut config: Config =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| {
+                    // 错误信息以 in `section.key` 结尾，附上值的来源
+                    let e = e.to_string().trim().to_string();
+                    let source = e
+                        .rsplit_once("in `")
+                        .and_then(|(_, key)| sources.get(key.trim_end_matches('`')));
+                    match source {
+                        Some(source) => format!("Invalid config, {}, from {}", e, source),
+                        None => format!("Invalid config, {}", e),
+                    }
+                })?;
+        config.sources = sources;
+        Config::prepare(config)
+    }
+}
+
+/// Merge keys of layer into config table, sections are merged key by key
+///
+/// # params
+///
+/// - `table`: config table
+/// - `layer`: config table of layer
+/// - `origin`: where the layer came from, recorded for each key
+/// - `sources`: origins of keys
+fn merge(
+    table: &mut toml::Table,
+    layer: toml::Table,
+    origin: &str,
+    sources: &mut BTreeMap<String, String>,
+) {
+    for (section, value) in layer {
+        match (table.get_mut(&section), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(value)) => {
+                for (key, value) in value {
+                    sources.insert(format!("{}.{}", section, key), origin.to_string());
+                    existing.insert(key, value);
                 }
             }
-        };
-        let config: Config = Config::from(path)?;
-        Ok(config)
+            (_, value) => {
+                if let toml::Value::Table(keys) = &value {
+                    for key in keys.keys() {
+                        sources.insert(format!("{}.{}", section, key), origin.to_string());
+                    }
+                }
+                table.insert(section, value);
+            }
+        }
     }
 }
 
+/// Key of config section
+pub struct Key {
+    pub section: String,
+    pub name: String,
+    /// Whether the value is a string, other values of environment variables are parsed
+    pub string: bool,
+}
+
+/// Keys of config sections in order of sections
+pub fn keys() -> Result<Vec<Key>, Box<dyn Error>> {
+    let schema = serde_json::to_value(schema_for!(Config))?;
+    let mut keys = Vec::new();
+    for section in SECTIONS {
+        let property = &schema["properties"][section];
+        // 有默认值的属性为 allOf 引用
+        let reference = property["$ref"]
+            .as_str()
+            .or_else(|| property["allOf"][0]["$ref"].as_str())
+            .unwrap_or_default();
+        let name = reference.trim_start_matches("#/definitions/");
+        if let Some(properties) = schema["definitions"][name]["properties"].as_object() {
+            for (key, property) in properties {
+                let string = match &property["type"] {
+                    Value::String(t) => t == "string",
+                    Value::Array(types) => types.iter().any(|t| t == "string"),
+                    _ => false,
+                };
+                keys.push(Key {
+                    section: section.to_string(),
+                    name: key.clone(),
+                    string,
+                });
+            }
+        }
+    }
+    Ok(keys)
+}
+
 pub fn get_with_home(input: &str) -> String {
     let home_dir = dirs::home_dir().unwrap();
     let home_dir_str = home_dir.to_string_lossy().into_owned();
@@ -57,9 +203,8 @@ pub fn get_with_home_path(input: &str) -> PathBuf {
 }
 
 impl Config {
-    fn from(s: PathBuf) -> Result<Self, Box<dyn Error>> {
-        let config_str = std::fs::read_to_string(s)?;
-        let mut config: Config = toml::from_str(&config_str)?;
+    /// Expand home of paths and create their directories
+    fn prepare(mut config: Config) -> Result<Self, Box<dyn Error>> {
         if let Some(log_dir) = &config.watchmen.log_dir {
             let log_dir = get_with_home_path(log_dir);
             if !log_dir.exists() {
@@ -97,8 +242,12 @@ impl Config {
             }
             config.watchmen.pid = Some(pid.to_str().unwrap().to_string());
         }
+        if let Some(include_dirs) = &config.watchmen.include_dirs {
+            config.watchmen.include_dirs =
+                Some(include_dirs.iter().map(|dir| get_with_home(dir)).collect());
+        }
         if config.watchmen.mat.is_none() {
-            config.watchmen.mat = Some(r"^.*\.(toml|ini|json)$".to_string());
+            config.watchmen.mat = Some(r"^.*\.(toml|ini|json|yaml|yml)$".to_string());
         }
         let path = get_with_home_path(&config.sock.path);
         let parent = path.parent().unwrap();
@@ -110,7 +259,8 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(default)]
 pub struct Watchmen {
     pub engine: String,
     pub engines: Vec<String>,
@@ -121,27 +271,35 @@ pub struct Watchmen {
     pub pid: Option<String>,
     pub mat: Option<String>,
     pub cache: Option<String>,
+    pub cache_snapshots: Option<usize>,
+    pub backend: Option<String>,
+    pub database: Option<String>,
     pub interval: Option<u64>,
+    pub include_dirs: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(default)]
 pub struct Sock {
     pub path: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(default)]
 pub struct Socket {
     pub host: String,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(default)]
 pub struct Http {
     pub host: String,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(default)]
 pub struct Redis {
     pub host: String,
     pub port: u16,
@@ -152,3 +310,65 @@ pub struct Redis {
     pub subscribe_channels: Vec<String>,
     pub subscribe_name: String,
 }
+
+impl Default for Watchmen {
+    fn default() -> Self {
+        Self {
+            engine: "sock".to_string(),
+            engines: vec!["sock".to_string()],
+            log_dir: None,
+            log_level: None,
+            stdout: None,
+            stderr: None,
+            pid: None,
+            mat: None,
+            cache: None,
+            cache_snapshots: None,
+            backend: None,
+            database: None,
+            interval: None,
+            include_dirs: None,
+        }
+    }
+}
+
+impl Default for Sock {
+    fn default() -> Self {
+        Self {
+            path: "/tmp/watchmen.sock".to_string(),
+        }
+    }
+}
+
+impl Default for Socket {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 1949,
+        }
+    }
+}
+
+impl Default for Http {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 1997,
+        }
+    }
+}
+
+impl Default for Redis {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 6379,
+            username: String::new(),
+            password: String::new(),
+            queue_index: 0,
+            queue_name: "watchmen".to_string(),
+            subscribe_channels: vec!["watchmen".to_string()],
+            subscribe_name: "watchmen".to_string(),
+        }
+    }
+}
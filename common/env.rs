@@ -0,0 +1,373 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+/// Variable parsed from env file: key, value and whether the value can be expanded
+pub type EnvVar = (String, String, bool);
+
+/// Parse env file in dotenv format
+///
+/// ```text
+/// # comment
+/// KEY=value
+/// export KEY="value with spaces"
+/// KEY='literal ${NOT_EXPANDED}'
+/// ```
+///
+/// # params
+///
+/// - `path`: env file path
+///
+/// # return
+///
+/// - `Vec<EnvVar>`: key, value and whether the value can be expanded
+pub fn parse_env_file(path: &Path) -> Result<Vec<EnvVar>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Env file [{}] read failed: {}", path.display(), e))?;
+    let mut vars = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, value) = match line.split_once('=') {
+            Some(kv) => kv,
+            None => {
+                return Err(format!(
+                    "Env file [{}:{}] invalid line, expected KEY=VALUE",
+                    path.display(),
+                    index + 1
+                )
+                .into())
+            }
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(format!(
+                "Env file [{}:{}] invalid key: {}",
+                path.display(),
+                index + 1,
+                key
+            )
+            .into());
+        }
+        let value = value.trim();
+        let (value, expand) = if let Some(v) = value.strip_prefix('"') {
+            match v.strip_suffix('"') {
+                Some(v) => (
+                    v.replace("\\n", "\n")
+                        .replace("\\t", "\t")
+                        .replace("\\\"", "\"")
+                        .replace("\\\\", "\\"),
+                    true,
+                ),
+                None => {
+                    return Err(format!(
+                        "Env file [{}:{}] unterminated double quote",
+                        path.display(),
+                        index + 1
+                    )
+                    .into())
+                }
+            }
+        } else if let Some(v) = value.strip_prefix('\'') {
+            match v.strip_suffix('\'') {
+                Some(v) => (v.to_string(), false),
+                None => {
+                    return Err(format!(
+                        "Env file [{}:{}] unterminated single quote",
+                        path.display(),
+                        index + 1
+                    )
+                    .into())
+                }
+            }
+        } else {
+            // 未加引号的值允许行尾注释
+            let value = match value.find(" #") {
+                Some(i) => value[..i].trim_end(),
+                None => value,
+            };
+            (value.to_string(), true)
+        };
+        vars.push((key.to_string(), value, expand));
+    }
+    Ok(vars)
+}
+
+/// Expand `${VAR}` in value, unknown variables are replaced with empty string
+///
+/// # params
+///
+/// - `value`: value to expand
+/// - `lookup`: variable lookup
+pub fn expand<F>(value: &str, lookup: &mut F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        match rest[start + 2..].find('}') {
+            Some(end) => {
+                result.push_str(&rest[..start]);
+                let name = &rest[start + 2..start + 2 + end];
+                result.push_str(&lookup(name).unwrap_or_default());
+                rest = &rest[start + 2 + end + 1..];
+            }
+            None => break,
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Split string by whitespace, single and double quotes group words
+///
+/// # example
+///
+/// ```text
+/// key1=value1 key2="value 2" -> ["key1=value1", "key2=value 2"]
+/// ```
+pub fn split_words(s: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    for c in s.chars() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                } else {
+                    word.push(c);
+                }
+            }
+            None => {
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                    in_word = true;
+                } else if c.is_whitespace() {
+                    if in_word {
+                        words.push(std::mem::take(&mut word));
+                        in_word = false;
+                    }
+                } else {
+                    word.push(c);
+                    in_word = true;
+                }
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unterminated quote in: {}", s).into());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Resolve env values of task, `${VAR}` is looked up in task env first, then base environment.
+/// A cyclic reference is looked up in base environment, keys are resolved in sorted order so
+/// the result does not depend on hash order
+pub fn resolve(
+    env: &HashMap<String, String>,
+    base: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    fn get(
+        name: &str,
+        env: &HashMap<String, String>,
+        base: &HashMap<String, String>,
+        resolving: &mut Vec<String>,
+        resolved: &mut HashMap<String, String>,
+    ) -> Option<String> {
+        if let Some(v) = resolved.get(name) {
+            return Some(v.clone());
+        }
+        match env.get(name) {
+            // 循环引用时使用基础环境变量
+            Some(raw) if !resolving.iter().any(|r| r == name) => {
+                resolving.push(name.to_string());
+                let value = expand(raw, &mut |n: &str| get(n, env, base, resolving, resolved));
+                resolving.pop();
+                resolved.insert(name.to_string(), value.clone());
+                Some(value)
+            }
+            _ => base.get(name).cloned(),
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+    for key in keys {
+        get(key, env, base, &mut Vec::new(), &mut resolved);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::task::{InheritEnv, Task};
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("watchmen-env-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn var(key: &str, value: &str, expand: bool) -> EnvVar {
+        (key.to_string(), value.to_string(), expand)
+    }
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let dir = temp_dir("parse");
+        let path = dir.join("app.env");
+        fs::write(
+            &path,
+            concat!(
+                "# comment\n",
+                "\n",
+                "PLAIN=value # trailing comment\n",
+                "export EXPORTED = spaced\n",
+                "DOUBLE=\"a\\tb\\n\\\"c\\\" \\\\ ${HOME}\"\n",
+                "SINGLE='literal ${HOME} # kept'\n",
+                "HASH=a#b\n",
+                "app.key=\n",
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            parse_env_file(&path).unwrap(),
+            vec![
+                var("PLAIN", "value", true),
+                var("EXPORTED", "spaced", true),
+                var("DOUBLE", "a\tb\n\"c\" \\ ${HOME}", true),
+                var("SINGLE", "literal ${HOME} # kept", false),
+                var("HASH", "a#b", true),
+                var("app.key", "", true),
+            ]
+        );
+
+        for line in ["NO_VALUE", "BAD-KEY=1", "=1", "D=\"open", "S='open"] {
+            fs::write(&path, format!("A=1\n{}\n", line)).unwrap();
+            let e = parse_env_file(&path).unwrap_err().to_string();
+            assert!(e.contains(":2]"), "{}: {}", line, e);
+        }
+        assert!(parse_env_file(&dir.join("missing.env")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand() {
+        let vars = map(&[("A", "1"), ("B", "two")]);
+        let mut lookup = |name: &str| vars.get(name).cloned();
+        assert_eq!(expand("${A}-${B}", &mut lookup), "1-two");
+        // 未定义的变量替换为空，未闭合的引用保持原样
+        assert_eq!(expand("x${UNDEFINED}y", &mut lookup), "xy");
+        assert_eq!(expand("$A ${B", &mut lookup), "$A ${B");
+        assert_eq!(expand("", &mut lookup), "");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let env = map(&[
+            ("URL", "http://${HOST}:${PORT}"),
+            ("HOST", "localhost"),
+            ("PATH", "${PATH}:/opt/bin"),
+            ("EMPTY", "${UNDEFINED}"),
+        ]);
+        let base = map(&[("PATH", "/usr/bin"), ("PORT", "8080")]);
+        let resolved = resolve(&env, &base);
+        assert_eq!(resolved["URL"], "http://localhost:8080");
+        // 引用自身时使用基础环境变量
+        assert_eq!(resolved["PATH"], "/usr/bin:/opt/bin");
+        assert_eq!(resolved["EMPTY"], "");
+        assert!(!resolved.contains_key("PORT"));
+
+        // 循环引用使用基础环境变量，按键的顺序解析
+        let env = map(&[("A", "a${B}"), ("B", "b${A}")]);
+        assert_eq!(
+            resolve(&env, &HashMap::new()),
+            map(&[("A", "ab"), ("B", "b")])
+        );
+        let base = map(&[("A", "0")]);
+        assert_eq!(resolve(&env, &base), map(&[("A", "ab0"), ("B", "b0")]));
+    }
+
+    #[test]
+    fn test_split_words() {
+        let words = |s: &str| split_words(s).unwrap();
+        assert!(words("").is_empty());
+        assert!(words("   ").is_empty());
+        assert_eq!(words(" a  b\tc "), vec!["a", "b", "c"]);
+        assert_eq!(
+            words(r#"key1=value1 key2="value 2" 'it"s'"#),
+            vec!["key1=value1", "key2=value 2", "it\"s"]
+        );
+        // 引号内外相连的部分是同一个词，空引号是空词
+        assert_eq!(words(r#"a"b c"d ''"#), vec!["ab cd", ""]);
+        assert!(split_words("a 'b").is_err());
+        assert!(split_words("\"").is_err());
+    }
+
+    #[test]
+    fn test_environment() {
+        let dir = temp_dir("task");
+        fs::write(
+            dir.join("base.env"),
+            "GREETING=hello\nTARGET=${GREETING} world\n",
+        )
+        .unwrap();
+        let task = Task {
+            name: "env".to_string(),
+            command: "true".to_string(),
+            dir: Some(dir.to_string_lossy().to_string()),
+            // 以 - 开头的文件不存在时忽略
+            env_file: vec!["base.env".to_string(), "-missing.env".to_string()],
+            env: map(&[("MESSAGE", "${TARGET}!")]),
+            inherit_env: Some(InheritEnv::Only(vec!["PATH".to_string()])),
+            ..Task::default()
+        };
+        let envs = task.environment(None).unwrap();
+        assert_eq!(envs["TARGET"], "hello world");
+        assert_eq!(envs["MESSAGE"], "hello world!");
+        // 只继承列出的守护进程环境变量
+        assert_eq!(envs.get("PATH").cloned(), std::env::var("PATH").ok());
+        let inherited: Vec<&String> = envs
+            .keys()
+            .filter(|k| !k.starts_with("WATCHMEN_"))
+            .filter(|k| !["PATH", "GREETING", "TARGET", "MESSAGE"].contains(&k.as_str()))
+            .collect();
+        assert!(inherited.is_empty(), "{:?}", inherited);
+
+        let none = Task {
+            inherit_env: Some(InheritEnv::All(false)),
+            ..task.clone()
+        };
+        assert!(!none.environment(None).unwrap().contains_key("PATH"));
+
+        let required = Task {
+            env_file: vec!["missing.env".to_string()],
+            ..task
+        };
+        assert!(required.environment(None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
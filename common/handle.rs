@@ -2,7 +2,7 @@ use std::{collections::HashMap, fmt::Display};
 
 use serde::{Deserialize, Serialize};
 
-use crate::common::task::{Task, TaskFlag, TaskType};
+use crate::common::task::{LiveRun, Run, Task, TaskFlag, TaskType};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Request {
@@ -22,6 +22,13 @@ pub enum Command {
     Pause(TaskFlag),
     Resume(TaskFlag),
     List(Option<TaskFlag>),
+    Scale(TaskFlag, u32),
+    /// Run history of task: task, page, page size
+    History(TaskFlag, u64, u64),
+    /// Reconcile tasks with desired tasks: desired tasks, prune, dry run
+    Apply(Vec<Task>, bool, bool),
+    /// Definitions of tasks
+    Export(Option<TaskFlag>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +66,21 @@ impl Response {
             data: Some(Data::String(msg)),
         }
     }
+
+    /// Response of command applied to several tasks, code is the worst code of results
+    pub fn results(results: Vec<Response>) -> Self {
+        let code = results.iter().map(|r| r.code).max().unwrap_or(10000);
+        let msg = match code {
+            10000 => "Success",
+            40000 => "Wrong",
+            _ => "Failed",
+        };
+        Self {
+            code,
+            msg: msg.to_string(),
+            data: Some(Data::Results(results)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,6 +88,14 @@ pub enum Data {
     None,
     String(String),
     Status(Vec<Status>),
+    /// Total number of runs and runs of the page
+    History(u64, Vec<Run>),
+    /// Result of each task selected by command
+    Results(Vec<Response>),
+    /// Changes planned or made by apply
+    Plan(Vec<Change>),
+    /// Task definitions selected by export
+    Tasks(Vec<Task>),
 }
 
 impl Default for Data {
@@ -85,10 +115,49 @@ impl Display for Data {
                 }
                 Ok(())
             }
+            Data::History(total, runs) => {
+                write!(f, "{} runs", total)?;
+                for r in runs {
+                    write!(f, "{:?}", r)?;
+                }
+                Ok(())
+            }
+            Data::Results(results) => {
+                let results: Vec<String> = results
+                    .iter()
+                    .map(|r| r.data.clone().unwrap_or_default().to_string())
+                    .collect();
+                write!(f, "{}", results.join("; "))
+            }
+            Data::Tasks(tasks) => {
+                let tasks: Vec<String> = tasks
+                    .iter()
+                    .map(|t| format!("[{}:{}]", t.id, t.name))
+                    .collect();
+                write!(f, "{}", tasks.join("; "))
+            }
+            Data::Plan(changes) => {
+                let changes: Vec<String> = changes
+                    .iter()
+                    .map(|c| format!("{} [{}:{}]", c.action, c.id, c.name))
+                    .collect();
+                write!(f, "{}", changes.join("; "))
+            }
         }
     }
 }
 
+/// Change of task planned or made by apply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// add, reload, prune or unchanged
+    pub action: String,
+    pub id: i64,
+    pub name: String,
+    /// Result of the change, none for dry run and unchanged task
+    pub result: Option<Response>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Status {
     pub id: i64,
@@ -97,6 +166,7 @@ pub struct Status {
     pub args: Vec<String>,
     pub dir: Option<String>,
     pub group: Option<String>,
+    pub labels: HashMap<String, String>,
     pub env: HashMap<String, String>,
     pub stdin: Option<bool>,
     pub stdout: Option<String>,
@@ -106,6 +176,19 @@ pub struct Status {
     pub pid: Option<u32>,
     pub status: Option<String>,
     pub code: Option<i32>,
+    /// Signal that terminated the last run
+    pub signal: Option<i32>,
+    pub instances: Option<u32>,
+    pub parent: Option<i64>,
+    pub instance: Option<u32>,
+    /// Runs in flight, periodic and scheduled task only
+    pub runs: Vec<LiveRun>,
+    pub queued: u64,
+    pub timed_out: Option<u64>,
+    /// When a run failed after its retries were exhausted, u64: second
+    pub failed_at: Option<u64>,
+    /// Profile applied to task definition
+    pub profile: Option<String>,
 }
 
 impl From<crate::common::task::Task> for Status {
@@ -117,6 +200,7 @@ impl From<crate::common::task::Task> for Status {
             args: task.args,
             dir: task.dir,
             group: task.group,
+            labels: task.labels,
             env: task.env,
             stdin: task.stdin,
             stdout: task.stdout,
@@ -126,6 +210,15 @@ impl From<crate::common::task::Task> for Status {
             pid: task.pid,
             status: task.status,
             code: task.code,
+            signal: task.signal,
+            instances: task.instances,
+            parent: task.parent,
+            instance: task.instance,
+            runs: task.runs,
+            queued: task.queued,
+            timed_out: task.timed_out,
+            failed_at: task.failed_at,
+            profile: task.profile,
         }
     }
 }
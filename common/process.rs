@@ -0,0 +1,648 @@
+use std::{ffi::CString, io};
+
+use crate::common::task::Task;
+
+const IOPRIO_CLASS_SHIFT: i32 = 13;
+const IOPRIO_WHO_PROCESS: i32 = 1;
+
+/// Process attributes of a task, checked in daemon and applied in child process before exec
+pub struct Attributes {
+    rlimits: Vec<(libc::__rlimit_resource_t, u64)>,
+    nice: Option<i32>,
+    ioprio: Option<i32>,
+    oom_score_adj: Option<CString>,
+    umask: Option<libc::mode_t>,
+    cpu_affinity: Option<libc::cpu_set_t>,
+}
+
+impl Attributes {
+    /// Check and convert task process attributes
+    ///
+    /// # params
+    ///
+    /// - `task`: task to check
+    ///
+    /// # return
+    ///
+    /// - `Err(String)`: description of the first invalid attribute
+    pub fn from_task(task: &Task) -> Result<Attributes, String> {
+        let root = unsafe { libc::geteuid() } == 0;
+
+        let mut rlimits = Vec::new();
+        if let Some(limits) = &task.rlimits {
+            let resources = [
+                ("nofile", libc::RLIMIT_NOFILE, limits.nofile),
+                ("nproc", libc::RLIMIT_NPROC, limits.nproc),
+                ("core", libc::RLIMIT_CORE, limits.core),
+                ("as", libc::RLIMIT_AS, limits.address_space),
+                ("cpu", libc::RLIMIT_CPU, limits.cpu),
+            ];
+            for (name, resource, value) in resources {
+                if let Some(value) = value {
+                    // 非 root 用户不能超过硬限制
+                    let mut current = libc::rlimit {
+                        rlim_cur: 0,
+                        rlim_max: 0,
+                    };
+                    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+                        return Err(format!(
+                            "Invalid rlimit {}: {}",
+                            name,
+                            io::Error::last_os_error()
+                        ));
+                    }
+                    if !root && value > current.rlim_max {
+                        return Err(format!(
+                            "Invalid rlimit {}: {} exceeds hard limit {}",
+                            name, value, current.rlim_max
+                        ));
+                    }
+                    rlimits.push((resource, value));
+                }
+            }
+        }
+
+        if let Some(nice) = task.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(format!("Invalid nice: {}, must be in -20 ~ 19", nice));
+            }
+            if nice < 0 && !root {
+                return Err(format!(
+                    "Invalid nice: {}, negative nice requires root",
+                    nice
+                ));
+            }
+        }
+
+        let ioprio = if let Some(ionice) = &task.ionice {
+            let class = match ionice.class.as_str() {
+                "realtime" => 1,
+                "best-effort" => 2,
+                "idle" => 3,
+                _ => {
+                    return Err(format!(
+                        "Invalid ionice class: {}, must be realtime, best-effort or idle",
+                        ionice.class
+                    ))
+                }
+            };
+            if class == 1 && !root {
+                return Err("Invalid ionice class: realtime requires root".to_string());
+            }
+            let level = ionice.level.unwrap_or(4);
+            if level > 7 {
+                return Err(format!("Invalid ionice level: {}, must be in 0 ~ 7", level));
+            }
+            // idle 类不使用 level
+            let level = if class == 3 { 0 } else { level as i32 };
+            Some((class << IOPRIO_CLASS_SHIFT) | level)
+        } else {
+            None
+        };
+
+        let oom_score_adj = if let Some(adj) = task.oom_score_adj {
+            if !(-1000..=1000).contains(&adj) {
+                return Err(format!(
+                    "Invalid oom_score_adj: {}, must be in -1000 ~ 1000",
+                    adj
+                ));
+            }
+            if adj < 0 && !root {
+                return Err(format!(
+                    "Invalid oom_score_adj: {}, negative value requires root",
+                    adj
+                ));
+            }
+            Some(CString::new(adj.to_string()).unwrap())
+        } else {
+            None
+        };
+
+        let umask = if let Some(umask) = &task.umask {
+            match libc::mode_t::from_str_radix(umask.trim_start_matches("0o"), 8) {
+                Ok(mask) if mask <= 0o777 => Some(mask),
+                _ => return Err(format!("Invalid umask: {}, must be octal 000 ~ 777", umask)),
+            }
+        } else {
+            None
+        };
+
+        let cpu_affinity = if let Some(cpus) = &task.cpu_affinity {
+            if cpus.is_empty() {
+                return Err("Invalid cpu_affinity: empty cpu list".to_string());
+            }
+            let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+            let count = if count > 0 { count as usize } else { 1 };
+            let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            for cpu in cpus {
+                if *cpu >= count || *cpu >= libc::CPU_SETSIZE as usize {
+                    return Err(format!(
+                        "Invalid cpu_affinity: cpu {} not exists, {} cpus available",
+                        cpu, count
+                    ));
+                }
+                unsafe { libc::CPU_SET(*cpu, &mut set) };
+            }
+            Some(set)
+        } else {
+            None
+        };
+
+        Ok(Attributes {
+            rlimits,
+            nice: task.nice,
+            ioprio,
+            oom_score_adj,
+            umask,
+            cpu_affinity,
+        })
+    }
+
+    /// Is there any attribute to apply
+    pub fn is_empty(&self) -> bool {
+        self.rlimits.is_empty()
+            && self.nice.is_none()
+            && self.ioprio.is_none()
+            && self.oom_score_adj.is_none()
+            && self.umask.is_none()
+            && self.cpu_affinity.is_none()
+    }
+
+    /// Apply attributes to current process, called in child process between fork and exec
+    pub fn apply(&self) -> io::Result<()> {
+        for (resource, value) in &self.rlimits {
+            let limit = libc::rlimit {
+                rlim_cur: *value,
+                rlim_max: *value,
+            };
+            if unsafe { libc::setrlimit(*resource, &limit) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some(nice) = self.nice {
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some(ioprio) = self.ioprio {
+            if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some(adj) = &self.oom_score_adj {
+            let path = b"/proc/self/oom_score_adj\0";
+            let fd = unsafe { libc::open(path.as_ptr() as *const libc::c_char, libc::O_WRONLY) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let bytes = adj.as_bytes();
+            let n = unsafe { libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+            unsafe { libc::close(fd) };
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some(umask) = self.umask {
+            unsafe { libc::umask(umask) };
+        }
+        if let Some(set) = &self.cpu_affinity {
+            if unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), set) }
+                != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// User and groups of a task, resolved in daemon and applied in child process before exec
+pub struct Credentials {
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+    pub groups: Vec<libc::gid_t>,
+    pub name: String,
+    pub home: Option<String>,
+}
+
+struct Passwd {
+    name: String,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    home: String,
+}
+
+fn get_passwd(user: &str) -> Result<Option<Passwd>, String> {
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16384];
+    let code = if let Ok(uid) = user.parse::<libc::uid_t>() {
+        unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) }
+    } else {
+        let name = CString::new(user).map_err(|_| format!("Invalid user: {}", user))?;
+        unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        }
+    };
+    if code != 0 {
+        return Err(format!(
+            "Invalid user: {}, {}",
+            user,
+            io::Error::from_raw_os_error(code)
+        ));
+    }
+    if result.is_null() {
+        return Ok(None);
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+    let home = unsafe { std::ffi::CStr::from_ptr(pwd.pw_dir) };
+    Ok(Some(Passwd {
+        name: name.to_string_lossy().to_string(),
+        uid: pwd.pw_uid,
+        gid: pwd.pw_gid,
+        home: home.to_string_lossy().to_string(),
+    }))
+}
+
+fn get_gid(group: &str) -> Result<libc::gid_t, String> {
+    if let Ok(gid) = group.parse::<libc::gid_t>() {
+        return Ok(gid);
+    }
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16384];
+    let name = CString::new(group).map_err(|_| format!("Invalid group: {}", group))?;
+    let code = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut grp,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if code != 0 {
+        return Err(format!(
+            "Invalid group: {}, {}",
+            group,
+            io::Error::from_raw_os_error(code)
+        ));
+    }
+    if result.is_null() {
+        return Err(format!("Invalid group: {}, group not exists", group));
+    }
+    Ok(grp.gr_gid)
+}
+
+fn get_group_list(name: &str, gid: libc::gid_t) -> Result<Vec<libc::gid_t>, String> {
+    let user = CString::new(name).map_err(|_| format!("Invalid user: {}", name))?;
+    let mut count: libc::c_int = 64;
+    loop {
+        let mut groups = vec![0 as libc::gid_t; count as usize];
+        let size = count;
+        let code =
+            unsafe { libc::getgrouplist(user.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        if code >= 0 {
+            groups.truncate(count as usize);
+            return Ok(groups);
+        }
+        // 缓冲区不足时 count 会被设置为实际数量
+        if count <= size {
+            count = size * 2;
+        }
+    }
+}
+
+impl Credentials {
+    /// Resolve task user and groups
+    ///
+    /// # params
+    ///
+    /// - `task`: task to resolve
+    ///
+    /// # return
+    ///
+    /// - `Ok(None)`: task runs as the daemon user
+    /// - `Err(String)`: user or group not exists, or the daemon lacks the privilege
+    pub fn from_task(task: &Task) -> Result<Option<Credentials>, String> {
+        if task.user.is_none() && task.user_group.is_none() && task.supplementary_groups.is_none() {
+            return Ok(None);
+        }
+
+        let euid = unsafe { libc::geteuid() };
+        let egid = unsafe { libc::getegid() };
+
+        // 用户不在 passwd 中时只允许数字 uid，此时没有 home 与附加组
+        let (uid, name, home, default_gid, known) = match &task.user {
+            Some(user) => match get_passwd(user)? {
+                Some(p) => (p.uid, p.name, p.home, p.gid, true),
+                None => match user.parse::<libc::uid_t>() {
+                    Ok(uid) => (uid, user.clone(), String::new(), egid, false),
+                    Err(_) => return Err(format!("Invalid user: {}, user not exists", user)),
+                },
+            },
+            None => match get_passwd(&euid.to_string())? {
+                Some(p) => (p.uid, p.name, p.home, egid, false),
+                None => (euid, euid.to_string(), String::new(), egid, false),
+            },
+        };
+
+        let gid = match &task.user_group {
+            Some(group) => get_gid(group)?,
+            None => default_gid,
+        };
+
+        let groups = match &task.supplementary_groups {
+            Some(groups) => {
+                let mut gids = Vec::new();
+                for group in groups {
+                    gids.push(get_gid(group)?);
+                }
+                gids
+            }
+            None => {
+                if known {
+                    get_group_list(&name, gid)?
+                } else {
+                    vec![gid]
+                }
+            }
+        };
+
+        if euid != 0 && (uid != euid || gid != egid || task.supplementary_groups.is_some()) {
+            return Err(format!(
+                "Invalid user: switching to {}:{} requires the daemon to run as root",
+                uid, gid
+            ));
+        }
+
+        Ok(Some(Credentials {
+            uid,
+            gid,
+            groups,
+            name,
+            home: if home.is_empty() { None } else { Some(home) },
+        }))
+    }
+
+    /// Drop privileges of current process, called in child process between fork and exec
+    pub fn apply(&self) -> io::Result<()> {
+        if unsafe { libc::geteuid() } == 0
+            && unsafe { libc::setgroups(self.groups.len(), self.groups.as_ptr()) } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::setgid(self.gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::setuid(self.uid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Start time of process, clock ticks after system boot, field 22 of /proc/<pid>/stat
+///
+/// Pids are reused, a process is identified by pid together with its start time.
+///
+/// # params
+///
+/// - `pid`: process id
+pub fn start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // 进程名可能包含空格与括号，从最后一个右括号之后开始解析
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // 第 3 个字段为状态，僵尸进程视为已退出
+    if fields.first() == Some(&"Z") {
+        return None;
+    }
+    fields.get(22 - 3)?.parse().ok()
+}
+
+/// Parse signal name or number, e.g. "SIGTERM", "TERM" or "15"
+///
+/// # params
+///
+/// - `name`: signal name or number
+pub fn parse_signal(name: &str) -> Option<libc::c_int> {
+    if let Ok(n) = name.parse::<libc::c_int>() {
+        return if n > 0 && n < libc::SIGRTMAX() {
+            Some(n)
+        } else {
+            None
+        };
+    }
+    let name = name.to_uppercase();
+    let signal = match name.trim_start_matches("SIG") {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "ABRT" => libc::SIGABRT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "ALRM" => libc::SIGALRM,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        "WINCH" => libc::SIGWINCH,
+        _ => return None,
+    };
+    Some(signal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::task::{Ionice, Rlimits};
+
+    fn root() -> bool {
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    fn task() -> Task {
+        Task {
+            name: "attributes".to_string(),
+            command: "true".to_string(),
+            ..Task::default()
+        }
+    }
+
+    fn ionice(class: &str, level: Option<u32>) -> Task {
+        Task {
+            ionice: Some(Ionice {
+                class: class.to_string(),
+                level,
+            }),
+            ..task()
+        }
+    }
+
+    #[test]
+    fn test_from_task_ranges() {
+        assert!(Attributes::from_task(&task()).unwrap().is_empty());
+
+        let nice = |nice: i32| {
+            Attributes::from_task(&Task {
+                nice: Some(nice),
+                ..task()
+            })
+        };
+        assert_eq!(nice(19).unwrap().nice, Some(19));
+        assert!(nice(0).is_ok());
+        assert!(nice(20).is_err());
+        assert!(nice(-21).is_err());
+        // 负的 nice 需要 root
+        assert_eq!(nice(-20).is_ok(), root());
+
+        let ioprio = |task: Task| Attributes::from_task(&task).map(|a| a.ioprio);
+        assert_eq!(
+            ioprio(ionice("best-effort", Some(7))),
+            Ok(Some((2 << 13) | 7))
+        );
+        assert_eq!(ioprio(ionice("best-effort", None)), Ok(Some((2 << 13) | 4)));
+        // idle 类不使用 level
+        assert_eq!(ioprio(ionice("idle", Some(5))), Ok(Some(3 << 13)));
+        assert!(ioprio(ionice("best-effort", Some(8))).is_err());
+        assert!(ioprio(ionice("fast", None)).is_err());
+        assert_eq!(ioprio(ionice("realtime", Some(0))).is_ok(), root());
+
+        let adj = |adj: i32| {
+            Attributes::from_task(&Task {
+                oom_score_adj: Some(adj),
+                ..task()
+            })
+        };
+        assert_eq!(
+            adj(1000).unwrap().oom_score_adj,
+            Some(CString::new("1000").unwrap())
+        );
+        assert!(adj(1001).is_err());
+        assert!(adj(-1001).is_err());
+        assert_eq!(adj(-1000).is_ok(), root());
+
+        let limits = Rlimits {
+            nofile: Some(64),
+            nproc: None,
+            core: Some(0),
+            address_space: None,
+            cpu: None,
+        };
+        let attributes = Attributes::from_task(&Task {
+            rlimits: Some(limits),
+            ..task()
+        })
+        .unwrap();
+        assert_eq!(
+            attributes.rlimits,
+            vec![(libc::RLIMIT_NOFILE, 64), (libc::RLIMIT_CORE, 0)]
+        );
+    }
+
+    #[test]
+    fn test_from_task_umask() {
+        let umask = |umask: &str| {
+            Attributes::from_task(&Task {
+                umask: Some(umask.to_string()),
+                ..task()
+            })
+            .map(|a| a.umask)
+        };
+        // 按八进制解析，允许 0o 前缀
+        assert_eq!(umask("022"), Ok(Some(0o22)));
+        assert_eq!(umask("0o077"), Ok(Some(0o77)));
+        assert_eq!(umask("777"), Ok(Some(0o777)));
+        assert_eq!(umask("0"), Ok(Some(0)));
+        for invalid in ["1000", "8", "09", "", "-1", "rwx"] {
+            assert!(umask(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_from_task_cpu_affinity() {
+        let affinity = |cpus: Vec<usize>| {
+            Attributes::from_task(&Task {
+                cpu_affinity: Some(cpus),
+                ..task()
+            })
+            .map(|a| a.cpu_affinity.unwrap())
+        };
+        let set = affinity(vec![0]).unwrap();
+        assert!(unsafe { libc::CPU_ISSET(0, &set) });
+        assert!(!unsafe { libc::CPU_ISSET(1, &set) });
+        assert!(affinity(vec![]).is_err());
+        let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) } as usize;
+        assert!(affinity(vec![0, count]).is_err());
+        assert!(affinity(vec![libc::CPU_SETSIZE as usize]).is_err());
+    }
+
+    #[test]
+    fn test_start_time() {
+        let own = start_time(std::process::id()).unwrap();
+        assert_eq!(start_time(std::process::id()), Some(own));
+        assert_eq!(start_time(0), None);
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        // 退出但未回收的僵尸进程视为已退出
+        let mut zombie = false;
+        for _ in 0..100 {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+            if stat[stat.rfind(')').unwrap() + 1..]
+                .trim_start()
+                .starts_with('Z')
+            {
+                zombie = true;
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(zombie);
+        assert_eq!(start_time(pid), None);
+        child.wait().unwrap();
+        assert_eq!(start_time(pid), None);
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let started = start_time(child.id()).unwrap();
+        assert!(started >= own);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        for name in ["SIGTERM", "TERM", "term", "SigTerm", "15"] {
+            assert_eq!(parse_signal(name), Some(libc::SIGTERM), "{}", name);
+        }
+        assert_eq!(parse_signal("SIGKILL"), Some(libc::SIGKILL));
+        assert_eq!(parse_signal("hup"), Some(libc::SIGHUP));
+        assert_eq!(parse_signal("WINCH"), Some(libc::SIGWINCH));
+        assert_eq!(parse_signal("1"), Some(1));
+        // 超出范围的编号和未知名称无效
+        let max = libc::SIGRTMAX();
+        assert_eq!(parse_signal(&(max - 1).to_string()), Some(max - 1));
+        for name in [
+            "0",
+            "-1",
+            &max.to_string(),
+            "SIG",
+            "",
+            "BOGUS",
+            "SIGTERM2",
+            " TERM",
+        ] {
+            assert_eq!(parse_signal(name), None, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+
+use schemars::{schema::Schema, schema_for, JsonSchema};
+use serde_json::{json, Value};
+
+use crate::common::{
+    config::Config,
+    task::{Task, Tasks},
+};
+
+/// Task config file, TOML and YAML files are tables with `task` array, JSON files are
+/// arrays of tasks
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(untagged)]
+enum TaskFile {
+    Table(Tasks),
+    Array(Vec<Task>),
+}
+
+/// JSON Schema of task config files, written to `schema/task.schema.json`
+pub fn task() -> Result<String, Box<dyn Error>> {
+    let mut schema = schema_for!(TaskFile);
+    schema.schema.metadata().title = Some("Watchmen task config file".to_string());
+
+    // 继承模板的任务可以从模板获得必需的键
+    let definitions = &mut schema.definitions;
+    if let Some(Schema::Object(task)) = definitions.get_mut("Task") {
+        task.object().properties.insert(
+            "extends".to_string(),
+            from_json(json!({
+                "description": "Name of template the task extends",
+                "type": "string"
+            }))?,
+        );
+        let required = std::mem::take(&mut task.object().required);
+        let mut patch = task.clone();
+        task.subschemas().if_schema =
+            Some(Box::new(from_json(json!({ "required": ["extends"] }))?));
+        task.subschemas().else_schema = Some(Box::new(from_json(json!({ "required": required }))?));
+        patch.metadata().description = Some("Keys of task, none is required".to_string());
+        definitions.insert("Patch".to_string(), Schema::Object(patch));
+    }
+    definitions.insert(
+        "Template".to_string(),
+        from_json(json!({
+            "description": "Template of tasks, keys of task override keys of template, env and labels are merged",
+            "allOf": [{ "$ref": "#/definitions/Patch" }],
+            "required": ["name"]
+        }))?,
+    );
+    definitions.insert(
+        "Profile".to_string(),
+        from_json(json!({
+            "description": "Overlay of tasks, keys apply to all tasks, keys of `task` entries apply to the task of the same name",
+            "allOf": [{ "$ref": "#/definitions/Patch" }],
+            "properties": {
+                "task": {
+                    "type": "array",
+                    "items": {
+                        "allOf": [{ "$ref": "#/definitions/Patch" }],
+                        "required": ["name"]
+                    }
+                }
+            }
+        }))?,
+    );
+    if let Some(Schema::Object(tasks)) = definitions.get_mut("Tasks") {
+        let object = tasks.object();
+        object.required.remove("task");
+        object.properties.insert(
+            "template".to_string(),
+            from_json(json!({
+                "type": "array",
+                "items": { "$ref": "#/definitions/Template" }
+            }))?,
+        );
+        object.properties.insert(
+            "profile".to_string(),
+            from_json(json!({
+                "description": "Profiles selected by `--profile` or `WATCHMEN_PROFILE`",
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/Profile" }
+            }))?,
+        );
+    }
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+/// JSON Schema of config file, written to `schema/config.schema.json`
+pub fn config() -> Result<String, Box<dyn Error>> {
+    let mut schema = schema_for!(Config);
+    schema.schema.metadata().title = Some("Watchmen config file".to_string());
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+fn from_json(value: Value) -> Result<Schema, Box<dyn Error>> {
+    Ok(serde_json::from_value(value)?)
+}
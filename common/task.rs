@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
@@ -32,7 +33,11 @@ fn default_map_string_string() -> HashMap<String, String> {
     HashMap::new()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_vec_live_run() -> Vec<LiveRun> {
+    Vec::new()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ScheduledTask {
     pub year: Option<i32>,
     pub month: Option<u32>,
@@ -42,40 +47,276 @@ pub struct ScheduledTask {
     pub second: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AsyncTask {
     #[serde(default = "default_none_u64")]
     pub max_restart: Option<u64>,
+    #[schemars(skip)]
     #[serde(default = "default_u64_0")]
     pub has_restart: u64,
+    #[schemars(skip)]
     #[serde(default = "default_u64_0")]
     pub started_at: u64,
+    #[schemars(skip)]
     #[serde(default = "default_u64_0")]
     pub stopped_at: u64,
+    /// Restart count triggered by file changes, separate from has_restart
+    #[schemars(skip)]
+    #[serde(default = "default_u64_0")]
+    pub watch_restart: u64,
+    /// Last changed file that triggered a restart
+    #[schemars(skip)]
+    #[serde(default = "default_none_string")]
+    pub last_change: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PeriodicTask {
     #[serde(default = "default_u64_0")]
     pub started_after: u64,
     pub interval: u64,
+    #[schemars(skip)]
     #[serde(default = "default_u64_0")]
     pub last_run: u64,
     #[serde(default = "default_false")]
     pub sync: bool,
+    /// Maximum random delay added to each run, u64: second
+    #[serde(default = "default_u64_0")]
+    pub jitter: u64,
+    /// Run on wall-clock boundaries of interval in local time, e.g. interval 900 runs
+    /// at 0, 15, 30 and 45 minutes past the hour
+    #[serde(default = "default_false")]
+    pub align: bool,
+    /// When the next run starts, u64: second, computed by daemon
+    #[schemars(skip)]
+    #[serde(default = "default_u64_0")]
+    pub next_run: u64,
+    /// Nominal time of the next run before jitter is added, u64: second, computed by daemon
+    #[schemars(skip)]
+    #[serde(default = "default_u64_0")]
+    pub slot: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OnceTask {
+    /// Local time to run at, e.g. "2026-11-01T03:00" or "2026-11-01 03:00:00"
+    pub at: Option<String>,
+    /// Delay after task added, u64: second
+    pub after: Option<u64>,
+    /// When the task runs, u64: second, computed by daemon
+    #[schemars(skip)]
+    #[serde(default = "default_u64_0")]
+    pub run_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct Rlimits {
+    /// Max number of open file descriptors
+    pub nofile: Option<u64>,
+    /// Max number of processes
+    pub nproc: Option<u64>,
+    /// Max size of core file, bytes
+    pub core: Option<u64>,
+    /// Max size of virtual memory, bytes
+    #[serde(rename = "as")]
+    pub address_space: Option<u64>,
+    /// Max cpu time, seconds
+    pub cpu: Option<u64>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct Ionice {
+    /// IO scheduling class: realtime, best-effort, idle
+    pub class: String,
+    /// IO scheduling level (0 ~ 7), only for realtime and best-effort
+    pub level: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct Watch {
+    /// Watched files or directories, directories are watched recursively
+    pub paths: Vec<String>,
+    /// Glob patterns of changed files to restart on, relative to watched path. Default is all
+    #[serde(default = "default_vec_string")]
+    pub include: Vec<String>,
+    /// Glob patterns of changed files to ignore, relative to watched path
+    #[serde(default = "default_vec_string")]
+    pub exclude: Vec<String>,
+    /// Debounce interval, u64: millisecond. Default is 500
+    pub debounce: Option<u64>,
+    /// Ignore changes while task is starting, u64: second
+    pub ignore_starting: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum InheritEnv {
+    /// Inherit all or none environment variables of daemon
+    All(bool),
+    /// Inherit only listed environment variables of daemon
+    Only(Vec<String>),
+}
+
+/// What started a run of task
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// Started by command
+    Manual,
+    /// Started by scheduled time
+    Schedule,
+    /// Started by periodic interval
+    Periodic,
+    /// Restarted after exit, file change or daemon restart
+    Restart,
+    /// Started again after the previous attempt failed
+    Retry,
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::Manual => "manual",
+            Trigger::Schedule => "schedule",
+            Trigger::Periodic => "periodic",
+            Trigger::Restart => "restart",
+            Trigger::Retry => "retry",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Trigger> {
+        match s {
+            "manual" => Some(Trigger::Manual),
+            "schedule" => Some(Trigger::Schedule),
+            "periodic" => Some(Trigger::Periodic),
+            "restart" => Some(Trigger::Restart),
+            "retry" => Some(Trigger::Retry),
+            _ => None,
+        }
+    }
+}
+
+/// How a new run of periodic or scheduled task is handled while previous runs are in flight
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcurrencyPolicy {
+    /// Start the new run, up to `max_concurrent_runs` runs in flight
+    Allow,
+    /// Skip the new run
+    Forbid,
+    /// Stop the runs in flight and start the new run
+    Replace,
+    /// Start the new run after a run in flight exits
+    Queue,
+}
+
+impl ConcurrencyPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConcurrencyPolicy::Allow => "allow",
+            ConcurrencyPolicy::Forbid => "forbid",
+            ConcurrencyPolicy::Replace => "replace",
+            ConcurrencyPolicy::Queue => "queue",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<ConcurrencyPolicy> {
+        match s {
+            "allow" => Some(ConcurrencyPolicy::Allow),
+            "forbid" => Some(ConcurrencyPolicy::Forbid),
+            "replace" => Some(ConcurrencyPolicy::Replace),
+            "queue" => Some(ConcurrencyPolicy::Queue),
+            _ => None,
+        }
+    }
+}
+
+/// Run of task whose process is still alive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveRun {
+    pub run_id: String,
+    pub pid: Option<u32>,
+    /// Start time of process, identifies the process together with pid
+    pub pid_start_time: Option<u64>,
+    /// u64: millisecond
+    pub started_at: u64,
+}
+
+/// One execution of task
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub run_id: String,
+    pub task_id: i64,
+    pub task_name: String,
+    pub trigger: Trigger,
+    /// Why the run was started, e.g. exit code of the previous run for restarts
+    pub reason: Option<String>,
+    /// u64: millisecond
+    pub started_at: u64,
+    /// u64: millisecond, none while running
+    pub ended_at: Option<u64>,
+    pub code: Option<i32>,
+    /// Signal that terminated the process
+    pub signal: Option<i32>,
+    /// Size of stdout file when the run started and ended, bytes
+    pub stdout_start: Option<u64>,
+    pub stdout_end: Option<u64>,
+    /// Size of stderr file when the run started and ended, bytes
+    pub stderr_start: Option<u64>,
+    pub stderr_end: Option<u64>,
+    /// Whether the run was stopped by timeout
+    #[serde(default = "default_false")]
+    pub timed_out: bool,
+}
+
+/// Fields changed by daemon while task runs, left out of task files and definition hash
+pub const RUNTIME_FIELDS: [&str; 18] = [
+    "pid",
+    "pid_start_time",
+    "run_id",
+    "runs",
+    "queued",
+    "attempt",
+    "retry_at",
+    "status",
+    "code",
+    "signal",
+    "timed_out",
+    "finished_at",
+    "failed_at",
+    "hash",
+    "created_at",
+    "parent",
+    "instance",
+    "source",
+];
+
+/// Fields of task type changed by daemon while task runs
+pub const RUNTIME_TYPE_FIELDS: [&str; 9] = [
+    "has_restart",
+    "started_at",
+    "stopped_at",
+    "watch_restart",
+    "last_change",
+    "last_run",
+    "next_run",
+    "slot",
+    "run_at",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum TaskType {
     Scheduled(ScheduledTask),
     Async(AsyncTask),
     Periodic(PeriodicTask),
+    Once(OnceTask),
     None,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Task {
-    /// Task id (unique)
+    /// Task id (unique), allocated by daemon if 0
+    #[serde(default = "default_i64_0")]
     pub id: i64,
 
     /// Task name (unique)
@@ -91,6 +332,10 @@ pub struct Task {
     /// Task group
     pub group: Option<String>,
 
+    /// Task labels, tasks are selected by label selector
+    #[serde(default = "default_map_string_string")]
+    pub labels: HashMap<String, String>,
+
     /// Task working directory
     pub dir: Option<String>,
 
@@ -98,19 +343,162 @@ pub struct Task {
     #[serde(default = "default_map_string_string")]
     pub env: HashMap<String, String>,
 
+    /// Task environment files in dotenv format, prefix `-` marks an optional file
+    #[serde(default = "default_vec_string")]
+    pub env_file: Vec<String>,
+
+    /// Inherit environment variables of daemon: true, false or a list of names. Default is true
+    pub inherit_env: Option<InheritEnv>,
+
     pub stdin: Option<bool>,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
 
+    /// Task resource limits
+    pub rlimits: Option<Rlimits>,
+
+    /// Task scheduling priority (-20 ~ 19)
+    pub nice: Option<i32>,
+
+    /// Task io scheduling class and level
+    pub ionice: Option<Ionice>,
+
+    /// Task oom score adjustment (-1000 ~ 1000)
+    pub oom_score_adj: Option<i32>,
+
+    /// Task file mode creation mask, octal string, e.g. "022"
+    pub umask: Option<String>,
+
+    /// Task cpu affinity, cpu indexes
+    pub cpu_affinity: Option<Vec<usize>>,
+
+    /// Run task as user, name or uid
+    pub user: Option<String>,
+
+    /// Run task as group, name or gid. Default is the primary group of user
+    pub user_group: Option<String>,
+
+    /// Task supplementary groups, names or gids. Default is the groups of user
+    pub supplementary_groups: Option<Vec<String>>,
+
+    /// Restart async task when watched files change
+    pub watch: Option<Watch>,
+
+    /// Number of instances sharing this definition, each instance is managed as a task
+    pub instances: Option<u32>,
+
+    /// Base port of instances, exported as PORT = port + instance index
+    pub port: Option<u16>,
+
+    /// Id of the multi-instance task this instance belongs to
+    #[schemars(skip)]
+    pub parent: Option<i64>,
+
+    /// Instance index (0 ~ instances - 1), exported as WATCHMEN_INSTANCE
+    #[schemars(skip)]
+    pub instance: Option<u32>,
+
+    /// How a new run of periodic or scheduled task is handled while previous runs are in
+    /// flight. Default is allow for periodic task with sync, otherwise forbid
+    pub concurrency_policy: Option<ConcurrencyPolicy>,
+
+    /// Maximum number of runs in flight for allow and queue policy. Default is unlimited
+    /// for allow and 1 for queue
+    pub max_concurrent_runs: Option<u32>,
+
+    /// Timeout of each run of periodic or scheduled task, u64: second
+    pub timeout: Option<u64>,
+
+    /// Signal sent to the process on timeout, name or number. Default is SIGTERM
+    pub stop_signal: Option<String>,
+
+    /// Time to wait for the process to exit after stop signal before it is killed,
+    /// u64: second. Default is 5
+    pub kill_timeout: Option<u64>,
+
+    /// Number of retries after a run timed out. Default is 0
+    pub retry_on_timeout: Option<u32>,
+
+    /// Number of retries after a run exited with non-zero code. Default is 0
+    pub retries: Option<u32>,
+
+    /// Delay before the first retry, u64: second. Default is 0
+    pub retry_delay: Option<u64>,
+
+    /// Multiplier of retry delay for each following retry. Default is 1
+    pub retry_backoff: Option<f64>,
+
+    /// Remove task automatically after it finished (completed, failed or stopped),
+    /// u64: second
+    pub ttl_after_finished: Option<u64>,
+
+    /// Hash of task definition when the task was added, apply reloads the task if it changed
+    #[schemars(skip)]
+    pub hash: Option<String>,
+
+    /// Profile applied to task definition when it was loaded from task file
+    #[schemars(skip)]
+    pub profile: Option<String>,
+
+    /// Task file of include directory the task was loaded from by daemon
+    #[schemars(skip)]
+    pub source: Option<String>,
+
+    #[schemars(skip)]
     #[serde(default = "default_created_at")]
     pub created_at: u64,
     pub task_type: TaskType,
 
+    #[schemars(skip)]
     pub pid: Option<u32>,
 
+    /// Start time of process, identifies the process together with pid
+    #[schemars(skip)]
+    pub pid_start_time: Option<u64>,
+
+    /// Id of current or last run, exported as WATCHMEN_RUN_ID
+    #[schemars(skip)]
+    pub run_id: Option<String>,
+
+    /// Runs of periodic or scheduled task in flight, oldest first
+    #[schemars(skip)]
+    #[serde(default = "default_vec_live_run")]
+    pub runs: Vec<LiveRun>,
+
+    /// Number of runs waiting for a run in flight to exit, queue policy only
+    #[schemars(skip)]
+    #[serde(default = "default_u64_0")]
+    pub queued: u64,
+
+    /// Attempt number of current or last run, starts from 1
+    #[schemars(skip)]
+    pub attempt: Option<u32>,
+
+    /// When the pending retry starts, u64: millisecond
+    #[schemars(skip)]
+    pub retry_at: Option<u64>,
+
+    #[schemars(skip)]
     #[serde(default = "default_status")]
     pub status: Option<String>,
+    #[schemars(skip)]
     pub code: Option<i32>,
+    /// Signal that terminated the last run
+    #[schemars(skip)]
+    pub signal: Option<i32>,
+
+    /// Duration of last run if it was stopped by timeout, u64: millisecond
+    #[schemars(skip)]
+    pub timed_out: Option<u64>,
+
+    /// When the task was found finished by daemon, u64: second
+    #[schemars(skip)]
+    pub finished_at: Option<u64>,
+
+    /// When a run failed after its retries were exhausted, u64: second, cleared by the next
+    /// successful run
+    #[schemars(skip)]
+    pub failed_at: Option<u64>,
 }
 
 fn default_created_at() -> u64 {
@@ -139,16 +527,56 @@ impl Default for Task {
             command: String::new(),
             args: vec![],
             group: None,
+            labels: HashMap::new(),
             dir: None,
             env: HashMap::new(),
+            env_file: vec![],
+            inherit_env: None,
             stdin: None,
             stdout: None,
             stderr: None,
+            rlimits: None,
+            nice: None,
+            ionice: None,
+            oom_score_adj: None,
+            umask: None,
+            cpu_affinity: None,
+            user: None,
+            user_group: None,
+            supplementary_groups: None,
+            watch: None,
+            instances: None,
+            port: None,
+            parent: None,
+            instance: None,
+            concurrency_policy: None,
+            max_concurrent_runs: None,
+            timeout: None,
+            stop_signal: None,
+            kill_timeout: None,
+            retry_on_timeout: None,
+            retries: None,
+            retry_delay: None,
+            retry_backoff: None,
+            ttl_after_finished: None,
+            hash: None,
+            profile: None,
+            source: None,
             created_at: timestamp,
             task_type: TaskType::None,
             pid: None,
+            pid_start_time: None,
+            run_id: None,
+            runs: vec![],
+            queued: 0,
+            attempt: None,
+            retry_at: None,
             status: None,
             code: None,
+            signal: None,
+            timed_out: None,
+            finished_at: None,
+            failed_at: None,
         }
     }
 }
@@ -166,6 +594,9 @@ pub struct TaskFlag {
     pub group: Option<String>,
     #[serde(default = "default_false")]
     pub mat: bool,
+    /// Label selector, e.g. `env=prod,tier in (api,worker),!canary`
+    #[serde(default = "default_none_string")]
+    pub selector: Option<String>,
 }
 
 impl Default for TaskFlag {
@@ -175,6 +606,7 @@ impl Default for TaskFlag {
             name: Some(String::new()),
             group: None,
             mat: false,
+            selector: None,
         }
     }
 }
@@ -182,7 +614,7 @@ impl Default for TaskFlag {
 unsafe impl Send for TaskFlag {}
 unsafe impl Sync for TaskFlag {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Tasks {
     pub task: Vec<Task>,
 }
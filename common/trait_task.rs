@@ -1,11 +1,19 @@
-use std::{error::Error, fs::File, io::Read, path::Path, process::Stdio};
+use std::{collections::HashMap, error::Error, fs::File, io::Read, path::Path, process::Stdio};
 
 use configparser::ini::Ini;
+use serde::Deserialize;
+use serde_json::{Map, Value};
 use tokio::process::{Child, Command};
 
 use crate::common::{
     arg::{AddArgs, FlagArgs},
-    task::{AsyncTask, PeriodicTask, ScheduledTask, Task, TaskFlag, TaskType, Tasks},
+    config::get_with_home_path,
+    env::{expand, parse_env_file, resolve, split_words},
+    process::{parse_signal, Attributes, Credentials},
+    task::{
+        AsyncTask, ConcurrencyPolicy, InheritEnv, Ionice, OnceTask, PeriodicTask, Rlimits,
+        ScheduledTask, Task, TaskFlag, TaskType, Tasks, Watch, RUNTIME_FIELDS, RUNTIME_TYPE_FIELDS,
+    },
 };
 
 impl TaskFlag {
@@ -17,6 +25,7 @@ impl TaskFlag {
                 name: None,
                 group: None,
                 mat: args.mat,
+                selector: args.selector,
             });
         } else if let Some(name) = args.name {
             tasks.push(TaskFlag {
@@ -24,6 +33,7 @@ impl TaskFlag {
                 name: Some(name),
                 group: None,
                 mat: args.mat,
+                selector: args.selector,
             });
         } else if let Some(group) = args.group {
             tasks.push(TaskFlag {
@@ -31,6 +41,15 @@ impl TaskFlag {
                 name: None,
                 group: Some(group),
                 mat: args.mat,
+                selector: args.selector,
+            });
+        } else if let Some(selector) = args.selector {
+            tasks.push(TaskFlag {
+                id: 0,
+                name: None,
+                group: None,
+                mat: args.mat,
+                selector: Some(selector),
             });
         } else {
             return Err(Box::new(std::io::Error::new(
@@ -63,6 +82,7 @@ impl TaskFlag {
             "ini" => TaskFlag::from_ini(path),
             "toml" => TaskFlag::from_toml(path),
             "json" => TaskFlag::from_json(path),
+            "yaml" | "yml" => TaskFlag::from_yaml(path),
             _ => Err(Box::new(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 format!("Invalid file extension: {}", ext),
@@ -76,16 +96,9 @@ impl TaskFlag {
         let mut tasks = Vec::new();
 
         for section in ini.sections() {
+            // 未配置 id 时按名称查找任务
             let id = match ini.getint(section.as_str(), "id") {
-                Ok(id) => match id {
-                    Some(id) => id,
-                    None => {
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "Invalid config file, id is none",
-                        )));
-                    }
-                },
+                Ok(id) => id.unwrap_or(0),
                 Err(_) => {
                     return Err(Box::new(std::io::Error::new(
                         std::io::ErrorKind::Other,
@@ -93,39 +106,55 @@ impl TaskFlag {
                     )));
                 }
             };
-            tasks.push(TaskFlag::new(id));
+            tasks.push(TaskFlag {
+                id,
+                name: ini.get(section.as_str(), "name"),
+                group: None,
+                mat: false,
+                selector: None,
+            });
         }
 
         Ok(tasks)
     }
 
     pub fn from_toml(path: &Path) -> Result<Vec<TaskFlag>, Box<dyn Error>> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
         let mut tasks = Vec::new();
-        for i in toml::from_str::<Tasks>(&contents)?.task {
+        for i in Task::from_toml(path, None)?.task {
             tasks.push(TaskFlag {
                 id: i.id,
                 name: Some(i.name),
                 group: i.group,
                 mat: false,
+                selector: None,
             });
         }
         Ok(tasks)
     }
 
     pub fn from_json(path: &Path) -> Result<Vec<TaskFlag>, Box<dyn Error>> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
         let mut tasks = Vec::new();
-        for i in Task::deserialize(&contents)? {
+        for i in Task::from_json(path, None)?.task {
             tasks.push(TaskFlag {
                 id: i.id,
                 name: Some(i.name),
                 group: i.group,
                 mat: false,
+                selector: None,
+            });
+        }
+        Ok(tasks)
+    }
+
+    pub fn from_yaml(path: &Path) -> Result<Vec<TaskFlag>, Box<dyn Error>> {
+        let mut tasks = Vec::new();
+        for i in Task::from_yaml(path, None)?.task {
+            tasks.push(TaskFlag {
+                id: i.id,
+                name: Some(i.name),
+                group: i.group,
+                mat: false,
+                selector: None,
             });
         }
         Ok(tasks)
@@ -134,16 +163,12 @@ impl TaskFlag {
 
 impl Task {
     pub fn from_args(args: AddArgs) -> Result<Tasks, Box<dyn Error>> {
-        let mut task = Task::default();
-
-        if let Some(name) = args.name {
-            task.name = name;
-        } else {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Task name is none",
-            )));
-        }
+        // 未指定名称时由守护进程生成
+        let mut task = Task {
+            name: args.name.unwrap_or_default(),
+            group: args.group,
+            ..Default::default()
+        };
 
         if let Some(command) = args.command {
             task.command = command;
@@ -160,6 +185,17 @@ impl Task {
             task.args = Vec::new();
         }
 
+        for label in args.labels.unwrap_or_default() {
+            match label.split_once('=') {
+                Some((key, value)) => {
+                    task.labels.insert(key.to_string(), value.to_string());
+                }
+                None => {
+                    return Err(format!("Invalid label: {}, expected KEY=VALUE", label).into());
+                }
+            }
+        }
+
         task.dir = args.dir;
 
         if args.stdin {
@@ -173,7 +209,13 @@ impl Task {
         Ok(tasks)
     }
 
-    pub fn from_file(path: &Path) -> Result<Tasks, Box<dyn Error>> {
+    /// Tasks of task config file
+    ///
+    /// # params
+    ///
+    /// - `path`: task config file
+    /// - `profile`: profile applied to tasks, INI files do not support profiles
+    pub fn from_file(path: &Path, profile: Option<&str>) -> Result<Tasks, Box<dyn Error>> {
         let ext = match path.extension() {
             Some(ext) => match ext.to_str() {
                 Some(ext) => ext,
@@ -192,9 +234,16 @@ impl Task {
             }
         };
         match ext {
+            // INI 文件不支持 profile，选择了 profile 时报错而不是忽略
+            "ini" if profile.is_some() => Err(format!(
+                "Profiles are not supported in INI task files: {}",
+                path.display()
+            )
+            .into()),
             "ini" => Task::from_ini(path),
-            "toml" => Task::from_toml(path),
-            "json" => Task::from_json(path),
+            "toml" => Task::from_toml(path, profile),
+            "json" => Task::from_json(path, profile),
+            "yaml" | "yml" => Task::from_yaml(path, profile),
             _ => Err(Box::new(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 format!("Invalid file extension: {}", ext),
@@ -202,22 +251,99 @@ impl Task {
         }
     }
 
+    /// Keys read by `Task::from_ini`, other keys are rejected
+    pub const INI_KEYS: [&str; 59] = [
+        "id",
+        "name",
+        "command",
+        "args",
+        "group",
+        "labels",
+        "dir",
+        "env",
+        "env_file",
+        "inherit_env",
+        "stdin",
+        "stdout",
+        "stderr",
+        "rlimit_nofile",
+        "rlimit_nproc",
+        "rlimit_core",
+        "rlimit_as",
+        "rlimit_cpu",
+        "nice",
+        "ionice_class",
+        "ionice_level",
+        "oom_score_adj",
+        "umask",
+        "cpu_affinity",
+        "watch",
+        "watch_include",
+        "watch_exclude",
+        "watch_debounce",
+        "watch_ignore_starting",
+        "user",
+        "user_group",
+        "supplementary_groups",
+        "instances",
+        "port",
+        "concurrency_policy",
+        "max_concurrent_runs",
+        "timeout",
+        "stop_signal",
+        "kill_timeout",
+        "retry_on_timeout",
+        "retries",
+        "retry_delay",
+        "retry_backoff",
+        "ttl_after_finished",
+        "task_type",
+        "year",
+        "month",
+        "day",
+        "hour",
+        "minute",
+        "second",
+        "max_restart",
+        "at",
+        "after",
+        "started_after",
+        "interval",
+        "sync",
+        "jitter",
+        "align",
+    ];
+
     pub fn from_ini(path: &Path) -> Result<Tasks, Box<dyn Error>> {
         let mut ini = Ini::new();
         ini.load(&path)?;
         let mut tasks = Vec::new();
+        // 未配置工作目录时使用任务文件所在的目录，与执行命令的目录无关
+        let base = std::fs::canonicalize(path)?
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string());
 
         for section in ini.sections() {
             let section = section.as_str();
+            if section == "profile" || section.starts_with("profile.") {
+                return Err(format!(
+                    "Profiles are not supported in INI task files, found section [{}]",
+                    section
+                )
+                .into());
+            }
+            if let Some(keys) = ini.get_map_ref().get(section) {
+                if let Some(key) = keys.keys().find(|k| !Self::INI_KEYS.contains(&k.as_str())) {
+                    return Err(format!(
+                        "Invalid config file, unknown key `{}` in section [{}]",
+                        key, section
+                    )
+                    .into());
+                }
+            }
             let mut task = Task::default();
-            task.id = if let Some(id) = ini.getint(section, "id")? {
-                id
-            } else {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Invalid config file, id is none",
-                )));
-            };
+            // 未配置 id 时由守护进程分配
+            task.id = ini.getint(section, "id")?.unwrap_or(0);
             task.name = if let Some(name) = ini.get(section, "name") {
                 name
             } else {
@@ -241,19 +367,131 @@ impl Task {
                 .map(|s| s.to_string())
                 .collect();
             task.group = ini.get(section, "group");
-            task.dir = ini.get(section, "dir");
-            if task.dir.is_none() {
-                task.dir = Some(std::env::current_dir()?.to_str().unwrap().to_string());
-            }
-            for env in ini.get(section, "env").unwrap_or(String::new()).split(" ") {
-                let kv: Vec<&str> = env.split("=").collect();
-                if kv.len() == 2 {
-                    task.env.insert(kv[0].to_string(), kv[1].to_string());
+            for label in split_words(&ini.get(section, "labels").unwrap_or_default())? {
+                match label.split_once('=') {
+                    Some((key, value)) => {
+                        task.labels.insert(key.to_string(), value.to_string());
+                    }
+                    None => {
+                        return Err(format!("Invalid label: {}, expected KEY=VALUE", label).into());
+                    }
+                }
+            }
+            task.dir = ini.get(section, "dir").or(base.clone());
+            for env in split_words(&ini.get(section, "env").unwrap_or_default())? {
+                match env.split_once('=') {
+                    Some((key, value)) => {
+                        task.env.insert(key.to_string(), value.to_string());
+                    }
+                    None => {
+                        return Err(format!("Invalid env: {}, expected KEY=VALUE", env).into());
+                    }
                 }
             }
+            if let Some(files) = ini.get(section, "env_file") {
+                task.env_file = split_words(&files)?;
+            }
+            if let Some(inherit) = ini.get(section, "inherit_env") {
+                task.inherit_env = Some(match inherit.to_lowercase().as_str() {
+                    "true" => InheritEnv::All(true),
+                    "false" => InheritEnv::All(false),
+                    _ => InheritEnv::Only(
+                        inherit
+                            .split(|c: char| c == ',' || c.is_whitespace())
+                            .filter(|k| !k.is_empty())
+                            .map(|k| k.to_string())
+                            .collect(),
+                    ),
+                });
+            }
             task.stdin = ini.getbool(section, "stdin")?;
             task.stdout = ini.get(section, "stdout");
             task.stderr = ini.get(section, "stderr");
+
+            let rlimits = Rlimits {
+                nofile: ini.getuint(section, "rlimit_nofile")?,
+                nproc: ini.getuint(section, "rlimit_nproc")?,
+                core: ini.getuint(section, "rlimit_core")?,
+                address_space: ini.getuint(section, "rlimit_as")?,
+                cpu: ini.getuint(section, "rlimit_cpu")?,
+            };
+            if rlimits.nofile.is_some()
+                || rlimits.nproc.is_some()
+                || rlimits.core.is_some()
+                || rlimits.address_space.is_some()
+                || rlimits.cpu.is_some()
+            {
+                task.rlimits = Some(rlimits);
+            }
+            if let Some(nice) = ini.getint(section, "nice")? {
+                task.nice =
+                    Some(i32::try_from(nice).map_err(|_| format!("Invalid nice: {}", nice))?);
+            }
+            if let Some(class) = ini.get(section, "ionice_class") {
+                let level = match ini.getuint(section, "ionice_level")? {
+                    Some(level) => Some(
+                        u32::try_from(level)
+                            .map_err(|_| format!("Invalid ionice level: {}", level))?,
+                    ),
+                    None => None,
+                };
+                task.ionice = Some(Ionice { class, level });
+            }
+            if let Some(adj) = ini.getint(section, "oom_score_adj")? {
+                task.oom_score_adj = Some(
+                    i32::try_from(adj).map_err(|_| format!("Invalid oom_score_adj: {}", adj))?,
+                );
+            }
+            task.umask = ini.get(section, "umask");
+            if let Some(cpus) = ini.get(section, "cpu_affinity") {
+                let mut affinity = Vec::new();
+                for cpu in cpus.split_whitespace() {
+                    affinity.push(cpu.parse::<usize>()?);
+                }
+                task.cpu_affinity = Some(affinity);
+            }
+            if let Some(paths) = ini.get(section, "watch") {
+                task.watch = Some(Watch {
+                    paths: split_words(&paths)?,
+                    include: split_words(&ini.get(section, "watch_include").unwrap_or_default())?,
+                    exclude: split_words(&ini.get(section, "watch_exclude").unwrap_or_default())?,
+                    debounce: ini.getuint(section, "watch_debounce")?,
+                    ignore_starting: ini.getuint(section, "watch_ignore_starting")?,
+                });
+            }
+            task.user = ini.get(section, "user");
+            task.user_group = ini.get(section, "user_group");
+            if let Some(groups) = ini.get(section, "supplementary_groups") {
+                task.supplementary_groups =
+                    Some(groups.split_whitespace().map(|g| g.to_string()).collect());
+            }
+            task.instances = ini.getuint(section, "instances")?.map(|n| n as u32);
+            if let Some(port) = ini.getuint(section, "port")? {
+                task.port =
+                    Some(u16::try_from(port).map_err(|_| format!("Invalid port: {}", port))?);
+            }
+            if let Some(policy) = ini.get(section, "concurrency_policy") {
+                task.concurrency_policy = Some(
+                    ConcurrencyPolicy::parse(&policy)
+                        .ok_or_else(|| format!("Invalid concurrency policy: {}", policy))?,
+                );
+            }
+            task.max_concurrent_runs = ini
+                .getuint(section, "max_concurrent_runs")?
+                .map(|n| n as u32);
+            task.timeout = ini.getuint(section, "timeout")?;
+            if let Some(signal) = ini.get(section, "stop_signal") {
+                if parse_signal(&signal).is_none() {
+                    return Err(format!("Invalid stop signal: {}", signal).into());
+                }
+                task.stop_signal = Some(signal);
+            }
+            task.kill_timeout = ini.getuint(section, "kill_timeout")?;
+            task.retry_on_timeout = ini.getuint(section, "retry_on_timeout")?.map(|n| n as u32);
+            task.retries = ini.getuint(section, "retries")?.map(|n| n as u32);
+            task.retry_delay = ini.getuint(section, "retry_delay")?;
+            task.retry_backoff = ini.getfloat(section, "retry_backoff")?;
+            task.ttl_after_finished = ini.getuint(section, "ttl_after_finished")?;
             task.status = Some("added".to_string());
 
             let task_type = ini.get(section, "task_type").unwrap_or("none".to_string());
@@ -335,6 +573,19 @@ impl Task {
                         has_restart: 0,
                         started_at: 0,
                         stopped_at: 0,
+                        watch_restart: 0,
+                        last_change: None,
+                    })
+                }
+                "once" => {
+                    let at = ini.get(section, "at");
+                    if let Some(at) = &at {
+                        OnceTask::parse_at(at)?;
+                    }
+                    TaskType::Once(OnceTask {
+                        at,
+                        after: ini.getuint(section, "after")?,
+                        run_at: 0,
                     })
                 }
                 "periodic" => {
@@ -343,6 +594,10 @@ impl Task {
                         interval: 60,
                         last_run: 0,
                         sync: true,
+                        jitter: 0,
+                        align: false,
+                        next_run: 0,
+                        slot: 0,
                     };
                     if let Some(started_after) = ini.getint(section, "started_after")? {
                         if started_after < 0 {
@@ -365,6 +620,12 @@ impl Task {
                     if let Some(sync) = ini.getbool(section, "sync")? {
                         tt.sync = sync;
                     }
+                    if let Some(jitter) = ini.getuint(section, "jitter")? {
+                        tt.jitter = jitter;
+                    }
+                    if let Some(align) = ini.getbool(section, "align")? {
+                        tt.align = align;
+                    }
                     TaskType::Periodic(tt)
                 }
                 _ => {
@@ -381,23 +642,353 @@ impl Task {
         Ok(Tasks { task: tasks })
     }
 
-    pub fn from_toml(path: &Path) -> Result<Tasks, Box<dyn Error>> {
+    pub fn from_toml(path: &Path, profile: Option<&str>) -> Result<Tasks, Box<dyn Error>> {
         let mut file = File::open(path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        let config: Tasks = toml::from_str(&contents)?;
-        Ok(config)
+        let root = serde_json::to_value(toml::from_str::<toml::Table>(&contents)?)?;
+        let mut sections = Sections::default();
+        sections.read(root)?;
+        sections.tasks(profile)
     }
 
-    pub fn from_json(path: &Path) -> Result<Tasks, Box<dyn Error>> {
+    /// Tasks of JSON file, an array of tasks or a table with `task`, `template` and
+    /// `profile` sections
+    pub fn from_json(path: &Path, profile: Option<&str>) -> Result<Tasks, Box<dyn Error>> {
         let mut file = File::open(path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        let tasks = Task::deserialize(&contents)?;
-        Ok(Tasks { task: tasks })
+        let mut sections = Sections::default();
+        sections.read(serde_json::from_str(&contents)?)?;
+        sections.tasks(profile)
+    }
+
+    /// Tasks of YAML file, documents separated by `---` are merged, templates and
+    /// profiles can be used in all documents
+    pub fn from_yaml(path: &Path, profile: Option<&str>) -> Result<Tasks, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let mut sections = Sections::default();
+        for document in serde_yaml::Deserializer::from_str(&contents) {
+            let value = yaml_to_json(serde_yaml::Value::deserialize(document)?)?;
+            // 空文档跳过
+            if !value.is_null() {
+                sections.read(value)?;
+            }
+        }
+        sections.tasks(profile)
     }
 }
 
+/// Sections of task file, which is a table with `template`, `task` and `profile`
+/// sections, or an array of tasks
+#[derive(Default)]
+pub struct Sections {
+    pub template: Vec<Value>,
+    pub task: Vec<Value>,
+    pub profile: Map<String, Value>,
+}
+
+impl Sections {
+    /// Read sections of a document, documents of YAML file are read one by one
+    pub fn read(&mut self, root: Value) -> Result<(), Box<dyn Error>> {
+        let mut root = match root {
+            Value::Array(tasks) => {
+                self.task.extend(tasks);
+                return Ok(());
+            }
+            Value::Object(root) => root,
+            _ => return Err("Invalid config file, expected a table of tasks".into()),
+        };
+        for key in ["template", "task"] {
+            match root.remove(key) {
+                Some(Value::Array(values)) if key == "task" => self.task.extend(values),
+                Some(Value::Array(values)) => self.template.extend(values),
+                Some(_) => {
+                    return Err(format!("Invalid config file, {} is not an array", key).into())
+                }
+                None => {}
+            }
+        }
+        match root.remove("profile") {
+            Some(Value::Object(profiles)) => {
+                for (name, profile) in profiles {
+                    self.add_profile(name, profile)?;
+                }
+            }
+            Some(_) => return Err("Invalid config file, profile is not a table".into()),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Add profile, profiles of the same name in different documents are merged
+    fn add_profile(&mut self, name: String, profile: Value) -> Result<(), Box<dyn Error>> {
+        let Value::Object(profile) = profile else {
+            return Err(format!("Profile [{}] is not a table", name).into());
+        };
+        let Value::Object(existing) = self
+            .profile
+            .entry(name)
+            .or_insert_with(|| Value::Object(Map::new()))
+        else {
+            return Ok(());
+        };
+        for (key, value) in profile {
+            match (existing.get_mut(&key), value) {
+                (Some(Value::Array(tasks)), Value::Array(value)) if key == "task" => {
+                    tasks.extend(value)
+                }
+                (_, value) => {
+                    existing.insert(key, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Profile of name, `None` if no profile is selected or the file has no profiles
+    ///
+    /// # params
+    ///
+    /// - `name`: name of selected profile
+    pub fn select(&self, name: Option<&str>) -> Result<Option<(&str, &Value)>, Box<dyn Error>> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        if self.profile.is_empty() {
+            return Ok(None);
+        }
+        match self.profile.get_key_value(name) {
+            Some((name, profile)) => Ok(Some((name.as_str(), profile))),
+            None => Err(format!(
+                "Profile [{}] not found, defined profiles: {}",
+                name,
+                self.profile.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+            .into()),
+        }
+    }
+
+    /// Tasks with templates and profile applied
+    ///
+    /// # params
+    ///
+    /// - `profile`: name of selected profile
+    fn tasks(self, profile: Option<&str>) -> Result<Tasks, Box<dyn Error>> {
+        let profile = self.select(profile)?;
+        let mut resolved = Vec::new();
+        for (index, task) in self.task.iter().enumerate() {
+            let task = extend(&self.template, profile, task.clone())
+                .map_err(|e| format!("task[{}]: {}", index, e))?;
+            let task: Task = serde_path_to_error::deserialize(task).map_err(|e| {
+                if e.path().iter().next().is_none() {
+                    format!("task[{}]: {}", index, e.inner())
+                } else {
+                    format!("task[{}].{}: {}", index, e.path(), e.inner())
+                }
+            })?;
+            resolved.push(task);
+        }
+        Ok(Tasks { task: resolved })
+    }
+}
+
+/// Task with template and profile applied and `{{name}}`, `{{group}}` and `{{id}}` replaced
+///
+/// Keys of task override keys of template, keys of profile override keys of task,
+/// `env` and `labels` are merged
+///
+/// # params
+///
+/// - `templates`: templates of task file, found by `name`
+/// - `profile`: name and overlay of selected profile
+/// - `task`: task, extends a template by `extends`
+pub fn extend(
+    templates: &[Value],
+    profile: Option<(&str, &Value)>,
+    task: Value,
+) -> Result<Value, Box<dyn Error>> {
+    let mut task = inherit(templates, task, &mut Vec::new())?;
+    if let Some((name, profile)) = profile {
+        task = overlay(name, profile, task)?;
+    }
+    interpolate(&mut task)?;
+    Ok(task)
+}
+
+/// Task patched by profile, keys of profile apply to all tasks, keys of `task` entries
+/// of profile apply to the task of the same name
+fn overlay(name: &str, profile: &Value, task: Value) -> Result<Value, Box<dyn Error>> {
+    let (Value::Object(profile), Value::Object(mut task)) = (profile, task) else {
+        return Err(format!("Profile [{}] is not a table", name).into());
+    };
+    let mut patches = profile.clone();
+    let entries = match patches.remove("task") {
+        Some(Value::Array(entries)) => entries,
+        Some(_) => return Err(format!("Profile [{}] task is not an array", name).into()),
+        None => Vec::new(),
+    };
+    merge(&mut task, patches);
+    for entry in entries {
+        let Value::Object(mut entry) = entry else {
+            return Err(format!("Profile [{}] task is not a table", name).into());
+        };
+        // 按名称匹配任务，名称不被覆盖
+        if entry.remove("name") == task.get("name").cloned() {
+            merge(&mut task, entry);
+        }
+    }
+    task.insert("profile".to_string(), Value::String(name.to_string()));
+    Ok(Value::Object(task))
+}
+
+/// Merge keys of overlay into base, `env` and `labels` are merged, other keys are replaced
+fn merge(base: &mut Map<String, Value>, overlay: Map<String, Value>) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Object(inherited)), Value::Object(value))
+                if key == "env" || key == "labels" =>
+            {
+                inherited.extend(value);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn inherit(
+    templates: &[Value],
+    value: Value,
+    chain: &mut Vec<String>,
+) -> Result<Value, Box<dyn Error>> {
+    let mut map = match value {
+        Value::Object(map) => map,
+        value => return Ok(value),
+    };
+    let parent = match map.remove("extends") {
+        Some(Value::String(parent)) => parent,
+        Some(_) => return Err("extends is not a template name".into()),
+        None => return Ok(Value::Object(map)),
+    };
+    if chain.contains(&parent) {
+        return Err(format!("Template cycle: {} -> {}", chain.join(" -> "), parent).into());
+    }
+    let template = templates
+        .iter()
+        .find(|t| t.get("name").and_then(Value::as_str) == Some(parent.as_str()))
+        .ok_or_else(|| format!("Template [{}] not found", parent))?;
+    chain.push(parent.clone());
+    let mut base = match inherit(templates, template.clone(), chain)? {
+        Value::Object(base) => base,
+        _ => return Err(format!("Template [{}] is not a table", parent).into()),
+    };
+    // 模板名称不是任务名称
+    base.remove("name");
+    merge(&mut base, map);
+    Ok(Value::Object(base))
+}
+
+/// Replace variables in string values of task, `{{name}}` is replaced first so that
+/// variables in name are replaced too
+fn interpolate(task: &mut Value) -> Result<(), Box<dyn Error>> {
+    let variables = [
+        (
+            "name",
+            task.get("name")
+                .and_then(Value::as_str)
+                .map(|n| n.to_string()),
+        ),
+        (
+            "group",
+            task.get("group")
+                .and_then(Value::as_str)
+                .map(|g| g.to_string()),
+        ),
+        (
+            "id",
+            task.get("id")
+                .and_then(Value::as_i64)
+                .filter(|id| *id > 0)
+                .map(|id| id.to_string()),
+        ),
+    ];
+    fn replace(value: &mut Value, variables: &[(&str, Option<String>)]) -> Result<(), String> {
+        match value {
+            Value::String(s) => {
+                for (variable, replacement) in variables {
+                    let pattern = format!("{{{{{}}}}}", variable);
+                    if s.contains(&pattern) {
+                        match replacement {
+                            Some(replacement) => *s = s.replace(&pattern, replacement),
+                            None => {
+                                return Err(format!(
+                                    "{} is used but task has no {}",
+                                    pattern, variable
+                                ))
+                            }
+                        }
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    replace(value, variables)?;
+                }
+            }
+            Value::Object(map) => {
+                for value in map.values_mut() {
+                    replace(value, variables)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    replace(task, &variables)?;
+    Ok(())
+}
+
+/// Convert YAML value to JSON value, so that enums are written as maps like
+/// in TOML and JSON task files, e.g. `task_type: {Async: {}}`, tags such as
+/// `task_type: !Async {}` are converted to the same form
+pub fn yaml_to_json(value: serde_yaml::Value) -> Result<serde_json::Value, Box<dyn Error>> {
+    Ok(match value {
+        serde_yaml::Value::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let mut map = serde_json::Map::new();
+            map.insert(
+                tag.trim_start_matches('!').to_string(),
+                yaml_to_json(tagged.value)?,
+            );
+            serde_json::Value::Object(map)
+        }
+        serde_yaml::Value::Sequence(values) => serde_json::Value::Array(
+            values
+                .into_iter()
+                .map(yaml_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut map = serde_json::Map::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    serde_yaml::Value::String(key) => key,
+                    serde_yaml::Value::Number(key) => key.to_string(),
+                    serde_yaml::Value::Bool(key) => key.to_string(),
+                    _ => return Err("YAML mapping key must be a string".into()),
+                };
+                map.insert(key, yaml_to_json(value)?);
+            }
+            serde_json::Value::Object(map)
+        }
+        value => serde_json::to_value(value)?,
+    })
+}
+
 impl Task {
     pub fn deserialize(s: &str) -> Result<Vec<Task>, Box<dyn Error>> {
         let tasks: Vec<Task> = serde_json::from_str(s)?;
@@ -411,14 +1002,170 @@ impl Task {
 }
 
 impl Task {
+    /// Hash of task definition, id and fields changed by daemon are left out, tasks are
+    /// hashed after paths are normalised by daemon
+    pub fn definition_hash(&self) -> Result<String, Box<dyn Error>> {
+        let mut value = serde_json::to_value(self)?;
+        if let Value::Object(map) = &mut value {
+            // id 由守护进程分配，导出的任务文件带有 id
+            map.remove("id");
+            for field in RUNTIME_FIELDS {
+                map.remove(field);
+            }
+            if let Some(Value::Object(tt)) = map.get_mut("task_type") {
+                for fields in tt.values_mut() {
+                    if let Value::Object(fields) = fields {
+                        for field in RUNTIME_TYPE_FIELDS {
+                            fields.remove(field);
+                        }
+                    }
+                }
+            }
+        }
+        // serde_json::Value 的键有序，结果与 HashMap 遍历顺序无关
+        // FNV-1a 64
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in value.to_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        Ok(format!("{:016x}", hash))
+    }
+}
+
+impl OnceTask {
+    /// Parse local time of once task, RFC 3339 time with offset is also accepted
+    ///
+    /// # params
+    ///
+    /// - `at`: time, e.g. "2026-11-01T03:00" or "2026-11-01 03:00:00"
+    pub fn parse_at(at: &str) -> Result<u64, String> {
+        use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+
+        if let Ok(t) = DateTime::parse_from_rfc3339(at) {
+            return Ok(t.timestamp().max(0) as u64);
+        }
+        for format in [
+            "%Y-%m-%dT%H:%M:%S",
+            "%Y-%m-%dT%H:%M",
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%d %H:%M",
+        ] {
+            if let Ok(t) = NaiveDateTime::parse_from_str(at, format) {
+                return match Local.from_local_datetime(&t).earliest() {
+                    Some(t) => Ok(t.timestamp().max(0) as u64),
+                    None => Err(format!(
+                        "Invalid at: {}, time not exists in local timezone",
+                        at
+                    )),
+                };
+            }
+        }
+        Err(format!("Invalid at: {}", at))
+    }
+}
+
+impl Task {
+    /// Concurrency policy of task, the default follows `sync` of periodic task
+    pub fn policy(&self) -> ConcurrencyPolicy {
+        match (&self.concurrency_policy, &self.task_type) {
+            (Some(policy), _) => *policy,
+            (None, TaskType::Periodic(tt)) if tt.sync => ConcurrencyPolicy::Allow,
+            _ => ConcurrencyPolicy::Forbid,
+        }
+    }
+}
+
+impl Task {
+    /// Environment variables of task process
+    ///
+    /// Inherited daemon environment, user variables, env files, task env and
+    /// watchmen variables are merged in order, later ones override earlier ones.
+    ///
+    /// # params
+    ///
+    /// - `credentials`: user the task runs as, sets HOME, USER and LOGNAME
+    pub fn environment(
+        &self,
+        credentials: Option<&Credentials>,
+    ) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        let mut envs: HashMap<String, String> = match &self.inherit_env {
+            None | Some(InheritEnv::All(true)) => std::env::vars().collect(),
+            Some(InheritEnv::All(false)) => HashMap::new(),
+            Some(InheritEnv::Only(keys)) => std::env::vars()
+                .filter(|(key, _)| keys.contains(key))
+                .collect(),
+        };
+
+        if let Some(credentials) = credentials {
+            if let Some(home) = &credentials.home {
+                envs.insert("HOME".to_string(), home.clone());
+            }
+            envs.insert("USER".to_string(), credentials.name.clone());
+            envs.insert("LOGNAME".to_string(), credentials.name.clone());
+        }
+
+        for file in &self.env_file {
+            // 以 - 开头的文件不存在时忽略
+            let (file, optional) = match file.strip_prefix('-') {
+                Some(file) => (file, true),
+                None => (file.as_str(), false),
+            };
+            let mut path = get_with_home_path(file);
+            if path.is_relative() {
+                if let Some(dir) = &self.dir {
+                    path = Path::new(dir).join(path);
+                }
+            }
+            if !path.is_file() {
+                if optional {
+                    continue;
+                }
+                return Err(format!("Env file [{}] not exists", path.display()).into());
+            }
+            for (key, value, expandable) in parse_env_file(&path)? {
+                let value = if expandable {
+                    expand(&value, &mut |name: &str| envs.get(name).cloned())
+                } else {
+                    value
+                };
+                envs.insert(key, value);
+            }
+        }
+
+        let resolved = resolve(&self.env, &envs);
+        envs.extend(resolved);
+
+        let restart = match &self.task_type {
+            TaskType::Async(tt) => tt.has_restart,
+            _ => 0,
+        };
+        envs.insert("WATCHMEN_TASK_ID".to_string(), self.id.to_string());
+        envs.insert("WATCHMEN_TASK_NAME".to_string(), self.name.clone());
+        envs.insert("WATCHMEN_RESTART_COUNT".to_string(), restart.to_string());
+        if let Some(run_id) = &self.run_id {
+            envs.insert("WATCHMEN_RUN_ID".to_string(), run_id.clone());
+        }
+        if let Some(attempt) = self.attempt {
+            envs.insert("WATCHMEN_ATTEMPT".to_string(), attempt.to_string());
+        }
+        if let Some(instance) = self.instance {
+            envs.insert("WATCHMEN_INSTANCE".to_string(), instance.to_string());
+        }
+        if let Some(port) = self.port {
+            let port = port as u32 + self.instance.unwrap_or(0);
+            envs.insert("PORT".to_string(), port.to_string());
+        }
+        Ok(envs)
+    }
+
     pub async fn start(&self) -> Result<Child, Box<dyn Error>> {
+        let credentials = Credentials::from_task(self)?;
+        let envs = self.environment(credentials.as_ref())?;
         let mut command = Command::new(&self.command);
         let command = command.args(&self.args);
-        let command = command.envs(std::env::vars());
+        let command = command.env_clear().envs(envs);
         let mut command = command.kill_on_drop(false);
-        for (key, value) in &self.env {
-            command = command.env(key, value);
-        }
         if let Some(dir) = &self.dir {
             command = command.current_dir(&dir);
         }
@@ -462,6 +1209,20 @@ impl Task {
             command = command.stdin(Stdio::null());
         }
 
+        // 在 fork 之后、exec 之前设置进程属性，最后降低权限
+        let attributes = Attributes::from_task(self)?;
+        if !attributes.is_empty() || credentials.is_some() {
+            unsafe {
+                command = command.pre_exec(move || {
+                    attributes.apply()?;
+                    if let Some(credentials) = &credentials {
+                        credentials.apply()?;
+                    }
+                    Ok(())
+                });
+            }
+        }
+
         let child = command.spawn()?;
 
         Ok(child)
@@ -481,6 +1242,210 @@ impl TaskFlag {
             name: Some(String::new()),
             group: None,
             mat: false,
+            selector: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_definition_hash() {
+        let task = Task {
+            name: "web".to_string(),
+            command: "sleep".to_string(),
+            args: vec!["100".to_string()],
+            task_type: TaskType::Periodic(PeriodicTask {
+                started_after: 0,
+                interval: 60,
+                last_run: 0,
+                sync: false,
+                jitter: 0,
+                align: false,
+                next_run: 0,
+                slot: 0,
+            }),
+            ..Task::default()
+        };
+        let hash = task.definition_hash().unwrap();
+
+        // 守护进程运行时修改的字段不影响哈希
+        let mut running = task.clone();
+        running.id = 3;
+        running.status = Some("executing".to_string());
+        running.pid = Some(42);
+        running.created_at += 10;
+        running.source = Some("/etc/watchmen/tasks.d/web.toml".to_string());
+        if let TaskType::Periodic(tt) = &mut running.task_type {
+            tt.last_run = 100;
+            tt.next_run = 160;
+            tt.slot = 150;
         }
+        assert_eq!(running.definition_hash().unwrap(), hash);
+
+        let mut changed = task.clone();
+        changed.args.push("-v".to_string());
+        assert_ne!(changed.definition_hash().unwrap(), hash);
+        let mut changed = task;
+        if let TaskType::Periodic(tt) = &mut changed.task_type {
+            tt.interval = 30;
+        }
+        assert_ne!(changed.definition_hash().unwrap(), hash);
+    }
+
+    fn templates() -> Vec<Value> {
+        vec![
+            json!({
+                "name": "base",
+                "command": "python",
+                "dir": "/srv/base",
+                "env": { "LANG": "C", "LEVEL": "info" },
+                "labels": { "tier": "web" },
+                "task_type": { "Async": {} }
+            }),
+            json!({
+                "name": "api",
+                "extends": "base",
+                "args": ["{{name}}.py"],
+                "env": { "LEVEL": "debug" }
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_extend_templates() {
+        let task = json!({
+            "name": "orders",
+            "extends": "api",
+            "dir": "/srv/orders",
+            "env": { "PORT": "8000" },
+            "labels": { "app": "orders" }
+        });
+        let task = extend(&templates(), None, task).unwrap();
+        // 任务覆盖模板，子模板覆盖父模板，env 和 labels 合并
+        assert_eq!(
+            task,
+            json!({
+                "name": "orders",
+                "command": "python",
+                "args": ["orders.py"],
+                "dir": "/srv/orders",
+                "env": { "LANG": "C", "LEVEL": "debug", "PORT": "8000" },
+                "labels": { "tier": "web", "app": "orders" },
+                "task_type": { "Async": {} }
+            })
+        );
+    }
+
+    #[test]
+    fn test_extend_errors() {
+        let err = |templates: &[Value], task: Value| {
+            extend(templates, None, task).unwrap_err().to_string()
+        };
+        assert_eq!(
+            err(&templates(), json!({ "name": "a", "extends": "web" })),
+            "Template [web] not found"
+        );
+        assert_eq!(
+            err(&templates(), json!({ "name": "a", "extends": 1 })),
+            "extends is not a template name"
+        );
+        let cycle = vec![
+            json!({ "name": "x", "extends": "y" }),
+            json!({ "name": "y", "extends": "x" }),
+        ];
+        assert_eq!(
+            err(&cycle, json!({ "name": "a", "extends": "x" })),
+            "Template cycle: x -> y -> x"
+        );
+        // 没有 id 的任务不能使用 {{id}}
+        assert_eq!(
+            err(&[], json!({ "name": "a", "args": ["--id={{id}}"] })),
+            "{{id}} is used but task has no id"
+        );
+        assert_eq!(
+            err(
+                &[],
+                json!({ "name": "a", "stdout": "/var/log/{{group}}.log" })
+            ),
+            "{{group}} is used but task has no group"
+        );
+    }
+
+    #[test]
+    fn test_extend_interpolate() {
+        let task = json!({
+            "id": 7,
+            "name": "{{group}}-worker",
+            "group": "jobs",
+            "args": ["--id={{id}}", "--name={{name}}"],
+            "env": { "LOG": "/var/log/{{name}}.log" }
+        });
+        let task = extend(&[], None, task).unwrap();
+        assert_eq!(task["args"], json!(["--id=7", "--name=jobs-worker"]));
+        assert_eq!(task["env"]["LOG"], "/var/log/jobs-worker.log");
+        // 名称中的变量同样被替换
+        assert_eq!(task["name"], "jobs-worker");
+    }
+
+    #[test]
+    fn test_extend_profile() {
+        let profile = json!({
+            "env": { "LEVEL": "warn" },
+            "task": [{ "name": "orders", "args": ["--prod"] }]
+        });
+        let task = json!({ "name": "orders", "extends": "api" });
+        let task = extend(&templates(), Some(("prod", &profile)), task).unwrap();
+        assert_eq!(task["args"], json!(["--prod"]));
+        assert_eq!(task["env"], json!({ "LANG": "C", "LEVEL": "warn" }));
+        assert_eq!(task["profile"], "prod");
+
+        // 其他任务的条目不生效
+        let task = json!({ "name": "users", "extends": "api" });
+        let task = extend(&templates(), Some(("prod", &profile)), task).unwrap();
+        assert_eq!(task["args"], json!(["users.py"]));
+    }
+
+    #[test]
+    fn test_ini_profile() {
+        let dir = std::env::temp_dir().join(format!("watchmen-ini-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tasks.ini");
+        std::fs::write(
+            &path,
+            "[web]\nname = web\ncommand = true\ntask_type = async\n",
+        )
+        .unwrap();
+        assert_eq!(Task::from_file(&path, None).unwrap().task.len(), 1);
+        // INI 文件不支持 profile，选择或定义 profile 都报错
+        assert!(Task::from_file(&path, Some("prod")).is_err());
+        std::fs::write(
+            &path,
+            "[web]\nname = web\ncommand = true\ntask_type = async\n\n[profile.prod]\nenv = LEVEL=warn\n",
+        )
+        .unwrap();
+        assert!(Task::from_file(&path, None).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ini_unknown_key() {
+        let dir = std::env::temp_dir().join(format!("watchmen-ini-key-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tasks.ini");
+        std::fs::write(
+            &path,
+            "[web]\nname = web\ncommand = true\ntask_type = async\nretry = 3\n",
+        )
+        .unwrap();
+        let e = Task::from_file(&path, None).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Invalid config file, unknown key `retry` in section [web]"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
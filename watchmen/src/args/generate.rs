@@ -32,12 +32,29 @@ stderr = "$HOME/.watchmen/watchmen.stderr.log"
 pid = "$HOME/.watchmen/watchmen.pid"
 
 # The task config file name matching pattern
-# Default is `^.*\\.(toml|ini|json)$`
-mat = "^.*\\.(toml|ini|json)$"
+# Default is `^.*\\.(toml|ini|json|yaml|yml)$`
+mat = "^.*\\.(toml|ini|json|yaml|yml)$"
 
 # Tasks cache file, json format
 cache = "$HOME/.watchmen/cache.json"
 
+# Number of previous cache files kept as snapshots, used when the cache file is broken
+# Default is 3
+cache_snapshots = 3
+
+# Backend for tasks state: json (cache file) or sqlite (database, keeps run history)
+# Default is `json`
+backend = "json"
+
+# Sqlite database file, used when backend is sqlite
+# Default is `$HOME/.watchmen/watchmen.db`
+database = "$HOME/.watchmen/watchmen.db"
+
+# Directories of task config files loaded by the watchmen server, files are watched,
+# tasks are added, reloaded or removed when files are created, changed or deleted
+# Default is None
+include_dirs = ["$HOME/.watchmen/tasks.d"]
+
 # Monitor interval for rerun tasks, u64: second
 interval = 5
 
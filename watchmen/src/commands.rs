@@ -1,5 +1,9 @@
 // pub mod exit;
 pub mod add;
+pub mod apply;
+pub mod config;
+pub mod export;
+pub mod history;
 pub mod list;
 pub mod pause;
 pub mod reload;
@@ -7,8 +11,11 @@ pub mod remove;
 pub mod restart;
 pub mod resume;
 pub mod run;
+pub mod scale;
+pub mod schema;
 pub mod start;
 pub mod stop;
+pub mod validate;
 
 use crate::common::{
     arg::{AddArgs, Commands, FlagArgs},
@@ -33,6 +40,13 @@ pub async fn handle_exec(commands: Commands, config: Config) -> Result<(), Box<d
         Commands::Pause(args) => self::pause::pause(args, config).await?,
         Commands::Resume(args) => self::resume::resume(args, config).await?,
         Commands::List(args) => self::list::list(args, config).await?,
+        Commands::Scale(args) => self::scale::scale(args, config).await?,
+        Commands::History(args) => self::history::history(args, config).await?,
+        Commands::Apply(args) => self::apply::apply(args, config).await?,
+        Commands::Export(args) => self::export::export(args, config).await?,
+        Commands::Validate(args) => self::validate::validate(args, config).await?,
+        Commands::Schema(args) => self::schema::schema(args).await?,
+        Commands::Config(args) => self::config::config(args, config).await?,
     }
     Ok(())
 }
@@ -51,7 +65,7 @@ pub async fn taskflag_to_request(
             mat = matc;
         } else {
             // 最后使用默认参数
-            mat = String::from(r"^.*\.(toml|ini|json)$");
+            mat = String::from(r"^.*\.(toml|ini|json|yaml|yml)$");
         }
         let regex: Regex = Regex::new(&mat).unwrap();
         let mut matched_files = Vec::new();
@@ -61,45 +75,17 @@ pub async fn taskflag_to_request(
 
         for file in matched_files {
             let path = Path::new(&file);
-            if path.is_file() && path.extension().unwrap().to_str().unwrap() == "ini" {
-                let ts = TaskFlag::from_ini(path)?;
-                for tf in ts {
-                    tfs.push(tf);
-                }
-            } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "toml" {
-                let ts = TaskFlag::from_toml(path)?;
-                for tf in ts {
-                    tfs.push(tf);
-                }
-            } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "json" {
-                // tasks.push(Task::from_json(path)?);
-                // TODO: 读取 JSON 格式的配置文件
-            } else {
-                return Err(Box::from(format!(
-                    "File {} is not a TOML or INI or JSON file",
-                    path.to_str().unwrap()
-                )));
+            if path.is_file() {
+                tfs.extend(TaskFlag::from_file(path)?);
             }
         }
         tfs
     } else if let Some(file) = args.config {
         let path = Path::new(&file);
-        let ts = if path.is_file() && path.extension().unwrap().to_str().unwrap() == "ini" {
-            TaskFlag::from_ini(path)?
-        } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "toml" {
-            TaskFlag::from_toml(path)?
-        } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "json" {
-            // TODO: 读取 JSON 格式的配置文件
-            return Err(Box::from(format!(
-                "File {} is not a TOML or INI or JSON file",
-                path.to_str().unwrap()
-            )));
-        } else {
-            return Err(Box::from(format!(
-                "File {} is not a TOML or INI or JSON file",
-                path.to_str().unwrap()
-            )));
-        };
+        if !path.is_file() {
+            return Err(Box::from(format!("File {} not exists", file)));
+        }
+        let ts = TaskFlag::from_file(path)?;
         let mut tfs = Vec::new();
         for tf in ts {
             if args.name.is_some() && tf.name.is_some() {
@@ -132,7 +118,7 @@ pub async fn task_to_request(args: AddArgs, config: Config) -> Result<Vec<Task>,
             mat = matc;
         } else {
             // 最后使用默认参数
-            mat = String::from(r"^.*\.(toml|ini|json)$");
+            mat = String::from(r"^.*\.(toml|ini|json|yaml|yml)$");
         }
         let regex: Regex = Regex::new(&mat).unwrap();
         let mut matched_files = Vec::new();
@@ -142,9 +128,8 @@ pub async fn task_to_request(args: AddArgs, config: Config) -> Result<Vec<Task>,
 
         for file in matched_files {
             let path = Path::new(&file);
-            if path.is_file() && path.extension().unwrap().to_str().unwrap() == "ini" {
-                let ts = Task::from_ini(path)?;
-                for task in ts.task {
+            if path.is_file() {
+                for task in Task::from_file(path, args.profile.as_deref())?.task {
                     if let Some(name) = &args.name {
                         if &task.name != name {
                             continue;
@@ -152,45 +137,15 @@ pub async fn task_to_request(args: AddArgs, config: Config) -> Result<Vec<Task>,
                     }
                     tasks.push(task);
                 }
-            } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "toml" {
-                let ts = Task::from_toml(path)?;
-                for task in ts.task {
-                    if let Some(name) = &args.name {
-                        if &task.name != name {
-                            continue;
-                        }
-                    }
-                    tasks.push(task);
-                }
-            } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "json" {
-                // tasks.push(Task::from_json(path)?);
-                // TODO: 读取 JSON 格式的配置文件
-            } else {
-                return Err(Box::from(format!(
-                    "File [{}] is not a TOML or INI or JSON file",
-                    path.to_str().unwrap()
-                )));
             }
         }
         tasks
     } else if let Some(file) = args.config {
         let path = Path::new(&file);
-        let ts = if path.is_file() && path.extension().unwrap().to_str().unwrap() == "ini" {
-            Task::from_ini(path)?
-        } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "toml" {
-            Task::from_toml(path)?
-        } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "json" {
-            // TODO: 读取 JSON 格式的配置文件
-            return Err(Box::from(format!(
-                "File {} is not a TOML or INI or JSON file",
-                path.to_str().unwrap()
-            )));
-        } else {
-            return Err(Box::from(format!(
-                "File {} is not a TOML or INI or JSON file",
-                path.to_str().unwrap()
-            )));
-        };
+        if !path.is_file() {
+            return Err(Box::from(format!("File {} not exists", file)));
+        }
+        let ts = Task::from_file(path, args.profile.as_deref())?;
         ts.task
     } else {
         Task::from_args(args)?.task
@@ -0,0 +1,122 @@
+use crate::common::{
+    arg::ApplyArgs,
+    config::Config,
+    handle::{Change, Command, Data, Request, Response},
+    task::Task,
+};
+use colored::Colorize;
+use regex::Regex;
+use std::{error::Error, path::Path};
+
+use crate::{
+    engine::send,
+    utils::{print_result as pr, recursive_search_files},
+};
+
+pub async fn apply(args: ApplyArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    let files = if let Some(path) = args.path {
+        let mat;
+        if let Some(matc) = args.regex {
+            // 优先使用命令行参数
+            mat = matc;
+        } else if let Some(matc) = config.watchmen.mat.clone() {
+            // 其次使用配置文件参数
+            mat = matc;
+        } else {
+            // 最后使用默认参数
+            mat = String::from(r"^.*\.(toml|ini|json|yaml|yml)$");
+        }
+        let regex: Regex = Regex::new(&mat)?;
+        let mut matched_files = Vec::new();
+        recursive_search_files(&path, &regex, &mut matched_files);
+        matched_files.sort();
+        matched_files
+    } else if let Some(file) = args.config {
+        vec![file]
+    } else {
+        return Err(Box::from("Task config directory or file is required"));
+    };
+
+    let mut tasks = Vec::new();
+    for file in files {
+        let path = Path::new(&file);
+        if !path.is_file() {
+            return Err(Box::from(format!("File {} not exists", file)));
+        }
+        tasks.extend(Task::from_file(path, args.profile.as_deref())?.task);
+    }
+
+    let request = Request {
+        command: Command::Apply(tasks, args.prune, args.dry_run),
+    };
+    print_result(send(config, vec![request]).await?, args.dry_run).await;
+    Ok(())
+}
+
+/// Print changes like a plan, `+` add, `~` reload, `-` prune
+pub async fn print_result(res: Vec<Response>, dry_run: bool) {
+    for r in res {
+        let changes = match r.data {
+            Some(Data::Plan(changes)) => changes,
+            data => {
+                pr(vec![Response { data, ..r }]).await;
+                continue;
+            }
+        };
+        let (mut add, mut reload, mut prune, mut unchanged) = (0, 0, 0, 0);
+        for change in &changes {
+            let line = format!("[{}:{}]", change.id, change.name);
+            let line = match change.action.as_str() {
+                "add" => {
+                    add += 1;
+                    format!("  + {}", line).green()
+                }
+                "reload" => {
+                    reload += 1;
+                    format!("  ~ {}", line).yellow()
+                }
+                "prune" => {
+                    prune += 1;
+                    format!("  - {}", line).red()
+                }
+                _ => {
+                    unchanged += 1;
+                    format!("    {}", line).dimmed()
+                }
+            };
+            match result(change) {
+                Some(result) => println!("{}  {}", line, result),
+                None => println!("{}", line),
+            }
+        }
+        if dry_run {
+            println!(
+                "{} {} to add, {} to reload, {} to prune, {} unchanged.",
+                "Plan:".bold(),
+                add.to_string().green(),
+                reload.to_string().yellow(),
+                prune.to_string().red(),
+                unchanged,
+            );
+        } else {
+            println!(
+                "{} {} added, {} reloaded, {} pruned, {} unchanged.",
+                "Applied:".bold(),
+                add.to_string().green(),
+                reload.to_string().yellow(),
+                prune.to_string().red(),
+                unchanged,
+            );
+        }
+    }
+}
+
+fn result(change: &Change) -> Option<String> {
+    let r = change.result.as_ref()?;
+    let msg = r.data.clone().unwrap_or_default().to_string();
+    Some(match r.code {
+        10000 => msg.normal().to_string(),
+        40000 => msg.yellow().to_string(),
+        _ => msg.red().to_string(),
+    })
+}
@@ -0,0 +1,58 @@
+use crate::common::{
+    arg::{ConfigArgs, ConfigCommands},
+    config::{keys, Config, Key},
+};
+use std::error::Error;
+
+pub async fn config(args: ConfigArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    match args.command {
+        ConfigCommands::Show => show(config),
+    }
+}
+
+/// Print effective config in TOML, each value is followed by where it came from
+fn show(config: Config) -> Result<(), Box<dyn Error>> {
+    let table = match toml::Value::try_from(&config)? {
+        toml::Value::Table(table) => table,
+        _ => toml::Table::new(),
+    };
+    let keys = keys()?;
+    let mut section = String::new();
+    let mut lines: Vec<(String, String)> = Vec::new();
+    for Key {
+        section: s,
+        name: key,
+        ..
+    } in keys
+    {
+        if s != section {
+            if !section.is_empty() {
+                lines.push((String::new(), String::new()));
+            }
+            lines.push((format!("[{}]", s), String::new()));
+            section = s.clone();
+        }
+        let value = table.get(&s).and_then(|t| t.get(&key));
+        let source = config.sources.get(&format!("{}.{}", s, key));
+        match (value, source) {
+            (Some(value), source) => lines.push((
+                format!("{} = {}", key, value),
+                format!("# {}", source.map(|s| s.as_str()).unwrap_or("default")),
+            )),
+            // 未设置的键显示对应的环境变量
+            (None, _) => lines.push((
+                format!("# {} is not set", key),
+                format!("# WATCHMEN_{}_{}", s, key).to_uppercase(),
+            )),
+        }
+    }
+    let width = lines.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+    for (line, source) in lines {
+        if source.is_empty() {
+            println!("{}", line);
+        } else {
+            println!("{:<width$}  {}", line, source, width = width);
+        }
+    }
+    Ok(())
+}
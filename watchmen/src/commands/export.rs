@@ -0,0 +1,456 @@
+use crate::common::{
+    arg::ExportArgs,
+    config::Config,
+    handle::{Command, Data, Request},
+    task::{InheritEnv, Task, TaskFlag, TaskType, RUNTIME_FIELDS, RUNTIME_TYPE_FIELDS},
+};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, error::Error, fmt::Write, path::Path};
+
+use crate::{engine::send, utils::print_result};
+
+/// Fields left out when empty, they have default values
+const DEFAULT_FIELDS: [&str; 3] = ["env", "env_file", "labels"];
+
+pub async fn export(args: ExportArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    let format = args.format.to_lowercase();
+    let ext = match format.as_str() {
+        "toml" | "ini" | "json" | "yaml" => format.as_str(),
+        "yml" => "yaml",
+        _ => return Err(Box::from(format!("Invalid export format: {}", args.format))),
+    };
+    let condition = if args.id.is_some()
+        || args.name.is_some()
+        || args.group.is_some()
+        || args.selector.is_some()
+    {
+        Some(TaskFlag {
+            id: args.id.unwrap_or(0),
+            name: args.name,
+            group: args.group,
+            mat: args.mat,
+            selector: args.selector,
+        })
+    } else {
+        None
+    };
+    let request = Request {
+        command: Command::Export(condition),
+    };
+    let mut tasks = Vec::new();
+    for r in send(config, vec![request]).await? {
+        match r.data {
+            Some(Data::Tasks(ts)) if r.code == 10000 => tasks.extend(ts),
+            _ => {
+                print_result(vec![r]).await;
+                return Ok(());
+            }
+        }
+    }
+
+    // 按分组拆分文件，未分组的任务写入 default
+    let mut files: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+    for task in tasks {
+        let file = if args.split {
+            task.group.clone().unwrap_or("default".to_string())
+        } else {
+            "tasks".to_string()
+        };
+        files.entry(file).or_default().push(task);
+    }
+    match args.output {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            for (file, tasks) in files {
+                let path = Path::new(&dir).join(format!("{}.{}", file, ext));
+                std::fs::write(&path, render(&tasks, ext)?)?;
+                println!("{} tasks exported to {}", tasks.len(), path.display());
+            }
+        }
+        None => {
+            for (_, tasks) in files {
+                print!("{}", render(&tasks, ext)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Task files content in format
+///
+/// # params
+///
+/// - `tasks`: tasks
+/// - `format`: toml, ini, json or yaml
+fn render(tasks: &[Task], format: &str) -> Result<String, Box<dyn Error>> {
+    if format == "ini" {
+        return to_ini(tasks);
+    }
+    let mut definitions = Vec::new();
+    for task in tasks {
+        definitions.push(definition(task)?);
+    }
+    let mut root = Map::new();
+    root.insert("task".to_string(), Value::Array(definitions));
+    let root = Value::Object(root);
+    Ok(match format {
+        "toml" => toml::to_string(&root)?,
+        "yaml" => serde_yaml::to_string(&root)?,
+        // JSON 任务文件为任务数组
+        _ => format!("{}\n", serde_json::to_string_pretty(&root["task"])?),
+    })
+}
+
+/// Task definition without runtime fields and empty values
+fn definition(task: &Task) -> Result<Value, Box<dyn Error>> {
+    let mut value = serde_json::to_value(task)?;
+    if let Value::Object(map) = &mut value {
+        for field in RUNTIME_FIELDS {
+            map.remove(field);
+        }
+        for field in DEFAULT_FIELDS {
+            let empty = match map.get(field) {
+                Some(Value::Object(m)) => m.is_empty(),
+                Some(Value::Array(a)) => a.is_empty(),
+                _ => false,
+            };
+            if empty {
+                map.remove(field);
+            }
+        }
+        if let Some(Value::Object(tt)) = map.get_mut("task_type") {
+            for (_, fields) in tt.iter_mut() {
+                if let Value::Object(fields) = fields {
+                    for field in RUNTIME_TYPE_FIELDS {
+                        fields.remove(field);
+                    }
+                }
+            }
+        }
+    }
+    strip_null(&mut value);
+    Ok(value)
+}
+
+fn strip_null(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_null);
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_null),
+        _ => {}
+    }
+}
+
+/// Quote word for INI value that is split into words
+fn quote(word: &str) -> String {
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+        if word.contains('"') {
+            format!("'{}'", word)
+        } else {
+            format!("\"{}\"", word)
+        }
+    } else {
+        word.to_string()
+    }
+}
+
+fn pairs(map: &std::collections::HashMap<String, String>) -> String {
+    let map: BTreeMap<&String, &String> = map.iter().collect();
+    map.iter()
+        .map(|(k, v)| quote(&format!("{}={}", k, v)))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Task file in INI format, keys are the ones read by `Task::from_ini`
+fn to_ini(tasks: &[Task]) -> Result<String, Box<dyn Error>> {
+    let mut s = String::new();
+    for task in tasks {
+        let mut kv: Vec<(&str, String)> = Vec::new();
+        if task.id > 0 {
+            kv.push(("id", task.id.to_string()));
+        }
+        kv.push(("name", task.name.clone()));
+        kv.push(("command", task.command.clone()));
+        if !task.args.is_empty() {
+            // INI 任务文件的参数按空格分割
+            if let Some(arg) = task.args.iter().find(|a| a.contains(char::is_whitespace)) {
+                return Err(Box::from(format!(
+                    "Task [{}] argument `{}` contains whitespace, which INI task files cannot hold, export to toml, json or yaml instead",
+                    task.name, arg
+                )));
+            }
+            kv.push(("args", task.args.join(" ")));
+        }
+        if let Some(group) = &task.group {
+            kv.push(("group", group.clone()));
+        }
+        if !task.labels.is_empty() {
+            kv.push(("labels", pairs(&task.labels)));
+        }
+        if let Some(dir) = &task.dir {
+            kv.push(("dir", dir.clone()));
+        }
+        if !task.env.is_empty() {
+            kv.push(("env", pairs(&task.env)));
+        }
+        if !task.env_file.is_empty() {
+            let files: Vec<String> = task.env_file.iter().map(|f| quote(f)).collect();
+            kv.push(("env_file", files.join(" ")));
+        }
+        match &task.inherit_env {
+            Some(InheritEnv::All(all)) => kv.push(("inherit_env", all.to_string())),
+            Some(InheritEnv::Only(keys)) => kv.push(("inherit_env", keys.join(","))),
+            None => {}
+        }
+        if let Some(stdin) = task.stdin {
+            kv.push(("stdin", stdin.to_string()));
+        }
+        if let Some(stdout) = &task.stdout {
+            kv.push(("stdout", stdout.clone()));
+        }
+        if let Some(stderr) = &task.stderr {
+            kv.push(("stderr", stderr.clone()));
+        }
+        if let Some(rlimits) = &task.rlimits {
+            for (key, value) in [
+                ("rlimit_nofile", rlimits.nofile),
+                ("rlimit_nproc", rlimits.nproc),
+                ("rlimit_core", rlimits.core),
+                ("rlimit_as", rlimits.address_space),
+                ("rlimit_cpu", rlimits.cpu),
+            ] {
+                if let Some(value) = value {
+                    kv.push((key, value.to_string()));
+                }
+            }
+        }
+        if let Some(nice) = task.nice {
+            kv.push(("nice", nice.to_string()));
+        }
+        if let Some(ionice) = &task.ionice {
+            kv.push(("ionice_class", ionice.class.clone()));
+            if let Some(level) = ionice.level {
+                kv.push(("ionice_level", level.to_string()));
+            }
+        }
+        if let Some(adj) = task.oom_score_adj {
+            kv.push(("oom_score_adj", adj.to_string()));
+        }
+        if let Some(umask) = &task.umask {
+            kv.push(("umask", umask.clone()));
+        }
+        if let Some(cpus) = &task.cpu_affinity {
+            let cpus: Vec<String> = cpus.iter().map(|c| c.to_string()).collect();
+            kv.push(("cpu_affinity", cpus.join(" ")));
+        }
+        if let Some(watch) = &task.watch {
+            let words = |words: &[String]| -> String {
+                words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ")
+            };
+            kv.push(("watch", words(&watch.paths)));
+            if !watch.include.is_empty() {
+                kv.push(("watch_include", words(&watch.include)));
+            }
+            if !watch.exclude.is_empty() {
+                kv.push(("watch_exclude", words(&watch.exclude)));
+            }
+            if let Some(debounce) = watch.debounce {
+                kv.push(("watch_debounce", debounce.to_string()));
+            }
+            if let Some(ignore) = watch.ignore_starting {
+                kv.push(("watch_ignore_starting", ignore.to_string()));
+            }
+        }
+        if let Some(user) = &task.user {
+            kv.push(("user", user.clone()));
+        }
+        if let Some(group) = &task.user_group {
+            kv.push(("user_group", group.clone()));
+        }
+        if let Some(groups) = &task.supplementary_groups {
+            kv.push(("supplementary_groups", groups.join(" ")));
+        }
+        if let Some(instances) = task.instances {
+            kv.push(("instances", instances.to_string()));
+        }
+        if let Some(port) = task.port {
+            kv.push(("port", port.to_string()));
+        }
+        if let Some(policy) = &task.concurrency_policy {
+            kv.push(("concurrency_policy", policy.as_str().to_string()));
+        }
+        for (key, value) in [
+            (
+                "max_concurrent_runs",
+                task.max_concurrent_runs.map(|n| n as u64),
+            ),
+            ("timeout", task.timeout),
+            ("kill_timeout", task.kill_timeout),
+            ("retry_on_timeout", task.retry_on_timeout.map(|n| n as u64)),
+            ("retries", task.retries.map(|n| n as u64)),
+            ("retry_delay", task.retry_delay),
+            ("ttl_after_finished", task.ttl_after_finished),
+        ] {
+            if let Some(value) = value {
+                kv.push((key, value.to_string()));
+            }
+        }
+        if let Some(signal) = &task.stop_signal {
+            kv.push(("stop_signal", signal.clone()));
+        }
+        if let Some(backoff) = task.retry_backoff {
+            kv.push(("retry_backoff", backoff.to_string()));
+        }
+        match &task.task_type {
+            TaskType::Scheduled(tt) => {
+                kv.push(("task_type", "scheduled".to_string()));
+                for (key, value) in [
+                    ("year", tt.year.map(|n| n as i64)),
+                    ("month", tt.month.map(|n| n as i64)),
+                    ("day", tt.day.map(|n| n as i64)),
+                    ("hour", tt.hour.map(|n| n as i64)),
+                    ("minute", tt.minute.map(|n| n as i64)),
+                    ("second", tt.second.map(|n| n as i64)),
+                ] {
+                    if let Some(value) = value {
+                        kv.push((key, value.to_string()));
+                    }
+                }
+            }
+            TaskType::Async(tt) => {
+                kv.push(("task_type", "async".to_string()));
+                if let Some(max) = tt.max_restart {
+                    kv.push(("max_restart", max.to_string()));
+                }
+            }
+            TaskType::Periodic(tt) => {
+                kv.push(("task_type", "periodic".to_string()));
+                kv.push(("started_after", tt.started_after.to_string()));
+                kv.push(("interval", tt.interval.to_string()));
+                kv.push(("sync", tt.sync.to_string()));
+                kv.push(("jitter", tt.jitter.to_string()));
+                kv.push(("align", tt.align.to_string()));
+            }
+            TaskType::Once(tt) => {
+                kv.push(("task_type", "once".to_string()));
+                if let Some(at) = &tt.at {
+                    kv.push(("at", at.clone()));
+                }
+                if let Some(after) = tt.after {
+                    kv.push(("after", after.to_string()));
+                }
+            }
+            TaskType::None => {}
+        }
+
+        writeln!(s, "[{}]", task.name)?;
+        for (key, value) in kv {
+            writeln!(s, "{} = {}", key, value)?;
+        }
+        writeln!(s)?;
+    }
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::task::{AsyncTask, PeriodicTask};
+    use std::collections::HashMap;
+
+    fn tasks() -> Vec<Task> {
+        let web = Task {
+            id: 1,
+            name: "web".to_string(),
+            command: "python3".to_string(),
+            args: vec!["-m".to_string(), "http.server".to_string()],
+            group: Some("api".to_string()),
+            dir: Some("/srv/web".to_string()),
+            // 带空格和引号的值需要 quote
+            env: HashMap::from([
+                ("GREETING".to_string(), "hello world".to_string()),
+                ("QUOTED".to_string(), "say \"hi\"".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+            ]),
+            labels: HashMap::from([
+                ("tier".to_string(), "frontend".to_string()),
+                ("owner".to_string(), "web team".to_string()),
+            ]),
+            env_file: vec!["/etc/web/env".to_string(), "/etc/web/my env".to_string()],
+            inherit_env: Some(InheritEnv::Only(vec![
+                "PATH".to_string(),
+                "HOME".to_string(),
+            ])),
+            task_type: TaskType::Async(AsyncTask {
+                max_restart: Some(3),
+                has_restart: 1,
+                started_at: 1700000000,
+                stopped_at: 0,
+                watch_restart: 0,
+                last_change: None,
+            }),
+            ..Task::default()
+        };
+        let backup = Task {
+            id: 2,
+            name: "backup".to_string(),
+            command: "tar".to_string(),
+            args: vec!["-czf".to_string(), "/tmp/backup.tgz".to_string()],
+            dir: Some("/var/backup".to_string()),
+            timeout: Some(600),
+            retries: Some(2),
+            task_type: TaskType::Periodic(PeriodicTask {
+                started_after: 0,
+                interval: 3600,
+                last_run: 1700000000,
+                sync: false,
+                jitter: 30,
+                align: true,
+                next_run: 1700003600,
+                slot: 1700003600,
+            }),
+            ..Task::default()
+        };
+        vec![web, backup]
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("C"), "C");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("a b"), "\"a b\"");
+        assert_eq!(quote("say \"hi\""), "'say \"hi\"'");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("watchmen-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tasks = tasks();
+        // 导出的任务文件重新加载后定义不变
+        for ext in ["toml", "ini", "json", "yaml"] {
+            let path = dir.join(format!("tasks.{}", ext));
+            std::fs::write(&path, render(&tasks, ext).unwrap()).unwrap();
+            let loaded = Task::from_file(&path, None).unwrap().task;
+            assert_eq!(loaded.len(), tasks.len(), "{}", ext);
+            for task in &tasks {
+                // INI 文件的节不保证顺序，按名称匹配
+                let loaded = loaded.iter().find(|t| t.name == task.name).unwrap();
+                assert_eq!(loaded.env, task.env, "{}", ext);
+                assert_eq!(loaded.labels, task.labels, "{}", ext);
+                assert_eq!(loaded.env_file, task.env_file, "{}", ext);
+                assert_eq!(
+                    loaded.definition_hash().unwrap(),
+                    task.definition_hash().unwrap(),
+                    "{} task {}",
+                    ext,
+                    task.name
+                );
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
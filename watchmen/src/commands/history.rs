@@ -0,0 +1,142 @@
+use crate::common::{
+    arg::HistoryArgs,
+    config::Config,
+    handle::{Command, Data, Request, Response},
+    task::{Run, TaskFlag},
+};
+use chrono::{Local, TimeZone};
+use colored::Colorize;
+use std::error::Error;
+
+use crate::{engine::send, utils::print_result as pr};
+
+pub async fn history(args: HistoryArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    // 纯数字视为任务 id，否则视为任务名
+    let tf = match args.task.parse::<i64>() {
+        Ok(id) => TaskFlag {
+            id,
+            name: None,
+            group: None,
+            mat: false,
+            selector: None,
+        },
+        Err(_) => TaskFlag {
+            id: 0,
+            name: Some(args.task.clone()),
+            group: None,
+            mat: false,
+            selector: None,
+        },
+    };
+    let request = Request {
+        command: Command::History(tf, args.page, args.size),
+    };
+    print_result(send(config, vec![request]).await?, args.page, args.size).await;
+    Ok(())
+}
+
+fn format_time(millis: u64) -> String {
+    match Local.timestamp_millis_opt(millis as i64) {
+        chrono::LocalResult::Single(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+        _ => millis.to_string(),
+    }
+}
+
+fn format_exit(run: &Run) -> String {
+    if run.timed_out {
+        return "timed out".to_string();
+    }
+    match (run.code, run.signal) {
+        (Some(code), _) => code.to_string(),
+        (None, Some(signal)) => format!("signal {}", signal),
+        (None, None) => String::new(),
+    }
+}
+
+pub async fn print_result(res: Vec<Response>, page: u64, size: u64) {
+    let mut total = 0;
+    let mut runs: Vec<Run> = Vec::new();
+    for r in res {
+        if r.code != 10000 {
+            pr(vec![r]).await;
+            return;
+        }
+        if let Some(Data::History(t, rs)) = r.data {
+            total = t;
+            runs.extend(rs);
+        }
+    }
+
+    let mut column_run = vec!["Run".bold()];
+    let mut column_trigger = vec!["Trigger".bold()];
+    let mut column_started = vec!["Started".bold()];
+    let mut column_duration = vec!["Duration".bold()];
+    let mut column_exit = vec!["Exit".bold()];
+    let mut column_reason = vec!["Reason".bold()];
+
+    for run in &runs {
+        column_run.push(run.run_id.italic());
+        column_trigger.push(run.trigger.as_str().normal());
+        column_started.push(format_time(run.started_at).normal());
+        match run.ended_at {
+            Some(ended) => column_duration.push(
+                format!(
+                    "{:.3}s",
+                    ended.saturating_sub(run.started_at) as f64 / 1000.0
+                )
+                .normal(),
+            ),
+            None => column_duration.push("running".green()),
+        }
+        let exit = format_exit(run);
+        if run.code == Some(0) && !run.timed_out {
+            column_exit.push(exit.green());
+        } else {
+            column_exit.push(exit.red());
+        }
+        column_reason.push(run.reason.clone().unwrap_or_default().normal());
+    }
+
+    let max_run = column_run.iter().map(|s| s.len()).max().unwrap();
+    let max_trigger = column_trigger.iter().map(|s| s.len()).max().unwrap();
+    let max_started = column_started.iter().map(|s| s.len()).max().unwrap();
+    let max_duration = column_duration.iter().map(|s| s.len()).max().unwrap();
+    let max_exit = column_exit.iter().map(|s| s.len()).max().unwrap();
+    let max_reason = column_reason.iter().map(|s| s.len()).max().unwrap();
+
+    let max_sum = max_run
+        + max_trigger
+        + max_started
+        + max_duration
+        + max_exit
+        + max_reason
+        + 3 * (7 - 1)
+        + 1;
+
+    for i in 0..column_run.len() {
+        println!("{:-<max_sum$}", "", max_sum = max_sum);
+        println!(
+            "| {: <max_run$} | {: <max_trigger$} | {: <max_started$} | {: <max_duration$} | {: <max_exit$} | {: <max_reason$} |",
+            column_run[i],
+            column_trigger[i],
+            column_started[i],
+            column_duration[i],
+            column_exit[i],
+            column_reason[i],
+            max_run = max_run,
+            max_trigger = max_trigger,
+            max_started = max_started,
+            max_duration = max_duration,
+            max_exit = max_exit,
+            max_reason = max_reason,
+        );
+    }
+    println!("{:-<max_sum$}", "", max_sum = max_sum);
+    let pages = total.div_ceil(size.max(1));
+    println!(
+        "{} Total runs, page {} of {}",
+        total.to_string().bold().purple(),
+        page,
+        pages
+    );
+}
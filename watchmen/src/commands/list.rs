@@ -1,10 +1,11 @@
-use colored::Colorize;
 use crate::common::{
     arg::ListArgs,
     config::Config,
     handle::{Command, Request, Response, Status},
     task::TaskFlag,
 };
+use chrono::{Local, TimeZone};
+use colored::{ColoredString, Colorize};
 use regex::Regex;
 use std::{error::Error, path::Path};
 
@@ -24,7 +25,7 @@ pub async fn list(args: ListArgs, config: Config) -> Result<(), Box<dyn Error>>
             mat = matc;
         } else {
             // 最后使用默认参数
-            mat = String::from(r"^.*\.(toml|ini|json)$");
+            mat = String::from(r"^.*\.(toml|ini|json|yaml|yml)$");
         }
         let regex: Regex = Regex::new(&mat).unwrap();
         let mut matched_files = Vec::new();
@@ -34,51 +35,22 @@ pub async fn list(args: ListArgs, config: Config) -> Result<(), Box<dyn Error>>
 
         for file in matched_files {
             let path = Path::new(&file);
-            if path.is_file() && path.extension().unwrap().to_str().unwrap() == "ini" {
-                let ts = TaskFlag::from_ini(path)?;
-                for tf in ts {
+            if path.is_file() {
+                for tf in TaskFlag::from_file(path)? {
                     let request: Request = Request {
                         command: Command::List(Some(tf)),
                     };
                     reqs.push(request);
                 }
-            } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "toml" {
-                let ts = TaskFlag::from_toml(path)?;
-                for tf in ts {
-                    let request: Request = Request {
-                        command: Command::List(Some(tf)),
-                    };
-                    reqs.push(request);
-                }
-            } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "json" {
-                // tasks.push(Task::from_json(path)?);
-                // TODO: 读取 JSON 格式的配置文件
-            } else {
-                return Err(Box::from(format!(
-                    "File {} is not a TOML or INI or JSON file",
-                    path.to_str().unwrap()
-                )));
             }
         }
         reqs
     } else if let Some(file) = args.config {
         let path = Path::new(&file);
-        let tfs = if path.is_file() && path.extension().unwrap().to_str().unwrap() == "ini" {
-            TaskFlag::from_ini(path)?
-        } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "toml" {
-            TaskFlag::from_toml(path)?
-        } else if path.is_file() && path.extension().unwrap().to_str().unwrap() == "json" {
-            // TODO: 读取 JSON 格式的配置文件
-            return Err(Box::from(format!(
-                "File {} is not a TOML or INI or JSON file",
-                path.to_str().unwrap()
-            )));
-        } else {
-            return Err(Box::from(format!(
-                "File {} is not a TOML or INI or JSON file",
-                path.to_str().unwrap()
-            )));
-        };
+        if !path.is_file() {
+            return Err(Box::from(format!("File {} not exists", file)));
+        }
+        let tfs = TaskFlag::from_file(path)?;
         let mut reqs = Vec::new();
         for tf in tfs {
             let request: Request = Request {
@@ -97,6 +69,7 @@ pub async fn list(args: ListArgs, config: Config) -> Result<(), Box<dyn Error>>
                 name: None,
                 group: None,
                 mat: args.mat,
+                selector: args.selector,
             })),
         };
         vec![request]
@@ -107,6 +80,7 @@ pub async fn list(args: ListArgs, config: Config) -> Result<(), Box<dyn Error>>
                 name: Some(name),
                 group: None,
                 mat: args.mat,
+                selector: args.selector,
             })),
         };
         vec![request]
@@ -117,6 +91,18 @@ pub async fn list(args: ListArgs, config: Config) -> Result<(), Box<dyn Error>>
                 name: None,
                 group: Some(group),
                 mat: args.mat,
+                selector: args.selector,
+            })),
+        };
+        vec![request]
+    } else if let Some(selector) = args.selector {
+        let request: Request = Request {
+            command: Command::List(Some(TaskFlag {
+                id: 0,
+                name: None,
+                group: None,
+                mat: args.mat,
+                selector: Some(selector),
             })),
         };
         vec![request]
@@ -127,16 +113,169 @@ pub async fn list(args: ListArgs, config: Config) -> Result<(), Box<dyn Error>>
         vec![request]
     };
     if args.less {
-        print_result_less(send(config.clone(), requests).await?).await;
+        print_result_less(send(config.clone(), requests).await?, &args.labels).await;
     } else if args.more {
-        print_result_more(send(config.clone(), requests).await?).await;
+        print_result_more(send(config.clone(), requests).await?, &args.labels).await;
     } else {
-        print_result(send(config.clone(), requests).await?).await;
+        print_result(send(config.clone(), requests).await?, &args.labels).await;
     }
     Ok(())
 }
+/// Sort instances right after their multi-instance task, status of multi-instance task
+/// is replaced by the number of active instances
+fn group_instances(status: Vec<Status>) -> Vec<Status> {
+    let (instances, tasks): (Vec<Status>, Vec<Status>) =
+        status.into_iter().partition(|s| s.parent.is_some());
+    let mut grouped = Vec::new();
+    for mut task in tasks {
+        let mut children: Vec<Status> = instances
+            .iter()
+            .filter(|s| s.parent == Some(task.id))
+            .cloned()
+            .collect();
+        children.sort_by_key(|s| s.instance);
+        if task.instances.is_some() {
+            let active = children
+                .iter()
+                .filter(|s| {
+                    matches!(
+                        s.status.as_deref(),
+                        Some("running" | "auto restart" | "interval" | "executing")
+                    )
+                })
+                .count();
+            task.status = Some(format!("{}/{} active", active, children.len()));
+        }
+        grouped.push(task);
+        grouped.extend(children);
+    }
+    // 未列出所属任务的实例放在最后
+    for s in instances {
+        if !grouped.iter().any(|g| g.id == s.id) {
+            grouped.push(s);
+        }
+    }
+    grouped
+}
+
+/// Instances are indented under their multi-instance task
+fn display_name(s: &Status) -> String {
+    if s.parent.is_some() {
+        format!("  {}", s.name)
+    } else {
+        s.name.clone()
+    }
+}
+
+/// Pids of all runs in flight, with number of queued runs
+fn display_pid(s: &Status) -> String {
+    let mut pid = if s.runs.is_empty() {
+        s.pid.map(|p| p.to_string()).unwrap_or_default()
+    } else {
+        s.runs
+            .iter()
+            .filter_map(|r| r.pid)
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+    if s.queued > 0 {
+        pid.push_str(&format!(" (+{} queued)", s.queued));
+    }
+    pid
+}
 
-pub async fn print_result(res: Vec<Response>) {
+/// Exit code of last run, a run failed after its retries were exhausted is marked failed
+fn display_code(s: &Status) -> ColoredString {
+    let code = match (s.timed_out, s.code) {
+        (Some(t), _) => format!("timed out {:.1}s", t as f64 / 1000.0),
+        (None, Some(t)) => t.to_string(),
+        (None, None) => String::new(),
+    };
+    match (s.failed_at, s.timed_out) {
+        (Some(_), _) => format!("{} (failed)", code).red(),
+        (None, Some(_)) => code.red(),
+        (None, None) => code.normal(),
+    }
+}
+
+/// Next run time of periodic and once task in local time
+fn display_next(s: &Status) -> String {
+    match &s.task_type {
+        crate::common::task::TaskType::Periodic(tt) if tt.next_run > 0 => format_time(tt.next_run),
+        crate::common::task::TaskType::Once(tt) if s.status.as_deref() == Some("waiting") => {
+            format_time(tt.run_at)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Schedule of task as defined, fields of scheduled task not set match any value
+fn display_schedule(s: &Status) -> String {
+    match &s.task_type {
+        crate::common::task::TaskType::Scheduled(tt) => {
+            let field = |v: Option<String>| v.unwrap_or("*".to_string());
+            format!(
+                "{}-{}-{} {}:{}:{}",
+                field(tt.year.map(|v| v.to_string())),
+                field(tt.month.map(|v| format!("{:02}", v))),
+                field(tt.day.map(|v| format!("{:02}", v))),
+                field(tt.hour.map(|v| format!("{:02}", v))),
+                field(tt.minute.map(|v| format!("{:02}", v))),
+                field(tt.second.map(|v| format!("{:02}", v))),
+            )
+        }
+        crate::common::task::TaskType::Periodic(tt) => {
+            let mut schedule = format!("every {}s", tt.interval);
+            if tt.align {
+                schedule.push_str(", aligned");
+            }
+            if tt.jitter > 0 {
+                schedule.push_str(&format!(", jitter {}s", tt.jitter));
+            }
+            if tt.started_after > 0 {
+                schedule.push_str(&format!(", after {}", format_time(tt.started_after)));
+            }
+            schedule
+        }
+        crate::common::task::TaskType::Once(tt) => match (&tt.at, tt.after) {
+            (Some(at), _) => format!("at {}", at),
+            (None, Some(after)) => format!("{}s after added", after),
+            (None, None) => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Environment variables of task sorted by name
+fn display_env(s: &Status) -> String {
+    let mut env: Vec<String> = s.env.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    env.sort();
+    env.join(" ")
+}
+
+/// Columns of labels chosen to show, header first
+fn label_columns(status: &[Status], labels: &[String]) -> Vec<Vec<ColoredString>> {
+    labels
+        .iter()
+        .map(|key| {
+            let mut column = vec![key.bold()];
+            for s in status {
+                column.push(s.labels.get(key).cloned().unwrap_or_default().normal());
+            }
+            column
+        })
+        .collect()
+}
+
+fn format_time(secs: u64) -> String {
+    match Local.timestamp_opt(secs as i64, 0) {
+        chrono::LocalResult::Single(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+        _ => secs.to_string(),
+    }
+}
+
+pub async fn print_result(res: Vec<Response>, labels: &[String]) {
     let mut status: Vec<Status> = Vec::new();
     for r in res {
         if r.code != 10000 {
@@ -147,6 +286,10 @@ pub async fn print_result(res: Vec<Response>) {
             match data {
                 crate::common::handle::Data::None => {}
                 crate::common::handle::Data::String(_) => {}
+                crate::common::handle::Data::History(_, _) => {}
+                crate::common::handle::Data::Results(_) => {}
+                crate::common::handle::Data::Plan(_) => {}
+                crate::common::handle::Data::Tasks(_) => {}
                 crate::common::handle::Data::Status(s) => {
                     for i in &s {
                         status.push(i.clone());
@@ -155,6 +298,8 @@ pub async fn print_result(res: Vec<Response>) {
             }
         }
     }
+    let status = group_instances(status);
+    let column_labels = label_columns(&status, labels);
 
     let mut total = 0;
     let mut total_added = 0;
@@ -185,10 +330,18 @@ pub async fn print_result(res: Vec<Response>) {
     let mut column_type = Vec::new();
     column_type.push("Type".bold());
 
+    let mut column_next = Vec::new();
+    column_next.push("Next Run".bold());
+
     for s in status {
-        total += 1;
+        if s.instances.is_none() {
+            total += 1;
+        }
         column_id.push(s.id.to_string().italic());
-        column_name.push(s.name.normal());
+        column_pid.push(display_pid(&s).normal());
+        column_next.push(display_next(&s).normal());
+        column_code.push(display_code(&s));
+        column_name.push(display_name(&s).normal());
         match s.status {
             Some(t) => match t.as_str() {
                 "added" => {
@@ -217,23 +370,18 @@ pub async fn print_result(res: Vec<Response>) {
                     column_status.push(t.yellow())
                 }
                 "executing" => column_status.push(t.green()),
+                "failed" => column_status.push(t.red()),
+                "completed" => column_status.push(t.blue()),
                 _ => column_status.push(t.normal()),
             },
             None => column_status.push("".normal()),
         }
         column_command.push(s.command.split("/").last().unwrap().normal());
-        match s.pid {
-            Some(t) => column_pid.push(t.to_string().normal()),
-            None => column_pid.push("".normal()),
-        }
-        match s.code {
-            Some(t) => column_code.push(t.to_string().normal()),
-            None => column_code.push("".normal()),
-        }
         match s.task_type {
             crate::common::task::TaskType::Scheduled(_) => column_type.push("Scheduled".normal()),
             crate::common::task::TaskType::Async(_) => column_type.push("Async".normal()),
             crate::common::task::TaskType::Periodic(_) => column_type.push("Periodic".normal()),
+            crate::common::task::TaskType::Once(_) => column_type.push("Once".normal()),
             crate::common::task::TaskType::None => column_type.push("".normal()),
         }
     }
@@ -244,7 +392,12 @@ pub async fn print_result(res: Vec<Response>) {
     let max_pid = column_pid.iter().map(|s| s.len()).max().unwrap();
     let max_code = column_code.iter().map(|s| s.len()).max().unwrap();
     let max_type = column_type.iter().map(|s| s.len()).max().unwrap();
+    let max_next = column_next.iter().map(|s| s.len()).max().unwrap();
 
+    let max_labels: usize = column_labels
+        .iter()
+        .map(|c| c.iter().map(|s| s.len()).max().unwrap() + 3)
+        .sum();
     let max_sum = max_id
         + max_name
         + max_status
@@ -252,13 +405,15 @@ pub async fn print_result(res: Vec<Response>) {
         + max_pid
         + max_code
         + max_type
-        + 3 * (7 - 1)
-        + 4;
+        + max_next
+        + 3 * (8 - 1)
+        + 4
+        + max_labels;
 
     for i in 0..column_id.len() {
         println!("{:-<max_sum$}", "", max_sum = max_sum);
-        println!(
-            "| {: <max_id$} | {: <max_name$} | {: <max_status$} | {: <max_command$} | {: <max_pid$} | {: <max_code$} | {: <max_type$} |",
+        print!(
+            "| {: <max_id$} | {: <max_name$} | {: <max_status$} | {: <max_command$} | {: <max_pid$} | {: <max_code$} | {: <max_type$} | {: <max_next$} |",
             column_id[i],
             column_name[i],
             column_status[i],
@@ -266,6 +421,7 @@ pub async fn print_result(res: Vec<Response>) {
             column_pid[i],
             column_code[i],
             column_type[i],
+            column_next[i],
             max_id = max_id,
             max_name = max_name,
             max_status = max_status,
@@ -273,7 +429,13 @@ pub async fn print_result(res: Vec<Response>) {
             max_pid = max_pid,
             max_code = max_code,
             max_type = max_type,
+            max_next = max_next,
         );
+        for column in &column_labels {
+            let width = column.iter().map(|s| s.len()).max().unwrap();
+            print!(" {: <width$} |", column[i], width = width);
+        }
+        println!();
     }
     println!("{:-<max_sum$}", "", max_sum = max_sum);
     println!(
@@ -288,7 +450,7 @@ pub async fn print_result(res: Vec<Response>) {
     );
 }
 
-pub async fn print_result_more(res: Vec<Response>) {
+pub async fn print_result_more(res: Vec<Response>, labels: &[String]) {
     let mut status: Vec<Status> = Vec::new();
     for r in res {
         if r.code != 10000 {
@@ -299,6 +461,10 @@ pub async fn print_result_more(res: Vec<Response>) {
             match data {
                 crate::common::handle::Data::None => {}
                 crate::common::handle::Data::String(_) => {}
+                crate::common::handle::Data::History(_, _) => {}
+                crate::common::handle::Data::Results(_) => {}
+                crate::common::handle::Data::Plan(_) => {}
+                crate::common::handle::Data::Tasks(_) => {}
                 crate::common::handle::Data::Status(s) => {
                     for i in &s {
                         status.push(i.clone());
@@ -307,6 +473,8 @@ pub async fn print_result_more(res: Vec<Response>) {
             }
         }
     }
+    let status = group_instances(status);
+    let column_labels = label_columns(&status, labels);
 
     let mut total = 0;
     let mut total_added = 0;
@@ -325,6 +493,9 @@ pub async fn print_result_more(res: Vec<Response>) {
     let mut column_name = Vec::new();
     column_name.push("Name".bold());
 
+    let mut column_profile = Vec::new();
+    column_profile.push("Profile".bold());
+
     let mut column_status = Vec::new();
     column_status.push("Status".bold());
 
@@ -343,15 +514,44 @@ pub async fn print_result_more(res: Vec<Response>) {
     let mut column_type = Vec::new();
     column_type.push("Type".bold());
 
+    let mut column_schedule = Vec::new();
+    column_schedule.push("Schedule".bold());
+
+    let mut column_next = Vec::new();
+    column_next.push("Next Run".bold());
+
+    let mut column_instances = Vec::new();
+    column_instances.push("Instances".bold());
+
+    let mut column_env = Vec::new();
+    column_env.push("Env".bold());
+
+    let mut column_watch = Vec::new();
+    column_watch.push("Watch".bold());
+
     for s in status {
-        total += 1;
+        if s.instances.is_none() {
+            total += 1;
+        }
         column_id.push(s.id.to_string().italic());
+        column_pid.push(display_pid(&s).normal());
+        column_next.push(display_next(&s).normal());
+        column_schedule.push(display_schedule(&s).normal());
+        column_instances.push(
+            s.instances
+                .map(|n| n.to_string())
+                .unwrap_or_default()
+                .normal(),
+        );
+        column_env.push(display_env(&s).normal());
+        column_code.push(display_code(&s));
         if s.group.is_some() {
-            column_group.push(s.group.unwrap().normal());
+            column_group.push(s.group.clone().unwrap().normal());
         } else {
             column_group.push("".normal());
         }
-        column_name.push(s.name.normal());
+        column_name.push(display_name(&s).normal());
+        column_profile.push(s.profile.clone().unwrap_or_default().normal());
         match s.status {
             Some(t) => match t.as_str() {
                 "added" => {
@@ -380,71 +580,111 @@ pub async fn print_result_more(res: Vec<Response>) {
                     column_status.push(t.yellow())
                 }
                 "executing" => column_status.push(t.green()),
+                "failed" => column_status.push(t.red()),
+                "completed" => column_status.push(t.blue()),
                 _ => column_status.push(t.normal()),
             },
             None => column_status.push("".normal()),
         }
         column_command.push(s.command.normal());
         column_args.push(s.args.join(" ").normal());
-        match s.pid {
-            Some(t) => column_pid.push(t.to_string().normal()),
-            None => column_pid.push("".normal()),
-        }
-        match s.code {
-            Some(t) => column_code.push(t.to_string().normal()),
-            None => column_code.push("".normal()),
+        match &s.task_type {
+            crate::common::task::TaskType::Async(tt) if tt.watch_restart > 0 => column_watch.push(
+                format!(
+                    "{} ({})",
+                    tt.watch_restart,
+                    tt.last_change.clone().unwrap_or_default()
+                )
+                .normal(),
+            ),
+            _ => column_watch.push("".normal()),
         }
         match s.task_type {
             crate::common::task::TaskType::Scheduled(_) => column_type.push("Scheduled".normal()),
             crate::common::task::TaskType::Async(_) => column_type.push("Async".normal()),
             crate::common::task::TaskType::Periodic(_) => column_type.push("Periodic".normal()),
+            crate::common::task::TaskType::Once(_) => column_type.push("Once".normal()),
             crate::common::task::TaskType::None => column_type.push("".normal()),
         }
     }
     let max_id = column_id.iter().map(|s| s.len()).max().unwrap();
     let max_group = column_group.iter().map(|s| s.len()).max().unwrap();
     let max_name = column_name.iter().map(|s| s.len()).max().unwrap();
+    let max_profile = column_profile.iter().map(|s| s.len()).max().unwrap();
     let max_status = column_status.iter().map(|s| s.len()).max().unwrap();
     let max_command = column_command.iter().map(|s| s.len()).max().unwrap();
     let max_args = column_args.iter().map(|s| s.len()).max().unwrap();
     let max_pid = column_pid.iter().map(|s| s.len()).max().unwrap();
     let max_code = column_code.iter().map(|s| s.len()).max().unwrap();
     let max_type = column_type.iter().map(|s| s.len()).max().unwrap();
-
+    let max_schedule = column_schedule.iter().map(|s| s.len()).max().unwrap();
+    let max_next = column_next.iter().map(|s| s.len()).max().unwrap();
+    let max_instances = column_instances.iter().map(|s| s.len()).max().unwrap();
+    let max_env = column_env.iter().map(|s| s.len()).max().unwrap();
+    let max_watch = column_watch.iter().map(|s| s.len()).max().unwrap();
+
+    let max_labels: usize = column_labels
+        .iter()
+        .map(|c| c.iter().map(|s| s.len()).max().unwrap() + 3)
+        .sum();
     let max_sum = max_id
         + max_group
         + max_name
+        + max_profile
         + max_status
         + max_command
         + max_args
         + max_pid
         + max_code
         + max_type
-        + 3 * (9 - 1)
-        + 5;
+        + max_schedule
+        + max_next
+        + max_instances
+        + max_env
+        + max_watch
+        + 3 * (15 - 1)
+        + 5
+        + max_labels;
 
     for i in 0..column_id.len() {
         println!("{:-<max_sum$}", "", max_sum = max_sum);
-        println!(
-            "| {: <max_id$} | {: <max_group$}  | {: <max_name$} | {: <max_status$} | {: <max_command$} | {: <max_args$} | {: <max_pid$} | {: <max_code$} | {: <max_type$} |",
+        print!(
+            "| {: <max_id$} | {: <max_group$}  | {: <max_name$} | {: <max_profile$} | {: <max_status$} | {: <max_command$} | {: <max_args$} | {: <max_pid$} | {: <max_code$} | {: <max_type$} | {: <max_schedule$} | {: <max_next$} | {: <max_instances$} | {: <max_env$} | {: <max_watch$} |",
             column_id[i],
             column_group[i],
             column_name[i],
+            column_profile[i],
             column_status[i],
             column_command[i],
             column_args[i],
             column_pid[i],
             column_code[i],
             column_type[i],
+            column_schedule[i],
+            column_next[i],
+            column_instances[i],
+            column_env[i],
+            column_watch[i],
             max_id = max_id,
             max_name = max_name,
+            max_profile = max_profile,
             max_status = max_status,
             max_command = max_command,
             max_args = max_args,
             max_pid = max_pid,
             max_code = max_code,
             max_type = max_type,
+            max_schedule = max_schedule,
+            max_next = max_next,
+            max_instances = max_instances,
+            max_env = max_env,
+            max_watch = max_watch,
         );
+        for column in &column_labels {
+            let width = column.iter().map(|s| s.len()).max().unwrap();
+            print!(" {: <width$} |", column[i], width = width);
+        }
+        println!();
     }
     println!("{:-<max_sum$}", "", max_sum = max_sum);
     println!(
@@ -459,7 +699,7 @@ pub async fn print_result_more(res: Vec<Response>) {
     );
 }
 
-pub async fn print_result_less(res: Vec<Response>) {
+pub async fn print_result_less(res: Vec<Response>, labels: &[String]) {
     let mut status: Vec<Status> = Vec::new();
     for r in res {
         if r.code != 10000 {
@@ -470,6 +710,10 @@ pub async fn print_result_less(res: Vec<Response>) {
             match data {
                 crate::common::handle::Data::None => {}
                 crate::common::handle::Data::String(_) => {}
+                crate::common::handle::Data::History(_, _) => {}
+                crate::common::handle::Data::Results(_) => {}
+                crate::common::handle::Data::Plan(_) => {}
+                crate::common::handle::Data::Tasks(_) => {}
                 crate::common::handle::Data::Status(s) => {
                     for i in &s {
                         status.push(i.clone());
@@ -478,6 +722,8 @@ pub async fn print_result_less(res: Vec<Response>) {
             }
         }
     }
+    let status = group_instances(status);
+    let column_labels = label_columns(&status, labels);
 
     let mut total = 0;
     let mut total_added = 0;
@@ -497,9 +743,11 @@ pub async fn print_result_less(res: Vec<Response>) {
     column_status.push("Status".bold());
 
     for s in status {
-        total += 1;
+        if s.instances.is_none() {
+            total += 1;
+        }
         column_id.push(s.id.to_string().italic());
-        column_name.push(s.name.normal());
+        column_name.push(display_name(&s).normal());
         match s.status {
             Some(t) => match t.as_str() {
                 "added" => {
@@ -528,6 +776,8 @@ pub async fn print_result_less(res: Vec<Response>) {
                     column_status.push(t.yellow())
                 }
                 "executing" => column_status.push(t.green()),
+                "failed" => column_status.push(t.red()),
+                "completed" => column_status.push(t.blue()),
                 _ => column_status.push(t.normal()),
             },
             None => column_status.push("".normal()),
@@ -537,11 +787,15 @@ pub async fn print_result_less(res: Vec<Response>) {
     let max_name = column_name.iter().map(|s| s.len()).max().unwrap();
     let max_status = column_status.iter().map(|s| s.len()).max().unwrap();
 
-    let max_sum = max_id + max_name + max_status + 3 * (4 - 1) + 1;
+    let max_labels: usize = column_labels
+        .iter()
+        .map(|c| c.iter().map(|s| s.len()).max().unwrap() + 3)
+        .sum();
+    let max_sum = max_id + max_name + max_status + 3 * (4 - 1) + 1 + max_labels;
 
     for i in 0..column_id.len() {
         println!("{:-<max_sum$}", "", max_sum = max_sum);
-        println!(
+        print!(
             "| {: <max_id$} | {: <max_name$} | {: <max_status$} |",
             column_id[i],
             column_name[i],
@@ -550,6 +804,11 @@ pub async fn print_result_less(res: Vec<Response>) {
             max_name = max_name,
             max_status = max_status,
         );
+        for column in &column_labels {
+            let width = column.iter().map(|s| s.len()).max().unwrap();
+            print!(" {: <width$} |", column[i], width = width);
+        }
+        println!();
     }
     println!("{:-<max_sum$}", "", max_sum = max_sum);
     println!(
@@ -1,18 +1,52 @@
 use crate::common::{
-    arg::AddArgs,
+    arg::RunArgs,
     config::Config,
-    handle::{Command, Request, Response},
+    handle::{Command, Data, Request, Response},
+    task::{AsyncTask, Task, TaskFlag, TaskType},
 };
-use std::error::Error;
+use std::{
+    error::Error,
+    ffi::OsString,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::signal::unix::{signal, SignalKind};
 
 use crate::{engine::send, utils::print_result};
 
 use super::task_to_request;
 
-pub async fn run(args: AddArgs, config: Config) -> Result<(), Box<dyn Error>> {
-    let tasks = task_to_request(args, config.clone()).await?;
+pub async fn run(args: RunArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    let mut task_args = args.task;
+    // watchmen run -- command args...
+    if let Some((command, rest)) = args.cmd.split_first() {
+        task_args.command = Some(command.clone());
+        task_args.args = Some(rest.to_vec());
+    }
+    let mut tasks = task_to_request(task_args, config.clone()).await?;
+    for task in tasks.iter_mut() {
+        // 命令行指定的任务作为异步任务运行，不自动重启
+        if let TaskType::None = task.task_type {
+            task.task_type = TaskType::Async(AsyncTask {
+                max_restart: None,
+                has_restart: 0,
+                started_at: 0,
+                stopped_at: 0,
+                watch_restart: 0,
+                last_change: None,
+            });
+        }
+        if args.rm {
+            task.ttl_after_finished = Some(0);
+        }
+    }
     if tasks.is_empty() {
         print_result(vec![Response::wrong("No task to run".to_string())]).await;
+    } else if args.foreground {
+        foreground(tasks, args.rm, config).await?;
     } else {
         let mut requests = Vec::new();
         for task in tasks {
@@ -24,3 +58,178 @@ pub async fn run(args: AddArgs, config: Config) -> Result<(), Box<dyn Error>> {
     }
     Ok(())
 }
+
+/// Copy new content of log file to output
+///
+/// # params
+///
+/// - `path`: log file
+/// - `offset`: size of log file already copied
+/// - `out`: output
+fn follow(path: &Path, offset: &mut u64, out: &mut dyn Write) -> io::Result<()> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(()),
+    };
+    file.seek(SeekFrom::Start(*offset))?;
+    *offset += io::copy(&mut file, out)?;
+    out.flush()
+}
+
+/// Create a temporary directory with a random name that only the current user can access
+fn private_dir() -> io::Result<PathBuf> {
+    let template = std::env::temp_dir().join("watchmen-XXXXXX");
+    let mut path = template.as_os_str().as_bytes().to_vec();
+    path.push(0);
+    // mkdtemp 以 0700 权限创建目录，已存在的文件或链接不会被使用
+    let res = unsafe { libc::mkdtemp(path.as_mut_ptr() as *mut libc::c_char) };
+    if res.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    path.pop();
+    Ok(PathBuf::from(OsString::from_vec(path)))
+}
+
+/// Run task and stream its output until it exits, then exit with its exit code, or 128 plus
+/// the signal number if it was killed by a signal
+///
+/// # params
+///
+/// - `tasks`: task to run, only one task is supported
+/// - `rm`: remove task after it exited
+/// - `config`: config
+async fn foreground(tasks: Vec<Task>, rm: bool, config: Config) -> Result<(), Box<dyn Error>> {
+    let mut task = match <[Task; 1]>::try_from(tasks) {
+        Ok([task]) => task,
+        Err(_) => return Err("Foreground requires exactly one task".into()),
+    };
+    // 需要按名称查找任务状态，未指定名称时在此生成
+    if task.name.is_empty() {
+        let command = Path::new(&task.command)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or("task".to_string());
+        task.name = format!("{}-{}", command, std::process::id());
+    }
+    // 未配置输出文件时写入私有临时目录，文件名不使用任务名称
+    let mut temporary = None;
+    if task.stdout.is_none() || task.stderr.is_none() {
+        let dir = private_dir()?;
+        for (file, name) in [(&mut task.stdout, "stdout"), (&mut task.stderr, "stderr")] {
+            if file.is_none() {
+                *file = Some(dir.join(name).to_string_lossy().to_string());
+            }
+        }
+        temporary = Some(dir);
+    }
+    let stdout = PathBuf::from(task.stdout.clone().unwrap_or_default());
+    let stderr = PathBuf::from(task.stderr.clone().unwrap_or_default());
+    let mut stdout_offset = std::fs::metadata(&stdout).map(|m| m.len()).unwrap_or(0);
+    let mut stderr_offset = std::fs::metadata(&stderr).map(|m| m.len()).unwrap_or(0);
+    // 由本命令删除任务，守护进程按 ttl 兜底删除
+    if rm {
+        task.ttl_after_finished = Some(60);
+    }
+
+    // 提交任务前监听 Ctrl-C，任务 id 返回前的中断不会留下运行的任务
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let name = task.name.clone();
+    let res = send(
+        config.clone(),
+        vec![Request {
+            command: Command::Run(task),
+        }],
+    )
+    .await?;
+    if res.iter().any(|r| r.code != 10000) {
+        if let Some(dir) = temporary {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        print_result(res).await;
+        std::process::exit(1);
+    }
+
+    let condition = TaskFlag {
+        id: 0,
+        name: Some(name),
+        group: None,
+        mat: false,
+        selector: None,
+    };
+    let mut id = 0;
+    let (mut interrupted, mut stopped, mut remove) = (false, false, rm);
+    let code = loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(200)) => {}
+            _ = interrupt.recv(), if !interrupted => {
+                interrupted = true;
+                // 尚未得到 id 时，得到 id 后停止并删除任务
+                if id == 0 {
+                    remove = true;
+                }
+            }
+        }
+        follow(&stdout, &mut stdout_offset, &mut io::stdout())?;
+        follow(&stderr, &mut stderr_offset, &mut io::stderr())?;
+
+        let res = send(
+            config.clone(),
+            vec![Request {
+                command: Command::List(Some(condition.clone())),
+            }],
+        )
+        .await?;
+        let status = res.into_iter().find_map(|r| match r.data {
+            Some(Data::Status(s)) => s.into_iter().next(),
+            _ => None,
+        });
+        match status {
+            Some(s) => {
+                id = s.id;
+                if matches!(
+                    s.status.as_deref(),
+                    Some("stopped" | "completed" | "failed")
+                ) {
+                    break (s.code, s.signal);
+                }
+                // 中断后停止任务，id 返回前的中断在得到 id 时处理
+                if interrupted && !stopped {
+                    stopped = true;
+                    send(
+                        config.clone(),
+                        vec![Request {
+                            command: Command::Stop(TaskFlag::new(id)),
+                        }],
+                    )
+                    .await?;
+                }
+            }
+            // 任务已被删除
+            None => break (None, None),
+        }
+    };
+    follow(&stdout, &mut stdout_offset, &mut io::stdout())?;
+    follow(&stderr, &mut stderr_offset, &mut io::stderr())?;
+
+    if remove && id > 0 {
+        send(
+            config,
+            vec![Request {
+                command: Command::Remove(TaskFlag::new(id)),
+            }],
+        )
+        .await?;
+    }
+    if let Some(dir) = temporary {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    if interrupted {
+        std::process::exit(130);
+    }
+    // 被信号终止时与 shell 一致，退出码为 128 加信号值
+    std::process::exit(match code {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    });
+}
@@ -0,0 +1,26 @@
+use crate::common::{
+    arg::ScaleArgs,
+    config::Config,
+    handle::{Command, Request},
+    task::TaskFlag,
+};
+use std::error::Error;
+
+use crate::{engine::send, utils::print_result};
+
+pub async fn scale(args: ScaleArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    let request = Request {
+        command: Command::Scale(
+            TaskFlag {
+                id: 0,
+                name: Some(args.name),
+                group: None,
+                mat: false,
+                selector: None,
+            },
+            args.count,
+        ),
+    };
+    print_result(send(config, vec![request]).await?).await;
+    Ok(())
+}
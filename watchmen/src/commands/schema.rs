@@ -0,0 +1,11 @@
+use crate::common::{arg::SchemaArgs, schema};
+use std::error::Error;
+
+pub async fn schema(args: SchemaArgs) -> Result<(), Box<dyn Error>> {
+    let schema = match args.kind.as_str() {
+        "config" => schema::config()?,
+        _ => schema::task()?,
+    };
+    println!("{}", schema);
+    Ok(())
+}
@@ -0,0 +1,1267 @@
+use crate::common::{
+    arg::ValidateArgs,
+    config::{get_with_home_path, Config},
+    env::split_words,
+    process::{parse_signal, Attributes},
+    task::{
+        AsyncTask, ConcurrencyPolicy, Ionice, OnceTask, PeriodicTask, Rlimits, ScheduledTask, Task,
+        TaskType, Watch,
+    },
+    trait_task::{extend, yaml_to_json, Sections},
+};
+use chrono::{Datelike, Local, NaiveDate};
+use colored::Colorize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::CString,
+    fmt::Display,
+    ops::Range,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::Path,
+};
+
+use crate::utils::recursive_search_files;
+
+/// INI keys of integer values
+const INI_INT_KEYS: [&str; 11] = [
+    "id",
+    "nice",
+    "oom_score_adj",
+    "year",
+    "month",
+    "day",
+    "hour",
+    "minute",
+    "second",
+    "started_after",
+    "interval",
+];
+
+/// INI keys of non-negative integer values
+const INI_UINT_KEYS: [&str; 20] = [
+    "rlimit_nofile",
+    "rlimit_nproc",
+    "rlimit_core",
+    "rlimit_as",
+    "rlimit_cpu",
+    "ionice_level",
+    "watch_debounce",
+    "watch_ignore_starting",
+    "instances",
+    "port",
+    "max_concurrent_runs",
+    "timeout",
+    "kill_timeout",
+    "retry_on_timeout",
+    "retries",
+    "retry_delay",
+    "ttl_after_finished",
+    "jitter",
+    "after",
+    "max_restart",
+];
+
+/// INI keys of boolean values
+const INI_BOOL_KEYS: [&str; 3] = ["stdin", "sync", "align"];
+
+/// INI task types
+const INI_TASK_TYPES: [&str; 4] = ["scheduled", "async", "once", "periodic"];
+
+/// Problem found in task config file
+struct Diagnostic {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+/// Task config file, tasks are located by their byte ranges in the file
+struct Source {
+    file: String,
+    text: String,
+    /// toml, ini, json or yaml
+    format: String,
+    /// Byte ranges of tasks, in the order tasks are defined
+    ranges: Vec<Range<usize>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Source {
+    /// Line and column of byte offset, both start from 1
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, column)
+    }
+
+    fn report(&mut self, offset: usize, message: String) {
+        let (line, column) = self.position(offset);
+        self.report_at(line, column, message);
+    }
+
+    fn report_at(&mut self, line: usize, column: usize, message: String) {
+        self.diagnostics.push(Diagnostic {
+            line,
+            column,
+            message,
+        });
+    }
+
+    /// Report problem at key of task
+    ///
+    /// # params
+    ///
+    /// - `index`: index of task in file
+    /// - `keys`: path of key, e.g. `["task_type", "Periodic", "interval"]`
+    /// - `message`: description of problem
+    fn report_key(&mut self, index: usize, keys: &[&str], message: String) {
+        let offset = self.locate(index, keys);
+        self.report(offset, message);
+    }
+
+    /// Offset of key in range
+    fn find(&self, range: Range<usize>, key: &str) -> Option<usize> {
+        let key = regex::escape(key);
+        let pattern = match self.format.as_str() {
+            "toml" => format!(r#"(?m)(?:^|[\s{{,.\[])"?({})"?\s*[=.\]]"#, key),
+            "json" => format!(r#""({})"\s*:"#, key),
+            "yaml" => format!(
+                r#"(?m)(?:(?:^|[\s{{,])["']?({})["']?\s*:|!({})\b)"#,
+                key, key
+            ),
+            _ => format!(r"(?m)^[ \t]*({})[ \t]*[=:]", key),
+        };
+        let regex = Regex::new(&pattern).ok()?;
+        let captures = regex.captures(&self.text[range.clone()])?;
+        let key = captures.get(1).or(captures.get(2))?;
+        Some(range.start + key.start())
+    }
+
+    /// Offset of key of task, nested keys are searched after their parents, the task
+    /// itself if key is not found
+    fn locate(&self, index: usize, keys: &[&str]) -> usize {
+        let range = self
+            .ranges
+            .get(index)
+            .cloned()
+            .unwrap_or(0..self.text.len());
+        let mut offset = range.start;
+        for key in keys {
+            if let Some(found) = self
+                .find(offset..range.end, key)
+                .or_else(|| self.find(range.clone(), key))
+            {
+                offset = found;
+            }
+        }
+        offset
+    }
+}
+
+pub async fn validate(args: ValidateArgs, config: Config) -> Result<(), Box<dyn Error>> {
+    let mat;
+    if let Some(matc) = args.regex {
+        // 优先使用命令行参数
+        mat = matc;
+    } else if let Some(matc) = config.watchmen.mat.clone() {
+        // 其次使用配置文件参数
+        mat = matc;
+    } else {
+        // 最后使用默认参数
+        mat = String::from(r"^.*\.(toml|ini|json|yaml|yml)$");
+    }
+    let regex: Regex = Regex::new(&mat)?;
+    let mut files = Vec::new();
+    for path in args.paths {
+        if Path::new(&path).is_dir() {
+            let mut matched_files = Vec::new();
+            recursive_search_files(&path, &regex, &mut matched_files);
+            matched_files.sort();
+            files.extend(matched_files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut sources = Vec::new();
+    for file in files {
+        let mut source = Source {
+            format: match Path::new(&file).extension().and_then(|e| e.to_str()) {
+                Some("yml") => "yaml".to_string(),
+                Some(ext) => ext.to_string(),
+                None => String::new(),
+            },
+            text: String::new(),
+            file,
+            ranges: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let tasks = match std::fs::read_to_string(&source.file) {
+            Ok(text) => {
+                source.text = text;
+                load(&mut source, args.profile.as_deref())
+            }
+            Err(e) => {
+                source.report_at(1, 1, e.to_string());
+                Vec::new()
+            }
+        };
+        sources.push((source, tasks));
+    }
+
+    // 名称与 id 在所有文件中唯一
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut ids: HashMap<i64, String> = HashMap::new();
+    let mut count = 0;
+    for (source, tasks) in sources.iter_mut() {
+        for (index, task) in tasks.iter() {
+            count += 1;
+            check(source, *index, task);
+            if !task.name.is_empty() {
+                let offset = source.locate(*index, &["name"]);
+                let (line, column) = source.position(offset);
+                let here = format!("{}:{}:{}", source.file, line, column);
+                match names.get(&task.name) {
+                    Some(first) => source.report(
+                        offset,
+                        format!("Duplicate name `{}`, first defined at {}", task.name, first),
+                    ),
+                    None => {
+                        names.insert(task.name.clone(), here);
+                    }
+                }
+            }
+            if task.id > 0 {
+                let offset = source.locate(*index, &["id"]);
+                let (line, column) = source.position(offset);
+                let here = format!("{}:{}:{}", source.file, line, column);
+                match ids.get(&task.id) {
+                    Some(first) => source.report(
+                        offset,
+                        format!("Duplicate id `{}`, first defined at {}", task.id, first),
+                    ),
+                    None => {
+                        ids.insert(task.id, here);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut errors = 0;
+    for (source, _) in sources.iter_mut() {
+        source.diagnostics.sort_by_key(|d| (d.line, d.column));
+        for d in &source.diagnostics {
+            errors += 1;
+            println!(
+                "{}:{}:{}: {} {}",
+                source.file,
+                d.line,
+                d.column,
+                "error:".red().bold(),
+                d.message
+            );
+        }
+    }
+    if errors > 0 {
+        println!(
+            "{} {} errors in {} files, {} tasks",
+            "Invalid:".red().bold(),
+            errors,
+            sources.len(),
+            count
+        );
+        std::process::exit(1);
+    }
+    println!(
+        "{} {} files, {} tasks, no problems found",
+        "Valid:".green().bold(),
+        sources.len(),
+        count
+    );
+    Ok(())
+}
+
+/// Message of error without location, location is reported separately
+fn message(e: &dyn Display) -> String {
+    let e = e.to_string();
+    match Regex::new(r" at line \d+ column \d+") {
+        Ok(regex) => regex.replace_all(&e, "").to_string(),
+        Err(_) => e,
+    }
+}
+
+/// Tasks of task config file and their indexes in file, problems are reported to source
+///
+/// # params
+///
+/// - `source`: task config file
+/// - `profile`: profile applied to tasks
+fn load(source: &mut Source, profile: Option<&str>) -> Vec<(usize, Task)> {
+    let mut file = Sections::default();
+    match source.format.as_str() {
+        "ini" => {
+            // INI 文件不支持 profile
+            if profile.is_some() {
+                source.report_at(
+                    1,
+                    1,
+                    "Profiles are not supported in INI task files".to_string(),
+                );
+                return Vec::new();
+            }
+            return load_ini(source);
+        }
+        "toml" => {
+            source.ranges = toml_ranges(&source.text);
+            match toml::from_str::<toml::Table>(&source.text) {
+                Ok(table) => match serde_json::to_value(table) {
+                    Ok(root) => sections(source, root, &mut file),
+                    Err(e) => {
+                        source.report_at(1, 1, e.to_string());
+                        return Vec::new();
+                    }
+                },
+                Err(e) => {
+                    let offset = e.span().map(|s| s.start).unwrap_or(0);
+                    source.report(offset, e.message().to_string());
+                    return Vec::new();
+                }
+            }
+        }
+        "json" => {
+            source.ranges = json_ranges(&source.text);
+            match serde_json::from_str::<Value>(&source.text) {
+                Ok(Value::Array(tasks)) => file.task = tasks,
+                Ok(root) => sections(source, root, &mut file),
+                Err(e) => {
+                    source.report_at(e.line(), e.column(), message(&e));
+                    return Vec::new();
+                }
+            }
+        }
+        "yaml" => {
+            source.ranges = yaml_ranges(&source.text);
+            let text = source.text.clone();
+            for document in serde_yaml::Deserializer::from_str(&text) {
+                let value = match serde_yaml::Value::deserialize(document) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        let offset = e.location().map(|l| l.index()).unwrap_or(0);
+                        source.report(offset, message(&e));
+                        return Vec::new();
+                    }
+                };
+                match yaml_to_json(value) {
+                    // 空文档跳过
+                    Ok(Value::Null) => {}
+                    Ok(root) => sections(source, root, &mut file),
+                    Err(e) => {
+                        source.report_at(1, 1, e.to_string());
+                        return Vec::new();
+                    }
+                }
+            }
+        }
+        _ => {
+            source.report_at(
+                1,
+                1,
+                "Invalid file extension, expected toml, ini, json, yaml or yml".to_string(),
+            );
+            return Vec::new();
+        }
+    }
+
+    let profile_offset = source.find(0..source.text.len(), "profile").unwrap_or(0);
+    let names: Vec<&str> = file
+        .task
+        .iter()
+        .filter_map(|t| t.get("name").and_then(Value::as_str))
+        .collect();
+    for (name, overlay) in &file.profile {
+        let entries = overlay.get("task").and_then(Value::as_array);
+        for entry in entries.into_iter().flatten() {
+            match entry.get("name").and_then(Value::as_str) {
+                Some(task) if names.contains(&task) => {}
+                Some(task) => {
+                    let pattern = format!(r#""?name"?\s*[=:]\s*["']?{}["']?"#, regex::escape(task));
+                    let offset = Regex::new(&pattern)
+                        .ok()
+                        .and_then(|r| r.find(&source.text).map(|m| m.start()))
+                        .unwrap_or(profile_offset);
+                    source.report(
+                        offset,
+                        format!("Profile [{}] patches unknown task `{}`", name, task),
+                    )
+                }
+                None => source.report(
+                    profile_offset,
+                    format!("Profile [{}] patches a task without name", name),
+                ),
+            }
+        }
+    }
+    let profile = match file.select(profile) {
+        Ok(profile) => profile,
+        Err(e) => {
+            source.report(profile_offset, e.to_string());
+            return Vec::new();
+        }
+    };
+
+    let mut tasks = Vec::new();
+    for (index, value) in file.task.iter().enumerate() {
+        let value = match extend(&file.template, profile, value.clone()) {
+            Ok(value) => value,
+            Err(e) => {
+                source.report_key(index, &["extends"], e.to_string());
+                continue;
+            }
+        };
+        if let Some(task) = task(source, index, value) {
+            tasks.push((index, task));
+        }
+    }
+    tasks
+}
+
+/// Sections of document, which is a table with `template`, `task` and `profile` sections
+fn sections(source: &mut Source, root: Value, file: &mut Sections) {
+    let Value::Object(root) = root else {
+        source.report_at(1, 1, "Expected a table with `task` array".to_string());
+        return;
+    };
+    let mut known = Map::new();
+    for (key, value) in root {
+        let offset = source.find(0..source.text.len(), &key).unwrap_or(0);
+        match (key.as_str(), &value) {
+            ("task" | "template", Value::Array(_)) | ("profile", Value::Object(_)) => {
+                known.insert(key, value);
+            }
+            ("task" | "template", _) => {
+                source.report(offset, format!("Expected `{}` to be an array", key))
+            }
+            ("profile", _) => source.report(offset, "Expected `profile` to be a table".to_string()),
+            _ => source.report(offset, format!("Unknown key `{}`", key)),
+        }
+    }
+    if let Err(e) = file.read(Value::Object(known)) {
+        let offset = source.find(0..source.text.len(), "profile").unwrap_or(0);
+        source.report(offset, e.to_string());
+    }
+}
+
+/// Names of fields of value serialized as map
+fn fields<T: Serialize>(value: &T) -> Vec<String> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(map)) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Task of value, unknown keys and invalid values are reported
+fn task(source: &mut Source, index: usize, value: Value) -> Option<Task> {
+    let Value::Object(map) = &value else {
+        source.report_key(index, &[], "Expected a table of task".to_string());
+        return None;
+    };
+
+    let known = fields(&Task::default());
+    for key in map.keys() {
+        if !known.contains(key) {
+            source.report_key(index, &[key], format!("Unknown key `{}`", key));
+        }
+    }
+    let nested = [
+        (
+            "rlimits",
+            fields(&Rlimits {
+                nofile: None,
+                nproc: None,
+                core: None,
+                address_space: None,
+                cpu: None,
+            }),
+        ),
+        (
+            "ionice",
+            fields(&Ionice {
+                class: String::new(),
+                level: None,
+            }),
+        ),
+        (
+            "watch",
+            fields(&Watch {
+                paths: Vec::new(),
+                include: Vec::new(),
+                exclude: Vec::new(),
+                debounce: None,
+                ignore_starting: None,
+            }),
+        ),
+    ];
+    for (parent, known) in nested {
+        if let Some(Value::Object(m)) = map.get(parent) {
+            for key in m.keys() {
+                if !known.contains(key) {
+                    source.report_key(index, &[parent, key], format!("Unknown key `{}`", key));
+                }
+            }
+        }
+    }
+    if let Some(Value::Object(tt)) = map.get("task_type") {
+        for (variant, value) in tt {
+            let known = match variant.as_str() {
+                "Scheduled" => fields(&ScheduledTask {
+                    year: None,
+                    month: None,
+                    day: None,
+                    hour: None,
+                    minute: None,
+                    second: None,
+                }),
+                "Async" => fields(&AsyncTask {
+                    max_restart: None,
+                    has_restart: 0,
+                    started_at: 0,
+                    stopped_at: 0,
+                    watch_restart: 0,
+                    last_change: None,
+                }),
+                "Periodic" => fields(&PeriodicTask {
+                    started_after: 0,
+                    interval: 0,
+                    last_run: 0,
+                    sync: false,
+                    jitter: 0,
+                    align: false,
+                    next_run: 0,
+                    slot: 0,
+                }),
+                "Once" => fields(&OnceTask {
+                    at: None,
+                    after: None,
+                    run_at: 0,
+                }),
+                // 未知类型由反序列化报告
+                _ => continue,
+            };
+            if let Value::Object(m) = value {
+                for key in m.keys() {
+                    if !known.contains(key) {
+                        source.report_key(
+                            index,
+                            &["task_type", variant, key],
+                            format!("Unknown key `{}`", key),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    match serde_path_to_error::deserialize::<_, Task>(value) {
+        Ok(task) => Some(task),
+        Err(e) => {
+            let keys: Vec<String> = e
+                .path()
+                .iter()
+                .filter_map(|segment| match segment {
+                    serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+                    serde_path_to_error::Segment::Enum { variant } => Some(variant.clone()),
+                    _ => None,
+                })
+                .collect();
+            let keys: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+            source.report_key(index, &keys, e.inner().to_string());
+            None
+        }
+    }
+}
+
+/// Tasks of INI file, each section is a task
+fn load_ini(source: &mut Source) -> Vec<(usize, Task)> {
+    let errors = source.diagnostics.len();
+    let header = Regex::new(r"^\[([^\]]*)\]$").unwrap();
+    let mut starts = Vec::new();
+    // 每个任务的键，用于检查缺少的键及按名称对应任务
+    let mut sections: Vec<HashMap<String, String>> = Vec::new();
+    let mut profiles = Vec::new();
+    let mut offset = 0;
+    let text = source.text.clone();
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') {
+            continue;
+        }
+        let at = start + line.len() - line.trim_start().len();
+        if let Some(name) = header.captures(trimmed).map(|c| c[1].trim().to_lowercase()) {
+            if name == "profile" || name.starts_with("profile.") {
+                source.report(
+                    at,
+                    format!(
+                        "Profiles are not supported in INI task files, found [{}]",
+                        name
+                    ),
+                );
+                profiles.push(sections.len());
+            }
+            starts.push(at);
+            sections.push(HashMap::new());
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(['=', ':']) else {
+            source.report(at, format!("Expected `key = value`, found `{}`", trimmed));
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let Some(keys) = sections.last_mut() else {
+            source.report(at, format!("Key `{}` is outside of task section", key));
+            continue;
+        };
+        keys.insert(key.clone(), value.to_string());
+        if let Err(e) = check_ini(&key, value) {
+            source.report(at, e);
+        }
+    }
+    source.ranges = split(&source.text, starts);
+    for (index, keys) in sections.iter().enumerate() {
+        if profiles.contains(&index) {
+            continue;
+        }
+        for key in ["name", "command", "task_type"] {
+            if !keys.contains_key(key) {
+                source.report_key(index, &[], format!("Missing key `{}`", key));
+            }
+        }
+    }
+    if source.diagnostics.len() > errors {
+        return Vec::new();
+    }
+
+    match Task::from_ini(Path::new(&source.file)) {
+        Ok(tasks) => tasks
+            .task
+            .into_iter()
+            .map(|task| {
+                let index = sections
+                    .iter()
+                    .position(|keys| keys.get("name") == Some(&task.name))
+                    .unwrap_or(usize::MAX);
+                (index, task)
+            })
+            .collect(),
+        Err(e) => {
+            source.report_at(1, 1, e.to_string());
+            Vec::new()
+        }
+    }
+}
+
+/// Check INI key and value as `Task::from_ini` reads them
+fn check_ini(key: &str, value: &str) -> Result<(), String> {
+    if !Task::INI_KEYS.contains(&key) {
+        return Err(format!("Unknown key `{}`", key));
+    }
+    let invalid = |expected: &str| {
+        Err(format!(
+            "Invalid value `{}` of `{}`, expected {}",
+            value, key, expected
+        ))
+    };
+    if INI_INT_KEYS.contains(&key) && value.parse::<i64>().is_err() {
+        return invalid("an integer");
+    }
+    if INI_UINT_KEYS.contains(&key) && value.parse::<u64>().is_err() {
+        return invalid("a non-negative integer");
+    }
+    if INI_BOOL_KEYS.contains(&key) && !matches!(value.to_lowercase().as_str(), "true" | "false") {
+        return invalid("true or false");
+    }
+    match key {
+        "retry_backoff" if value.parse::<f64>().is_err() => invalid("a number"),
+        "port" if value.parse::<u16>().is_err() => invalid("a port (0 ~ 65535)"),
+        "nice" | "oom_score_adj" if value.parse::<i32>().is_err() => invalid("a 32-bit integer"),
+        "ionice_level" if value.parse::<u32>().is_err() => invalid("a non-negative 32-bit integer"),
+        "task_type" if !INI_TASK_TYPES.contains(&value) => {
+            invalid("one of scheduled, async, once, periodic")
+        }
+        "concurrency_policy" if ConcurrencyPolicy::parse(value).is_none() => {
+            invalid("one of allow, forbid, replace, queue")
+        }
+        "stop_signal" if parse_signal(value).is_none() => invalid("a signal name or number"),
+        "cpu_affinity"
+            if value
+                .split_whitespace()
+                .any(|c| c.parse::<usize>().is_err()) =>
+        {
+            invalid("cpu indexes separated by space")
+        }
+        "env" | "labels" => {
+            for word in split_words(value).map_err(|e| e.to_string())? {
+                if !word.contains('=') {
+                    return Err(format!(
+                        "Invalid value `{}` of `{}`, expected KEY=VALUE",
+                        word, key
+                    ));
+                }
+            }
+            Ok(())
+        }
+        "inherit_env" | "env_file" | "watch" | "watch_include" | "watch_exclude" => {
+            split_words(value).map(|_| ()).map_err(|e| e.to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Byte ranges between starts
+fn split(text: &str, starts: Vec<usize>) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(text.len());
+        ranges.push(*start..end);
+    }
+    ranges
+}
+
+/// Byte ranges of tasks in TOML file, a task starts at `[[task]]` and ends at the next
+/// header which is not a sub-table of task, e.g. `[[template]]` or `[profile.prod]`
+fn toml_ranges(text: &str) -> Vec<Range<usize>> {
+    let regex = Regex::new(r"(?m)^[ \t]*(\[\[?)[ \t]*([^\]\s]+)[ \t]*\]").unwrap();
+    let headers: Vec<(usize, bool)> = regex
+        .captures_iter(text)
+        .filter(|c| !c[2].starts_with("task."))
+        .filter_map(|c| Some((c.get(0)?.start(), &c[1] == "[[" && &c[2] == "task")))
+        .collect();
+    let mut ranges = Vec::new();
+    for (i, (start, task)) in headers.iter().enumerate() {
+        if *task {
+            let end = headers.get(i + 1).map(|h| h.0).unwrap_or(text.len());
+            ranges.push(*start..end);
+        }
+    }
+    ranges
+}
+
+/// Byte ranges of tasks in JSON file, tasks are objects of the top-level array or of
+/// the `task` array
+fn json_ranges(text: &str) -> Vec<Range<usize>> {
+    let begin = if text.trim_start().starts_with('[') {
+        0
+    } else {
+        // 只使用顶层的 task 数组，profile 中也有 task 数组
+        let regex = Regex::new(r#""task"\s*:\s*\["#).unwrap();
+        let task = regex
+            .find_iter(text)
+            .find(|m| json_depth(&text[..m.start()]) == 1);
+        match task {
+            Some(m) => m.end() - 1,
+            None => return Vec::new(),
+        }
+    };
+    let mut ranges = Vec::new();
+    let (mut depth, mut start, mut string, mut escape) = (0, 0, false, false);
+    for (i, c) in text[begin..].char_indices() {
+        let i = begin + i;
+        if string {
+            if escape {
+                escape = false;
+            } else if c == '\\' {
+                escape = true;
+            } else if c == '"' {
+                string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => string = true,
+            '[' | '{' => {
+                if depth == 1 && c == '{' {
+                    start = i;
+                }
+                depth += 1;
+            }
+            ']' | '}' => {
+                depth -= 1;
+                if depth == 1 && c == '}' {
+                    ranges.push(start..i + 1);
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Nesting depth of JSON text at its end, brackets in strings are skipped
+fn json_depth(text: &str) -> usize {
+    let (mut depth, mut string, mut escape) = (0usize, false, false);
+    for c in text.chars() {
+        if string {
+            if escape {
+                escape = false;
+            } else if c == '\\' {
+                escape = true;
+            } else if c == '"' {
+                string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => string = true,
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Byte ranges of tasks in YAML file, tasks are items of `task` list of each document, a
+/// task ends at the next item or at the end of the list
+fn yaml_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let (mut tasks, mut indent, mut start) = (false, None, None);
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let width = line.len() - trimmed.len();
+        let item = tasks && (trimmed.starts_with("- ") || trimmed.trim_end() == "-");
+        if item && *indent.get_or_insert(width) == width {
+            if let Some(start) = start.replace(offset + width) {
+                ranges.push(start..offset);
+            }
+        } else if line.starts_with("---")
+            || (width == 0 && !item && !trimmed.trim().is_empty() && !trimmed.starts_with('#'))
+        {
+            if let Some(start) = start.take() {
+                ranges.push(start..offset);
+            }
+            tasks = line.starts_with("task:");
+            indent = None;
+        }
+        offset += line.len();
+    }
+    if let Some(start) = start {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+/// Check task as daemon would run it
+fn check(source: &mut Source, index: usize, task: &Task) {
+    if task.command.is_empty() {
+        source.report_key(index, &["command"], "Command is empty".to_string());
+    } else if !command_exists(task) {
+        let message = if task.command.contains('/') {
+            format!("Command `{}` not found or not executable", task.command)
+        } else {
+            format!("Command `{}` not found in PATH", task.command)
+        };
+        source.report_key(index, &["command"], message);
+    }
+    if let Some(dir) = &task.dir {
+        if !Path::new(dir).is_dir() {
+            source.report_key(index, &["dir"], format!("Directory `{}` not exists", dir));
+        }
+    }
+    for (key, file) in [("stdout", &task.stdout), ("stderr", &task.stderr)] {
+        if let Some(file) = file {
+            if file.is_empty() {
+                continue;
+            }
+            let path = get_with_home_path(file);
+            if path.is_dir() {
+                source.report_key(index, &[key], format!("Log file `{}` is a directory", file));
+            } else if !writable(&path) {
+                source.report_key(
+                    index,
+                    &[key],
+                    format!("Log file `{}` is not writable", file),
+                );
+            }
+        }
+    }
+    if let Some((key, message)) = schedule(task) {
+        let variant = match task.task_type {
+            TaskType::Scheduled(_) => "Scheduled",
+            TaskType::Periodic(_) => "Periodic",
+            TaskType::Once(_) => "Once",
+            _ => "",
+        };
+        source.report_key(index, &["task_type", variant, key], message);
+    }
+    if let Err(e) = Attributes::from_task(task) {
+        source.report_key(index, &[], e);
+    }
+    if let (Some(port), Some(instances)) = (task.port, task.instances) {
+        if port as u32 + instances.saturating_sub(1) > u16::MAX as u32 {
+            source.report_key(
+                index,
+                &["port"],
+                format!(
+                    "Invalid port: {} + {} instances exceeds {}",
+                    port,
+                    instances,
+                    u16::MAX
+                ),
+            );
+        }
+    }
+}
+
+/// Whether command can be executed, commands without `/` are searched in PATH
+fn command_exists(task: &Task) -> bool {
+    let executable = |path: &Path| {
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+    let command = Path::new(&task.command);
+    if task.command.contains('/') {
+        return match &task.dir {
+            Some(dir) if command.is_relative() => executable(&Path::new(dir).join(command)),
+            _ => executable(command),
+        };
+    }
+    let mut paths = Vec::new();
+    if let Some(path) = task.env.get("PATH") {
+        paths.extend(std::env::split_paths(path));
+    }
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    paths.iter().any(|dir| executable(&dir.join(command)))
+}
+
+/// Whether file can be written, or created in its nearest existing directory
+fn writable(file: &Path) -> bool {
+    let mut path = file;
+    while !path.exists() {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => path = parent,
+            _ => {
+                path = Path::new(".");
+                break;
+            }
+        }
+    }
+    if path != file && !path.is_dir() {
+        return false;
+    }
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+/// Number of days in month
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (year, month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(31)
+}
+
+/// Problem of schedule that never runs, and the key it is found at
+fn schedule(task: &Task) -> Option<(&'static str, String)> {
+    match &task.task_type {
+        TaskType::Scheduled(tt) => {
+            for (key, value, min, max) in [
+                ("month", tt.month, 1, 12),
+                ("day", tt.day, 1, 31),
+                ("hour", tt.hour, 0, 23),
+                ("minute", tt.minute, 0, 59),
+                ("second", tt.second, 0, 59),
+            ] {
+                if let Some(value) = value {
+                    if value < min || value > max {
+                        return Some((
+                            key,
+                            format!("Invalid {}: {}, expected {} ~ {}", key, value, min, max),
+                        ));
+                    }
+                }
+            }
+            if let Some(year) = tt.year {
+                if year < 1970 {
+                    return Some(("year", format!("Invalid year: {}", year)));
+                }
+            }
+            if let (Some(month), Some(day)) = (tt.month, tt.day) {
+                // 未指定年份时 2 月按闰年计算
+                let days = days_in_month(tt.year.unwrap_or(2000), month);
+                if day > days {
+                    return Some((
+                        "day",
+                        format!("Invalid day: {}, month {} has {} days", day, month, days),
+                    ));
+                }
+            }
+            if let Some(year) = tt.year {
+                // 最晚的匹配时间已过去时任务不会再运行
+                let month = tt.month.unwrap_or(12);
+                let latest = NaiveDate::from_ymd_opt(
+                    year,
+                    month,
+                    tt.day.unwrap_or(days_in_month(year, month)),
+                )
+                .and_then(|d| {
+                    d.and_hms_opt(
+                        tt.hour.unwrap_or(23),
+                        tt.minute.unwrap_or(59),
+                        tt.second.unwrap_or(59),
+                    )
+                });
+                if let Some(latest) = latest {
+                    if latest < Local::now().naive_local() {
+                        return Some((
+                            "year",
+                            format!("Schedule is in the past, latest run was at {}", latest),
+                        ));
+                    }
+                }
+            }
+            None
+        }
+        TaskType::Periodic(tt) if tt.interval == 0 => Some((
+            "interval",
+            "Invalid interval: 0, expected greater than 0".to_string(),
+        )),
+        TaskType::Once(tt) => {
+            if tt.at.is_some() && tt.after.is_some() {
+                return Some(("after", "at and after cannot be both set".to_string()));
+            }
+            match &tt.at {
+                Some(at) => OnceTask::parse_at(at).err().map(|e| ("at", e)),
+                None => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(format: &str, text: &str) -> Source {
+        Source {
+            file: format!("tasks.{}", format),
+            text: text.to_string(),
+            format: format.to_string(),
+            ranges: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn texts<'a>(text: &'a str, ranges: &[Range<usize>]) -> Vec<&'a str> {
+        ranges.iter().map(|r| text[r.clone()].trim()).collect()
+    }
+
+    #[test]
+    fn test_ini_typed_keys() {
+        // 类型检查的键都是 Task::from_ini 读取的键
+        for key in INI_INT_KEYS
+            .iter()
+            .chain(INI_UINT_KEYS.iter())
+            .chain(INI_BOOL_KEYS.iter())
+        {
+            assert!(Task::INI_KEYS.contains(key), "`{}` is not in INI_KEYS", key);
+        }
+    }
+
+    #[test]
+    fn test_load_ini_profile() {
+        let text =
+            "[web]\nname = web\ncommand = true\ntask_type = async\n\n[profile.prod]\nnice = 1\n";
+        let mut ini = source("ini", text);
+        assert!(load(&mut ini, None).is_empty());
+        let messages: Vec<(usize, &str)> = ini
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![(
+                6,
+                "Profiles are not supported in INI task files, found [profile.prod]"
+            )]
+        );
+
+        let mut ini = source("ini", "[web]\nname = web\n");
+        assert!(load(&mut ini, Some("prod")).is_empty());
+        assert_eq!(ini.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_check_ini() {
+        assert!(check_ini("align", "true").is_ok());
+        assert!(check_ini("sync", "False").is_ok());
+        assert!(check_ini("interval", "-1").is_ok());
+        assert!(check_ini("cpu_affinity", "0 2").is_ok());
+        assert!(check_ini("env", "A=1 \"B=2 3\"").is_ok());
+        assert_eq!(
+            check_ini("aligned", "true").unwrap_err(),
+            "Unknown key `aligned`"
+        );
+        assert_eq!(
+            check_ini("align", "yes").unwrap_err(),
+            "Invalid value `yes` of `align`, expected true or false"
+        );
+        assert!(check_ini("nice", "x").is_err());
+        assert!(check_ini("retries", "-1").is_err());
+        assert!(check_ini("port", "65536").is_err());
+        assert!(check_ini("nice", "4294967301").is_err());
+        assert!(check_ini("oom_score_adj", "-1000").is_ok());
+        assert!(check_ini("ionice_level", "4294967296").is_err());
+        assert!(check_ini("retry_backoff", "x").is_err());
+        assert!(check_ini("task_type", "daily").is_err());
+        assert!(check_ini("concurrency_policy", "skip").is_err());
+        assert!(check_ini("stop_signal", "SIGNOPE").is_err());
+        assert!(check_ini("cpu_affinity", "0,1").is_err());
+        assert_eq!(
+            check_ini("labels", "app").unwrap_err(),
+            "Invalid value `app` of `labels`, expected KEY=VALUE"
+        );
+        assert!(check_ini("watch", "\"src").is_err());
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut task = Task {
+            task_type: TaskType::Scheduled(ScheduledTask {
+                year: None,
+                month: Some(2),
+                day: Some(30),
+                hour: None,
+                minute: None,
+                second: None,
+            }),
+            ..Task::default()
+        };
+        assert_eq!(
+            schedule(&task),
+            Some(("day", "Invalid day: 30, month 2 has 29 days".to_string()))
+        );
+        if let TaskType::Scheduled(tt) = &mut task.task_type {
+            tt.day = Some(29);
+            tt.year = Some(2023);
+        }
+        assert_eq!(schedule(&task).unwrap().0, "day");
+        if let TaskType::Scheduled(tt) = &mut task.task_type {
+            tt.day = Some(1);
+            tt.minute = Some(60);
+        }
+        assert_eq!(schedule(&task).unwrap().0, "minute");
+        if let TaskType::Scheduled(tt) = &mut task.task_type {
+            tt.minute = None;
+        }
+        assert_eq!(schedule(&task).unwrap().0, "year");
+        if let TaskType::Scheduled(tt) = &mut task.task_type {
+            tt.year = None;
+        }
+        assert_eq!(schedule(&task), None);
+
+        task.task_type = TaskType::Periodic(PeriodicTask {
+            started_after: 0,
+            interval: 0,
+            last_run: 0,
+            sync: false,
+            jitter: 0,
+            align: false,
+            next_run: 0,
+            slot: 0,
+        });
+        assert_eq!(schedule(&task).unwrap().0, "interval");
+
+        task.task_type = TaskType::Once(OnceTask {
+            at: Some("2026-11-01 03:00".to_string()),
+            after: Some(10),
+            run_at: 0,
+        });
+        assert_eq!(schedule(&task).unwrap().0, "after");
+        task.task_type = TaskType::Once(OnceTask {
+            at: Some("tomorrow".to_string()),
+            after: None,
+            run_at: 0,
+        });
+        assert_eq!(schedule(&task).unwrap().0, "at");
+    }
+
+    #[test]
+    fn test_toml_ranges() {
+        let text = "[[template]]\nname = \"base\"\n\n[[task]]\nname = \"a\"\n[task.env]\nA = \"1\"\n\n[[task]]\nname = \"b\"\n\n[profile.prod]\nnice = 1\n";
+        assert_eq!(
+            texts(text, &toml_ranges(text)),
+            vec![
+                "[[task]]\nname = \"a\"\n[task.env]\nA = \"1\"",
+                "[[task]]\nname = \"b\""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_ranges() {
+        let text = r#"[{"name": "a", "args": ["}"]}, {"name": "b"}]"#;
+        assert_eq!(
+            texts(text, &json_ranges(text)),
+            vec![r#"{"name": "a", "args": ["}"]}"#, r#"{"name": "b"}"#]
+        );
+        // profile 中的 task 数组不是任务
+        let text = r#"{"profile": {"prod": {"task": [{"name": "p"}]}}, "task": [{"name": "a"}]}"#;
+        assert_eq!(texts(text, &json_ranges(text)), vec![r#"{"name": "a"}"#]);
+        assert!(json_ranges(r#"{"template": []}"#).is_empty());
+    }
+
+    #[test]
+    fn test_yaml_ranges() {
+        let text = "task:\n  - name: a\n    args:\n      - -v\n  - name: b\nprofile:\n  prod:\n    task:\n      - name: p\n---\ntask:\n- name: c\n";
+        assert_eq!(
+            texts(text, &yaml_ranges(text)),
+            vec!["- name: a\n    args:\n      - -v", "- name: b", "- name: c"]
+        );
+    }
+
+    #[test]
+    fn test_position() {
+        let source = source("toml", "[[task]]\nname = \"日志\"\ncommand = 1\n");
+        assert_eq!(source.position(0), (1, 1));
+        assert_eq!(source.position(9), (2, 1));
+        // 列按字符计算
+        let offset = source.text.find("\"\n").unwrap();
+        assert_eq!(source.position(offset), (2, 11));
+        assert_eq!(source.position(usize::MAX), (4, 1));
+    }
+
+    #[test]
+    fn test_locate() {
+        let text = "[[task]]\nname = \"a\"\n\n[[task]]\nname = \"b\"\n[task.task_type.Periodic]\ninterval = 0\n";
+        let mut source = source("toml", text);
+        source.ranges = toml_ranges(text);
+        let offset = source.locate(1, &["task_type", "Periodic", "interval"]);
+        assert_eq!(source.position(offset), (7, 1));
+        // 找不到的键定位到任务本身
+        let offset = source.locate(0, &["command"]);
+        assert_eq!(source.position(offset), (1, 1));
+    }
+}
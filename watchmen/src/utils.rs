@@ -1,6 +1,6 @@
 use std::{error::Error, fs, path::Path};
 
-use crate::common::handle::Response;
+use crate::common::handle::{Data, Response};
 use colored::Colorize;
 use regex::Regex;
 
@@ -25,6 +25,11 @@ pub fn recursive_search_files(folder_path: &str, regex: &Regex, matched_files: &
 }
 
 pub async fn print_result(res: Vec<Response>) {
+    // 选中多个任务时逐个输出结果
+    let res = res.into_iter().flat_map(|r| match r.data {
+        Some(Data::Results(results)) => results,
+        data => vec![Response { data, ..r }],
+    });
     for r in res {
         let result = format!("{}\t{}\t{}", r.code, r.msg, r.data.unwrap_or_default());
         match r.code {
@@ -12,14 +12,20 @@ pub async fn handle_exec(request: Request) -> Result<Response, Box<dyn Error>> {
         Command::Run(task) => global::run(task).await,
         Command::Add(task) => global::add(task).await,
         Command::Reload(task) => global::reload(task).await,
-        Command::Start(tf) => global::start(tf).await,
-        Command::Restart(tf) => global::restart(tf).await,
-        Command::Stop(tf) => global::stop(tf, true).await,
-        Command::Remove(tf) => global::remove(tf, true).await,
-        Command::Write(tf, data) => global::write(tf, data).await,
-        Command::Pause(tf) => global::pause(tf).await,
-        Command::Resume(tf) => global::resume(tf).await,
+        Command::Start(tf) => global::select(tf, |tf| global::each(tf, global::start)).await,
+        Command::Restart(tf) => global::select(tf, |tf| global::each(tf, global::restart)).await,
+        Command::Stop(tf) => {
+            global::select(tf, |tf| global::each(tf, |tf| global::stop(tf, true))).await
+        }
+        Command::Remove(tf) => global::select(tf, |tf| global::remove(tf, true)).await,
+        Command::Write(tf, data) => global::select(tf, |tf| global::write(tf, data.clone())).await,
+        Command::Pause(tf) => global::select(tf, |tf| global::each(tf, global::pause)).await,
+        Command::Resume(tf) => global::select(tf, |tf| global::each(tf, global::resume)).await,
         Command::List(condition) => global::list(condition).await,
+        Command::Scale(tf, count) => global::scale(tf, count).await,
+        Command::History(tf, page, size) => global::history(tf, page, size).await,
+        Command::Apply(tasks, prune, dry_run) => global::apply(tasks, prune, dry_run).await,
+        Command::Export(condition) => global::export(condition).await,
     };
     match r {
         Ok(res) => {
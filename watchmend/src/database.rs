@@ -0,0 +1,502 @@
+#[cfg(feature = "sqlite")]
+mod backend {
+    use std::{
+        error::Error,
+        path::Path,
+        process::ExitStatus,
+        sync::{
+            mpsc::{self, Sender},
+            Mutex,
+        },
+    };
+
+    use lazy_static::lazy_static;
+    use rusqlite::{params, Connection, OptionalExtension, Row};
+    use tracing::error;
+
+    use super::file_size;
+    use crate::common::task::{Run, Task, Trigger};
+
+    /// Current version of database schema, stored in `PRAGMA user_version`
+    ///
+    /// - `1`: tasks and runs tables
+    /// - `2`: timed_out column of runs
+    const VERSION: i64 = 2;
+
+    /// Write of run history, executed on writer thread
+    type Job = Box<dyn FnOnce(&Connection) + Send>;
+
+    lazy_static! {
+        static ref DATABASE: Mutex<Option<Connection>> = Mutex::new(None);
+        // 运行记录由单独的线程按顺序写入，不阻塞异步任务
+        static ref WRITER: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+    }
+
+    /// Execute job on writer thread, do nothing if database is not opened
+    fn write(job: Job) {
+        if let Some(writer) = WRITER.lock().unwrap().as_ref() {
+            let _ = writer.send(job);
+        }
+    }
+
+    fn millis() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Failed to get timestamp")
+            .as_millis() as u64
+    }
+
+    /// Open database and create tables, database is used for tasks and run history after opened
+    ///
+    /// # params
+    ///
+    /// - `path`: database file path
+    pub fn open(path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        migrate(&conn)?;
+        *DATABASE.lock().unwrap() = Some(conn);
+        let (tx, rx) = mpsc::channel::<Job>();
+        std::thread::spawn(move || {
+            for job in rx {
+                if let Some(conn) = DATABASE.lock().unwrap().as_ref() {
+                    job(conn);
+                }
+            }
+        });
+        *WRITER.lock().unwrap() = Some(tx);
+        Ok(())
+    }
+
+    /// Create tables or upgrade them to current version of schema
+    ///
+    /// # params
+    ///
+    /// - `conn`: database connection
+    fn migrate(conn: &Connection) -> Result<(), Box<dyn Error>> {
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > VERSION {
+            return Err(format!(
+                "Database version {} is newer than supported version {}",
+                version, VERSION
+            )
+            .into());
+        }
+        if version < 1 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS tasks (
+                    id INTEGER PRIMARY KEY,
+                    data TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS runs (
+                    run_id TEXT PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    task_name TEXT NOT NULL,
+                    trigger TEXT NOT NULL,
+                    reason TEXT,
+                    started_at INTEGER NOT NULL,
+                    ended_at INTEGER,
+                    code INTEGER,
+                    signal INTEGER,
+                    stdout_start INTEGER,
+                    stdout_end INTEGER,
+                    stderr_start INTEGER,
+                    stderr_end INTEGER
+                );
+                CREATE INDEX IF NOT EXISTS runs_task ON runs (task_id, started_at);
+                CREATE INDEX IF NOT EXISTS runs_name ON runs (task_name, started_at);",
+            )?;
+        }
+        if version < 2 {
+            conn.execute_batch(
+                "ALTER TABLE runs ADD COLUMN timed_out INTEGER NOT NULL DEFAULT 0;",
+            )?;
+        }
+        conn.pragma_update(None, "user_version", VERSION)?;
+        Ok(())
+    }
+
+    /// Whether database is opened
+    pub fn enabled() -> bool {
+        DATABASE.lock().unwrap().is_some()
+    }
+
+    /// Replace all saved tasks in one transaction
+    pub fn save_tasks(tasks: Vec<Task>) -> Result<(), Box<dyn Error>> {
+        let mut database = DATABASE.lock().unwrap();
+        let conn = match database.as_mut() {
+            Some(conn) => conn,
+            None => return Err("Database is not opened".into()),
+        };
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM tasks", [])?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO tasks (id, data) VALUES (?1, ?2)")?;
+            for task in tasks {
+                stmt.execute(params![task.id, serde_json::to_string(&task)?])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Load saved tasks
+    pub fn load_tasks() -> Result<Vec<Task>, Box<dyn Error>> {
+        let database = DATABASE.lock().unwrap();
+        let conn = match database.as_ref() {
+            Some(conn) => conn,
+            None => return Err("Database is not opened".into()),
+        };
+        let mut stmt = conn.prepare("SELECT data FROM tasks ORDER BY id")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut tasks = Vec::new();
+        for row in rows {
+            tasks.push(serde_json::from_str(&row?)?);
+        }
+        Ok(tasks)
+    }
+
+    /// Record start of run, do nothing if database is not opened
+    ///
+    /// # params
+    ///
+    /// - `task`: task with run id of the run
+    /// - `trigger`: what started the run
+    /// - `reason`: why the run was started
+    /// - `offsets`: size of stdout and stderr file before the process started
+    pub fn started(
+        task: &Task,
+        trigger: Trigger,
+        reason: Option<String>,
+        offsets: (Option<u64>, Option<u64>),
+    ) {
+        let Some(run_id) = task.run_id.clone() else {
+            return;
+        };
+        let (id, name, started_at) = (task.id, task.name.clone(), millis());
+        write(Box::new(move |conn| {
+            let res = conn.execute(
+                "INSERT OR REPLACE INTO runs
+                    (run_id, task_id, task_name, trigger, reason, started_at, stdout_start, stderr_start)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    run_id,
+                    id,
+                    name,
+                    trigger.as_str(),
+                    reason,
+                    started_at,
+                    offsets.0,
+                    offsets.1,
+                ],
+            );
+            if let Err(e) = res {
+                error!("Run [{}] record failed: {}", run_id, e);
+            }
+        }));
+    }
+
+    /// Record end of run, do nothing if database is not opened
+    ///
+    /// # params
+    ///
+    /// - `run_id`: run id
+    /// - `status`: exit status of process, none if process is not a child of daemon
+    /// - `stdout`: stdout file of task
+    /// - `stderr`: stderr file of task
+    pub fn finished(
+        run_id: Option<&str>,
+        status: Option<&ExitStatus>,
+        stdout: Option<&str>,
+        stderr: Option<&str>,
+    ) {
+        use std::os::unix::process::ExitStatusExt;
+
+        let Some(run_id) = run_id.map(|id| id.to_string()) else {
+            return;
+        };
+        // 结束时间和日志大小在调用时获取
+        let (ended_at, code, signal) = (
+            millis(),
+            status.and_then(|s| s.code()),
+            status.and_then(|s| s.signal()),
+        );
+        let (stdout_end, stderr_end) = (file_size(stdout), file_size(stderr));
+        write(Box::new(move |conn| {
+            let res = conn.execute(
+                "UPDATE runs SET ended_at = ?2, code = ?3, signal = ?4, stdout_end = ?5, stderr_end = ?6
+                 WHERE run_id = ?1",
+                params![run_id, ended_at, code, signal, stdout_end, stderr_end],
+            );
+            if let Err(e) = res {
+                error!("Run [{}] record failed: {}", run_id, e);
+            }
+        }));
+    }
+
+    /// Mark run as stopped by timeout, do nothing if database is not opened
+    ///
+    /// # params
+    ///
+    /// - `run_id`: run id
+    pub fn timed_out(run_id: Option<&str>) {
+        let Some(run_id) = run_id.map(|id| id.to_string()) else {
+            return;
+        };
+        write(Box::new(move |conn| {
+            let res = conn.execute(
+                "UPDATE runs SET timed_out = 1 WHERE run_id = ?1",
+                params![run_id],
+            );
+            if let Err(e) = res {
+                error!("Run [{}] record failed: {}", run_id, e);
+            }
+        }));
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Run> {
+        let trigger: String = row.get(3)?;
+        Ok(Run {
+            run_id: row.get(0)?,
+            task_id: row.get(1)?,
+            task_name: row.get(2)?,
+            trigger: Trigger::parse(&trigger).unwrap_or(Trigger::Manual),
+            reason: row.get(4)?,
+            started_at: row.get(5)?,
+            ended_at: row.get(6)?,
+            code: row.get(7)?,
+            signal: row.get(8)?,
+            stdout_start: row.get(9)?,
+            stdout_end: row.get(10)?,
+            stderr_start: row.get(11)?,
+            stderr_end: row.get(12)?,
+            timed_out: row.get(13)?,
+        })
+    }
+
+    /// Runs of task with total number of runs, newest first. Ids of removed tasks are reused,
+    /// so runs of an existing task are matched by both id and name
+    ///
+    /// # params
+    ///
+    /// - `id`: task id, 0 to match by name only
+    /// - `name`: task name, none to match by id only
+    /// - `page`: page number, starts from 1
+    /// - `size`: number of runs per page
+    pub fn runs(
+        id: i64,
+        name: Option<&str>,
+        page: u64,
+        size: u64,
+    ) -> Result<(u64, Vec<Run>), Box<dyn Error>> {
+        let database = DATABASE.lock().unwrap();
+        match database.as_ref() {
+            Some(conn) => query_runs(conn, id, name, page, size),
+            None => Err("Run history requires sqlite backend".into()),
+        }
+    }
+
+    fn query_runs(
+        conn: &Connection,
+        id: i64,
+        name: Option<&str>,
+        page: u64,
+        size: u64,
+    ) -> Result<(u64, Vec<Run>), Box<dyn Error>> {
+        let total: u64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM runs
+                 WHERE (?1 = 0 OR task_id = ?1) AND (?2 IS NULL OR task_name = ?2)",
+                params![id, name],
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or(0);
+        let mut stmt = conn.prepare(
+            "SELECT run_id, task_id, task_name, trigger, reason, started_at, ended_at, code,
+                    signal, stdout_start, stdout_end, stderr_start, stderr_end, timed_out
+             FROM runs WHERE (?1 = 0 OR task_id = ?1) AND (?2 IS NULL OR task_name = ?2)
+             ORDER BY started_at DESC LIMIT ?3 OFFSET ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![id, name, size, page.saturating_sub(1) * size],
+            from_row,
+        )?;
+        let mut runs = Vec::new();
+        for row in rows {
+            runs.push(row?);
+        }
+        Ok((total, runs))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn insert(conn: &Connection, run_id: &str, id: i64, name: &str, started_at: u64) {
+            conn.execute(
+                "INSERT INTO runs (run_id, task_id, task_name, trigger, started_at)
+                 VALUES (?1, ?2, ?3, 'manual', ?4)",
+                params![run_id, id, name, started_at],
+            )
+            .unwrap();
+        }
+
+        fn run_ids(runs: &[Run]) -> Vec<&str> {
+            runs.iter().map(|r| r.run_id.as_str()).collect()
+        }
+
+        #[test]
+        fn test_migrate() {
+            // 版本 1 的表没有 timed_out 列
+            let conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(
+                "CREATE TABLE tasks (id INTEGER PRIMARY KEY, data TEXT NOT NULL);
+                CREATE TABLE runs (
+                    run_id TEXT PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    task_name TEXT NOT NULL,
+                    trigger TEXT NOT NULL,
+                    reason TEXT,
+                    started_at INTEGER NOT NULL,
+                    ended_at INTEGER,
+                    code INTEGER,
+                    signal INTEGER,
+                    stdout_start INTEGER,
+                    stdout_end INTEGER,
+                    stderr_start INTEGER,
+                    stderr_end INTEGER
+                );
+                PRAGMA user_version = 1;",
+            )
+            .unwrap();
+            insert(&conn, "a", 1, "web", 100);
+            migrate(&conn).unwrap();
+            let version: i64 = conn
+                .pragma_query_value(None, "user_version", |row| row.get(0))
+                .unwrap();
+            assert_eq!(version, VERSION);
+            let (total, runs) = query_runs(&conn, 1, Some("web"), 1, 10).unwrap();
+            assert_eq!(total, 1);
+            assert!(!runs[0].timed_out);
+
+            // 已是当前版本时不再修改表
+            migrate(&conn).unwrap();
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            insert(&conn, "b", 1, "web", 100);
+            assert_eq!(query_runs(&conn, 1, None, 1, 10).unwrap().0, 1);
+
+            conn.pragma_update(None, "user_version", VERSION + 1)
+                .unwrap();
+            assert!(migrate(&conn).is_err());
+        }
+
+        #[test]
+        fn test_runs() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            for n in 1..=5 {
+                insert(&conn, &format!("web-{}", n), 1, "web", n * 100);
+            }
+            // 删除的任务的 id 被其他任务复用
+            insert(&conn, "old-1", 2, "old", 150);
+            insert(&conn, "api-1", 2, "api", 250);
+
+            let (total, runs) = query_runs(&conn, 1, Some("web"), 1, 2).unwrap();
+            assert_eq!(total, 5);
+            assert_eq!(run_ids(&runs), vec!["web-5", "web-4"]);
+            let (total, runs) = query_runs(&conn, 1, Some("web"), 3, 2).unwrap();
+            assert_eq!(total, 5);
+            assert_eq!(run_ids(&runs), vec!["web-1"]);
+            assert!(query_runs(&conn, 1, Some("web"), 4, 2)
+                .unwrap()
+                .1
+                .is_empty());
+
+            // 现有任务不包含复用同一 id 的已删除任务的运行
+            let (total, runs) = query_runs(&conn, 2, Some("api"), 1, 10).unwrap();
+            assert_eq!((total, run_ids(&runs)), (1, vec!["api-1"]));
+            let (total, runs) = query_runs(&conn, 0, Some("old"), 1, 10).unwrap();
+            assert_eq!((total, run_ids(&runs)), (1, vec!["old-1"]));
+            assert_eq!(query_runs(&conn, 2, None, 1, 10).unwrap().0, 2);
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+mod backend {
+    use std::{error::Error, path::Path, process::ExitStatus};
+
+    use crate::common::task::{Run, Task, Trigger};
+
+    pub fn open(_path: &Path) -> Result<(), Box<dyn Error>> {
+        Err("Sqlite backend is not enabled, build watchmend with feature `sqlite`".into())
+    }
+
+    pub fn enabled() -> bool {
+        false
+    }
+
+    pub fn save_tasks(_tasks: Vec<Task>) -> Result<(), Box<dyn Error>> {
+        Err("Database is not opened".into())
+    }
+
+    pub fn load_tasks() -> Result<Vec<Task>, Box<dyn Error>> {
+        Err("Database is not opened".into())
+    }
+
+    pub fn started(
+        _task: &Task,
+        _trigger: Trigger,
+        _reason: Option<String>,
+        _offsets: (Option<u64>, Option<u64>),
+    ) {
+    }
+
+    pub fn finished(
+        _run_id: Option<&str>,
+        _status: Option<&ExitStatus>,
+        _stdout: Option<&str>,
+        _stderr: Option<&str>,
+    ) {
+    }
+
+    pub fn timed_out(_run_id: Option<&str>) {}
+
+    pub fn runs(
+        _id: i64,
+        _name: Option<&str>,
+        _page: u64,
+        _size: u64,
+    ) -> Result<(u64, Vec<Run>), Box<dyn Error>> {
+        Err("Run history requires sqlite backend".into())
+    }
+}
+
+pub use backend::*;
+
+use crate::common::task::Task;
+
+/// Size of log file, 0 if file not exists yet, none if file is not configured
+fn file_size(path: Option<&str>) -> Option<u64> {
+    match path {
+        Some(path) if !path.is_empty() => {
+            Some(std::fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+        }
+        _ => None,
+    }
+}
+
+/// Size of stdout and stderr file of task, used as log offsets of a run
+pub fn log_offsets(task: &Task) -> (Option<u64>, Option<u64>) {
+    (
+        file_size(task.stdout.as_deref()),
+        file_size(task.stderr.as_deref()),
+    )
+}
@@ -1,8 +1,8 @@
-use crate::common::config::Config;
+use crate::common::config::{get_with_home_path, Config};
 use tokio::sync::mpsc;
 use tracing::info;
 
-use crate::global;
+use crate::{database, global, include};
 
 #[cfg(feature = "sock")]
 pub mod sock;
@@ -14,9 +14,40 @@ pub mod socket;
 pub mod http;
 
 pub async fn start(config: Config, load: bool) {
-    if load {
+    if config.watchmen.backend.as_deref() == Some("sqlite") {
+        let path = config
+            .watchmen
+            .database
+            .clone()
+            .unwrap_or("$HOME/.watchmen/watchmen.db".to_string());
+        match database::open(&get_with_home_path(&path)) {
+            Ok(_) => {
+                info!("Database opened: {}", path);
+                println!("Database opened: {}", path);
+                if load {
+                    match global::load_database().await {
+                        Ok(_) => {
+                            info!("Database tasks loaded.");
+                            println!("Database tasks loaded.");
+                        }
+                        Err(e) => {
+                            info!("Database tasks load failed: {}", e);
+                            println!("Database tasks load failed: {}", e);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                info!("Database open failed: {}", e);
+                println!("Database open failed: {}", e);
+            }
+        }
+    } else if load {
         if let Some(path) = config.watchmen.cache.clone() {
             global::set_cache(path.clone()).await;
+            if let Some(snapshots) = config.watchmen.cache_snapshots {
+                global::set_cache_snapshots(snapshots).await;
+            }
             match global::load(&path).await {
                 Ok(_) => {
                     info!("Cache tasks loaded.");
@@ -33,6 +64,20 @@ pub async fn start(config: Config, load: bool) {
         }
     }
 
+    if let Some(dirs) = config.watchmen.include_dirs.clone() {
+        let mat = config
+            .watchmen
+            .mat
+            .clone()
+            .unwrap_or(r"^.*\.(toml|ini|json|yaml|yml)$".to_string());
+        tokio::spawn(async move {
+            if let Err(e) = include::run(dirs, mat).await {
+                info!("Include directories watch failed: {}", e);
+                println!("Include directories watch failed: {}", e);
+            }
+        });
+    }
+
     let (tx, mut rx) = mpsc::channel::<i32>(12);
 
     let tx_ctrl_c = tx.clone(); // 监听到 ctrl c 通信管道
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use inotify::{EventMask, Inotify, WatchDescriptor};
+use regex::Regex;
+use tokio::time;
+use tracing::{error, info};
+
+use crate::common::{
+    handle::{Data, Response},
+    task::{Task, TaskFlag},
+};
+use crate::global;
+use crate::watch::add_watches;
+
+/// Time to wait for following changes before task files are reconciled
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Load task files of include directories, then reconcile tasks when files are created,
+/// changed or deleted. Profile of task files is selected by `WATCHMEN_PROFILE`
+///
+/// # params
+///
+/// - `dirs`: include directories
+/// - `mat`: task config filename regex pattern
+pub async fn run(dirs: Vec<String>, mat: String) -> Result<(), Box<dyn Error>> {
+    let regex = Regex::new(&mat)?;
+    let inotify = Inotify::init()?;
+    let mut watches = inotify.watches();
+    let mut wds: HashMap<WatchDescriptor, PathBuf> = HashMap::new();
+    let mut roots = Vec::new();
+    for dir in dirs {
+        let dir = PathBuf::from(dir);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            error!("Include directory {} create failed: {}", dir.display(), e);
+            continue;
+        }
+        // 先监听再加载，加载期间的变更不会丢失
+        if let Err(e) = add_watches(&mut watches, &dir, &mut wds) {
+            error!("Include directory {} watch failed: {}", dir.display(), e);
+            continue;
+        }
+        info!("Include directory {} watching", dir.display());
+        roots.push(dir);
+    }
+    // 守护进程停止期间删除的文件，其任务也被移除
+    for root in &roots {
+        sync(root, &regex).await;
+    }
+
+    let mut stream = inotify.into_event_stream([0; 4096])?;
+    loop {
+        // 等待第一个变更，之后在防抖时间内合并后续变更
+        let mut changed: Vec<PathBuf> = Vec::new();
+        loop {
+            let event = if changed.is_empty() {
+                match stream.next().await {
+                    Some(event) => event?,
+                    None => return Ok(()),
+                }
+            } else {
+                match time::timeout(DEBOUNCE, stream.next()).await {
+                    Ok(Some(event)) => event?,
+                    Ok(None) => return Ok(()),
+                    Err(_) => break,
+                }
+            };
+            let path = match (wds.get(&event.wd), &event.name) {
+                (Some(base), Some(name)) => base.join(name),
+                _ => continue,
+            };
+            // 新建的目录也需要监听
+            if event.mask.contains(EventMask::CREATE | EventMask::ISDIR)
+                || event.mask.contains(EventMask::MOVED_TO | EventMask::ISDIR)
+            {
+                let _ = add_watches(&mut watches, &path, &mut wds);
+            } else if !event.mask.contains(EventMask::ISDIR) {
+                let matched = path
+                    .file_name()
+                    .map(|name| regex.is_match(&name.to_string_lossy()))
+                    .unwrap_or(false);
+                if !matched {
+                    continue;
+                }
+            }
+            if !changed.contains(&path) {
+                changed.push(path);
+            }
+        }
+        for path in changed {
+            sync(&path, &regex).await;
+        }
+    }
+}
+
+/// Reconcile tasks with task files of path, tasks of deleted files are removed
+///
+/// # params
+///
+/// - `path`: task file or directory, deleted or not
+/// - `regex`: task config filename regex pattern
+async fn sync(path: &Path, regex: &Regex) {
+    let mut files = Vec::new();
+    search(path, regex, &mut files);
+    files.sort();
+    for file in &files {
+        load(file).await;
+    }
+    let files: Vec<String> = files
+        .iter()
+        .map(|f| f.to_string_lossy().to_string())
+        .collect();
+    let prune = |t: &Task| match &t.source {
+        Some(source) => Path::new(source).starts_with(path) && !files.contains(source),
+        None => false,
+    };
+    let res = global::apply_with(Vec::new(), prune, false).await;
+    report(path, res.map_err(|e| e.to_string())).await;
+}
+
+/// Task files of path, directories are searched recursively
+fn search(path: &Path, regex: &Regex, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        if let Ok(entries) = std::fs::read_dir(path) {
+            for entry in entries.flatten() {
+                search(&entry.path(), regex, files);
+            }
+        }
+    } else if path.is_file() {
+        let matched = path
+            .file_name()
+            .map(|name| regex.is_match(&name.to_string_lossy()))
+            .unwrap_or(false);
+        if matched {
+            files.push(path.to_path_buf());
+        }
+    }
+}
+
+/// Reconcile tasks of task file, tasks are kept if the file is invalid
+async fn load(file: &Path) {
+    let profile = std::env::var("WATCHMEN_PROFILE").ok();
+    let mut desired = match Task::from_file(file, profile.as_deref()) {
+        Ok(tasks) => tasks.task,
+        Err(e) => {
+            error!("Include file {} load failed: {}", file.display(), e);
+            return;
+        }
+    };
+    let source = file.to_string_lossy().to_string();
+    // 其他文件的同名任务不被替换
+    let tasks = global::get_all().await.unwrap_or_default();
+    desired.retain(|task| {
+        let other = tasks.values().find(|t| {
+            t.parent.is_none()
+                && t.name == task.name
+                && t.source.is_some()
+                && t.source.as_deref() != Some(source.as_str())
+        });
+        match other {
+            Some(t) => {
+                error!(
+                    "Include file {} task [{}] skipped, already defined in {}",
+                    file.display(),
+                    task.name,
+                    t.source.clone().unwrap_or_default()
+                );
+                false
+            }
+            None => true,
+        }
+    });
+    for task in desired.iter_mut() {
+        task.source = Some(source.clone());
+    }
+    let prune = |t: &Task| t.source.as_deref() == Some(source.as_str());
+    let res = global::apply_with(desired, prune, false).await;
+    report(file, res.map_err(|e| e.to_string())).await;
+}
+
+/// Log changes of reconcile, added tasks are started
+async fn report(path: &Path, res: Result<Response, String>) {
+    let changes = match res {
+        Ok(res) => match res.data {
+            Some(Data::Plan(changes)) => changes,
+            _ => return,
+        },
+        Err(e) => {
+            error!("Include file {} apply failed: {}", path.display(), e);
+            return;
+        }
+    };
+    for change in changes {
+        let Some(res) = change.result else {
+            continue;
+        };
+        if res.code != 10000 {
+            error!(
+                "Include file {} {} task [{}] failed: {}",
+                path.display(),
+                change.action,
+                change.name,
+                res.msg
+            );
+            continue;
+        }
+        info!(
+            "Include file {} {} task [{}:{}]",
+            path.display(),
+            change.action,
+            change.id,
+            change.name
+        );
+        if change.action == "add" {
+            match global::each(TaskFlag::new(change.id), global::start).await {
+                Ok(res) if res.code == 10000 => {}
+                Ok(res) => error!("Task [{}] start failed: {}", change.name, res.msg),
+                Err(e) => error!("Task [{}] start failed: {}", change.name, e),
+            }
+        }
+    }
+}
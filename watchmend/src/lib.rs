@@ -2,32 +2,46 @@ pub mod command;
 pub mod common {
     include!("../../common.rs");
 }
+pub mod database;
 pub mod engine;
+pub mod include;
 pub mod monitor;
+pub mod selector;
+pub mod state;
 pub mod utils;
+pub mod watch;
 
 pub mod global {
 
     use std::{
         collections::HashMap,
         error::Error,
-        path::Path,
-        process::Stdio,
-        time::{SystemTime, UNIX_EPOCH},
+        future::Future,
+        os::unix::process::ExitStatusExt,
+        path::{Path, PathBuf},
+        process::{ExitStatus, Stdio},
+        time::{Duration, SystemTime, UNIX_EPOCH},
     };
 
     use crate::common::{
         config::{get_with_home, get_with_home_path},
-        handle::{Data, Response, Status},
-        task::{AsyncTask, Task, TaskFlag, TaskType},
+        handle::{Change, Data, Response, Status},
+        process::{self, Attributes, Credentials},
+        task::{
+            AsyncTask, ConcurrencyPolicy, LiveRun, OnceTask, PeriodicTask, Task, TaskFlag,
+            TaskType, Trigger,
+        },
     };
+    use crate::selector::Selector;
+    use crate::utils::{get_id, wait_exit, wait_timeout};
+    use crate::{database, monitor, state, watch};
     use lazy_static::lazy_static;
-    use log::info;
+    use log::{error, info};
     use regex::Regex;
     use tokio::{
         io::AsyncWriteExt,
         process::{Child, Command},
-        sync::{mpsc, RwLock},
+        sync::{mpsc, Notify, RwLock},
         task::JoinHandle,
     };
 
@@ -36,11 +50,16 @@ pub mod global {
     struct TaskProcess {
         task: Task,
         joinhandle: Option<JoinHandle<Option<i32>>>,
+        /// Handles of runs in flight of periodic and scheduled task, keyed by run id
+        runs: HashMap<String, JoinHandle<Option<i32>>>,
         tx: Option<mpsc::Sender<Vec<u8>>>,
     }
 
     lazy_static! {
         static ref CACHE: RwLock<Option<String>> = RwLock::new(None);
+        static ref CACHE_SNAPSHOTS: RwLock<usize> = RwLock::new(state::SNAPSHOTS);
+        static ref CACHE_NOTIFY: Notify = Notify::new();
+        static ref CACHE_WRITER: std::sync::Once = std::sync::Once::new();
         static ref TASKS: RwLock<HashMap<i64, TaskProcess>> = RwLock::new(HashMap::new());
     }
 
@@ -66,6 +85,16 @@ pub mod global {
         *cache = Some(path);
     }
 
+    /// Set number of snapshots kept beside cache file
+    ///
+    /// # params
+    ///
+    /// - `snapshots`: number of snapshots, 0 disables snapshots
+    pub async fn set_cache_snapshots(snapshots: usize) {
+        let mut cache_snapshots = CACHE_SNAPSHOTS.write().await;
+        *cache_snapshots = snapshots;
+    }
+
     pub async fn get_all() -> Result<HashMap<i64, Task>, Box<dyn Error>> {
         let tasks = TASKS.read().await;
         let mut tasks_map: HashMap<i64, Task> = HashMap::new();
@@ -76,52 +105,175 @@ pub mod global {
     }
 
     pub async fn cache() -> Result<(), Box<dyn Error>> {
-        // 启动协程写入缓存文件，避免阻塞对其他任务的操作
-        tokio::spawn(async move {
-            let path_mutex = CACHE.read().await;
-            let path = path_mutex.clone();
-            drop(path_mutex); // 释放锁，避免阻塞对其他任务的操作
-            if let Some(path) = path {
-                let path = get_with_home(path.as_str());
-                let path = Path::new(path.as_str());
-                let parent = path.parent().unwrap();
-                if !parent.exists() {
-                    std::fs::create_dir_all(parent).unwrap();
-                }
-                let tasks = TASKS.read().await;
-                let tasks_cache: Vec<Task> = tasks.values().map(|tp| tp.task.clone()).collect();
-                drop(tasks); // 释放锁，避免阻塞对其他任务的操作
-                let tasks_cache_str = serde_json::to_string(&tasks_cache).unwrap();
-                tokio::fs::write(path, tasks_cache_str).await.unwrap();
-            }
+        // 由唯一的写入协程保存缓存文件，多次通知合并为一次写入，避免阻塞对其他任务的操作
+        CACHE_WRITER.call_once(|| {
+            tokio::spawn(cache_writer());
         });
+        CACHE_NOTIFY.notify_one();
         Ok(())
     }
 
+    /// Write cache file or database whenever notified, always saves the latest tasks
+    async fn cache_writer() {
+        loop {
+            CACHE_NOTIFY.notified().await;
+            let path = CACHE.read().await.clone();
+            let path = path.map(|path| PathBuf::from(get_with_home(path.as_str())));
+            if path.is_none() && !database::enabled() {
+                continue;
+            }
+            let snapshots = *CACHE_SNAPSHOTS.read().await;
+            let tasks = TASKS.read().await;
+            let tasks_cache: Vec<Task> = tasks.values().map(|tp| tp.task.clone()).collect();
+            drop(tasks); // 释放锁，避免阻塞对其他任务的操作
+            let res = tokio::task::spawn_blocking(move || -> Result<(), String> {
+                if database::enabled() {
+                    database::save_tasks(tasks_cache).map_err(|e| e.to_string())
+                } else if let Some(path) = path {
+                    state::save(&path, tasks_cache, snapshots).map_err(|e| e.to_string())
+                } else {
+                    Ok(())
+                }
+            })
+            .await;
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("Cache tasks save failed: {}", e),
+                Err(e) => error!("Cache tasks save failed: {}", e),
+            }
+        }
+    }
+
     pub async fn load(path: &str) -> Result<(), Box<dyn Error>> {
         let path = get_with_home(path);
         let path = Path::new(path.as_str());
-        if !path.exists() || !path.is_file() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Cache file [{}] is not valid", path.to_str().unwrap()),
-            )));
-        }
 
-        // 读取缓存文件序列化成任务列表
-        let tasks_cache: Vec<Task> = serde_json::from_str(&std::fs::read_to_string(path).unwrap())?;
+        // 读取缓存文件序列化成任务列表，缓存文件损坏时使用快照
+        let snapshots = *CACHE_SNAPSHOTS.read().await;
+        let tasks_cache: Vec<Task> = state::load(path, snapshots)?;
+        restore(tasks_cache).await
+    }
+
+    /// Load tasks from database, database must be opened
+    pub async fn load_database() -> Result<(), Box<dyn Error>> {
+        let tasks_cache: Vec<Task> = database::load_tasks()?;
+        restore(tasks_cache).await
+    }
+
+    /// Add loaded tasks, tasks running before are started again
+    async fn restore(tasks_cache: Vec<Task>) -> Result<(), Box<dyn Error>> {
         let mut tasks = TASKS.write().await;
         for task in tasks_cache {
             let mut tp = TaskProcess {
                 task: task.clone(),
                 joinhandle: None,
+                runs: HashMap::new(),
                 tx: None,
             };
+
+            // 旧版本缓存没有下次运行时间
+            if let TaskType::Periodic(tt) = &mut tp.task.task_type {
+                if tt.next_run == 0 && tt.last_run > 0 {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("Failed to get timestamp")
+                        .as_secs();
+                    monitor::schedule(tt, now);
+                }
+            }
+
+            // 周期任务和定时任务逐个接管仍在运行的实例
+            if !tp.task.runs.is_empty() {
+                for run in std::mem::take(&mut tp.task.runs) {
+                    match (run.pid, run.pid_start_time) {
+                        (Some(pid), Some(start_time))
+                            if process::start_time(pid) == Some(start_time) =>
+                        {
+                            info!(
+                                "Task [{}:{}] adopted running process {}",
+                                task.id, task.name, pid
+                            );
+                            let jh = adopt_run(&tp.task, &run.run_id, pid, start_time);
+                            tp.runs.insert(run.run_id.clone(), jh);
+                            tp.task.runs.push(run);
+                        }
+                        _ => database::finished(
+                            Some(&run.run_id),
+                            None,
+                            tp.task.stdout.as_deref(),
+                            tp.task.stderr.as_deref(),
+                        ),
+                    }
+                }
+                if !tp.task.runs.is_empty() {
+                    tasks.insert(task.id, tp);
+                    continue;
+                }
+                tp.task.pid = None;
+            }
+
+            let active = matches!(
+                tp.task.status.as_deref(),
+                Some("running" | "executing" | "processing")
+            );
+            if let (true, Some(pid), Some(start_time)) =
+                (active, tp.task.pid, tp.task.pid_start_time)
+            {
+                // 上次启动的进程仍在运行时直接接管，避免重复启动
+                if process::start_time(pid) == Some(start_time) {
+                    info!(
+                        "Task [{}:{}] adopted running process {}",
+                        task.id, task.name, pid
+                    );
+                    tp.joinhandle = Some(adopt(&tp.task, pid, start_time));
+                    if let (TaskType::Async(_), Some(watch)) =
+                        (&tp.task.task_type, tp.task.watch.clone())
+                    {
+                        watch::register(task.id, watch, tp.task.dir.clone());
+                    }
+                    tasks.insert(task.id, tp);
+                    continue;
+                }
+            }
+            if active && tp.task.pid.is_some() {
+                // 上次启动的进程已退出，退出状态未知
+                database::finished(
+                    tp.task.run_id.as_deref(),
+                    None,
+                    tp.task.stdout.as_deref(),
+                    tp.task.stderr.as_deref(),
+                );
+            }
+            tp.task.pid = None;
+            tp.task.pid_start_time = None;
+            match tp.task.status.as_deref() {
+                Some("executing") => tp.task.status = Some("interval".to_string()),
+                // 一次性任务被中断时不再重复执行
+                Some("processing") if matches!(tp.task.task_type, TaskType::Once(_)) => {
+                    tp.task.status = Some("completed".to_string())
+                }
+                Some("processing") => tp.task.status = Some("waiting".to_string()),
+                _ => {}
+            }
+
             match &tp.task.task_type {
                 TaskType::Async(tt) => {
                     // 上次运行状态为 running 的染污加载后直接启动
                     if tp.task.status == Some("running".to_string()) {
+                        tp.task.run_id = Some(get_id().await);
+                        let offsets = database::log_offsets(&tp.task);
                         let child = tp.task.start().await?;
+                        database::started(
+                            &tp.task,
+                            Trigger::Restart,
+                            Some("daemon restarted".to_string()),
+                            offsets,
+                        );
+                        let (run_id, stdout, stderr) = (
+                            tp.task.run_id.clone(),
+                            tp.task.stdout.clone(),
+                            tp.task.stderr.clone(),
+                        );
 
                         // 配置了 stdin 时，启动一个协程用于向子进程 stdin 写入数据
                         let rx = if Some(true) == tp.task.stdin {
@@ -134,6 +286,7 @@ pub mod global {
 
                         // 更新任务状态等数据
                         tp.task.pid = child.id();
+                        tp.task.pid_start_time = tp.task.pid.and_then(process::start_time);
                         tp.task.status = Some("running".to_string());
                         let now: u64 = SystemTime::now()
                             .duration_since(UNIX_EPOCH)
@@ -145,6 +298,8 @@ pub mod global {
                             has_restart: 0,
                             started_at: now,
                             stopped_at: 0,
+                            watch_restart: tt.watch_restart,
+                            last_change: tt.last_change.clone(),
                         });
 
                         // 启动协程等待子进程退出
@@ -168,12 +323,18 @@ pub mod global {
 
                             // 等待子进程退出
                             let res = child.wait().await.unwrap();
+                            database::finished(
+                                run_id.as_deref(),
+                                Some(&res),
+                                stdout.as_deref(),
+                                stderr.as_deref(),
+                            );
 
                             update(
                                 task.id,
                                 Some(None),
                                 Some(Some("stopped".to_string())),
-                                Some(res.code()),
+                                Some(res),
                                 None,
                                 None,
                             )
@@ -190,6 +351,11 @@ pub mod global {
 
                         // 保存监控进程结束协程的句柄
                         tp.joinhandle = Some(jh);
+
+                        // 配置了 watch 时监听文件变更
+                        if let Some(watch) = tp.task.watch.clone() {
+                            watch::register(task.id, watch, tp.task.dir.clone());
+                        }
                     }
                 }
                 _ => {}
@@ -199,11 +365,274 @@ pub mod global {
         Ok(())
     }
 
+    /// Monitor process started before daemon restarted, started_at and restart counters of
+    /// task are kept. Exit code of the process is unknown, async task is restarted
+    /// if restarts are left
+    ///
+    /// # params
+    ///
+    /// - `task`: task the process belongs to
+    /// - `pid`: process id
+    /// - `start_time`: start time of process
+    fn adopt(task: &Task, pid: u32, start_time: u64) -> JoinHandle<Option<i32>> {
+        let id = task.id;
+        let run_id = task.run_id.clone();
+        let stdout = task.stdout.clone();
+        let stderr = task.stderr.clone();
+        tokio::spawn(async move {
+            wait_exit(pid, start_time).await;
+            database::finished(
+                run_id.as_deref(),
+                None,
+                stdout.as_deref(),
+                stderr.as_deref(),
+            );
+
+            let mut tasks = TASKS.write().await;
+            if let Some(tp) = tasks.get_mut(&id) {
+                if tp.task.pid == Some(pid) {
+                    info!("Task [{}] adopted process {} exited", id, pid);
+                    tp.task.pid = None;
+                    tp.task.pid_start_time = None;
+                    tp.task.code = None;
+                    tp.task.signal = None;
+                    let status = tp.task.status.clone();
+                    match &mut tp.task.task_type {
+                        TaskType::Async(tt) if status.as_deref() == Some("running") => {
+                            let restart = match tt.max_restart {
+                                Some(max) => tt.has_restart < max,
+                                None => false,
+                            };
+                            if restart {
+                                tt.has_restart += 1;
+                                tp.task.status = Some("auto restart".to_string());
+                            } else {
+                                tp.task.status = Some("stopped".to_string());
+                            }
+                        }
+                        TaskType::Periodic(_) if status.as_deref() == Some("executing") => {
+                            tp.task.status = Some("interval".to_string());
+                        }
+                        TaskType::Scheduled(_) if status.as_deref() == Some("processing") => {
+                            tp.task.status = Some("waiting".to_string());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            drop(tasks);
+            let _ = cache().await;
+            None
+        })
+    }
+
+    /// Monitor run of periodic or scheduled task started before daemon restarted
+    ///
+    /// # params
+    ///
+    /// - `task`: task the run belongs to
+    /// - `run_id`: run id
+    /// - `pid`: process id
+    /// - `start_time`: start time of process
+    fn adopt_run(task: &Task, run_id: &str, pid: u32, start_time: u64) -> JoinHandle<Option<i32>> {
+        let id = task.id;
+        let run_id = run_id.to_string();
+        let stdout = task.stdout.clone();
+        let stderr = task.stderr.clone();
+        tokio::spawn(async move {
+            wait_exit(pid, start_time).await;
+            database::finished(Some(&run_id), None, stdout.as_deref(), stderr.as_deref());
+            info!("Task [{}] adopted process {} exited", id, pid);
+            finish_run(id, &run_id, None, None).await;
+            None
+        })
+    }
+
+    /// Remove exited run of periodic or scheduled task, the task becomes idle when no run
+    /// is left in flight
+    ///
+    /// # params
+    ///
+    /// - `id`: task id
+    /// - `run_id`: run id
+    /// - `exit`: exit status of the run, none if the process was not spawned by daemon
+    /// - `timed_out`: duration of the run if it was stopped by timeout, millisecond
+    async fn finish_run(id: i64, run_id: &str, exit: Option<ExitStatus>, timed_out: Option<u64>) {
+        let mut tasks = TASKS.write().await;
+        if let Some(tp) = tasks.get_mut(&id) {
+            let code = exit.and_then(|e| e.code());
+            tp.runs.remove(run_id);
+            tp.task.runs.retain(|r| r.run_id != run_id);
+            tp.task.code = code;
+            tp.task.signal = exit.and_then(|e| e.signal());
+            tp.task.timed_out = timed_out;
+            // 失败后按配置的次数延迟重试，由监控协程启动
+            let limit = match timed_out {
+                Some(_) => tp.task.retry_on_timeout,
+                None if code.is_some_and(|c| c != 0) => tp.task.retries,
+                None => None,
+            }
+            .unwrap_or(0);
+            let attempt = tp.task.attempt.unwrap_or(1);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Failed to get timestamp")
+                .as_millis() as u64;
+            let mut failed = false;
+            if limit > 0 {
+                if attempt <= limit {
+                    let delay = tp.task.retry_delay.unwrap_or(0) as f64
+                        * tp.task
+                            .retry_backoff
+                            .unwrap_or(1.0)
+                            .powi(attempt as i32 - 1);
+                    tp.task.retry_at = Some(now + (delay * 1000.0) as u64);
+                } else {
+                    info!("Task [{}] failed after {} attempts", id, attempt);
+                    failed = true;
+                }
+            }
+            // 重试用尽的失败单独记录，成功的运行清除记录
+            if failed {
+                tp.task.failed_at = Some(now / 1000);
+            } else if code == Some(0) && timed_out.is_none() {
+                tp.task.failed_at = None;
+            }
+            let last = tp.task.runs.last();
+            tp.task.pid = last.and_then(|r| r.pid);
+            tp.task.pid_start_time = last.and_then(|r| r.pid_start_time);
+            if tp.task.runs.is_empty() {
+                if let Some(status) = idle_status(&tp.task, failed) {
+                    tp.task.status = Some(status.to_string());
+                }
+            }
+        }
+        drop(tasks);
+        let _ = cache().await;
+    }
+
+    /// Status of periodic, scheduled or once task after its last run in flight exited, none
+    /// if the status is unchanged
+    ///
+    /// # params
+    ///
+    /// - `task`: task whose run exited
+    /// - `failed`: the run failed after its retries were exhausted
+    fn idle_status(task: &Task, failed: bool) -> Option<&'static str> {
+        let once = matches!(task.task_type, TaskType::Once(_));
+        match task.status.as_deref() {
+            // 一次性任务重试用尽后失败，周期任务和定时任务继续按计划运行
+            Some("processing") if once && failed => Some("failed"),
+            Some("processing") if once && task.retry_at.is_none() => Some("completed"),
+            Some("processing") => Some("waiting"),
+            Some("executing") => Some("interval"),
+            _ => None,
+        }
+    }
+
+    /// Remove task if it finished at least ttl ago, the finish time is recorded when the
+    /// task is first found finished
+    ///
+    /// # params
+    ///
+    /// - `id`: task id
+    /// - `ttl`: time to keep finished task, second
+    /// - `now`: current time, second
+    pub async fn expire(id: i64, ttl: u64, now: u64) -> Result<Option<Response>, Box<dyn Error>> {
+        let mut tasks = TASKS.write().await;
+        let tp = match tasks.get_mut(&id) {
+            Some(tp) => tp,
+            None => return Ok(None),
+        };
+        if !matches!(
+            tp.task.status.as_deref(),
+            Some("completed" | "failed" | "stopped")
+        ) {
+            tp.task.finished_at = None;
+            return Ok(None);
+        }
+        let finished_at = *tp.task.finished_at.get_or_insert(now);
+        if now < finished_at + ttl {
+            return Ok(None);
+        }
+        drop(tasks);
+        info!("Task [{}] finished and expired", id);
+        remove(
+            TaskFlag {
+                id,
+                name: None,
+                group: None,
+                mat: false,
+                selector: None,
+            },
+            true,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Take the pending retry of task if it is due
+    ///
+    /// # params
+    ///
+    /// - `id`: task id
+    /// - `now`: current time, millisecond
+    pub async fn take_retry(id: i64, now: u64) -> bool {
+        let mut tasks = TASKS.write().await;
+        match tasks.get_mut(&id) {
+            Some(tp) if tp.task.retry_at.is_some_and(|at| at <= now) => {
+                tp.task.retry_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take one queued run of task if the number of runs in flight is below the limit
+    ///
+    /// # params
+    ///
+    /// - `id`: task id
+    pub async fn dequeue(id: i64) -> bool {
+        let mut tasks = TASKS.write().await;
+        match tasks.get_mut(&id) {
+            Some(tp) if tp.task.queued > 0 => {
+                let max = tp.task.max_concurrent_runs.unwrap_or(1) as usize;
+                if tp.task.runs.len() < max {
+                    tp.task.queued -= 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Next run time of periodic task, computed and saved if the task has none, so a
+    /// random jitter is drawn once per run
+    ///
+    /// # params
+    ///
+    /// - `id`: task id
+    /// - `now`: current time, u64: second
+    pub async fn plan(id: i64, now: u64) -> Option<u64> {
+        let mut tasks = TASKS.write().await;
+        let tt = match tasks.get_mut(&id).map(|tp| &mut tp.task.task_type) {
+            Some(TaskType::Periodic(tt)) => tt,
+            _ => return None,
+        };
+        if tt.next_run == 0 {
+            monitor::schedule(tt, now);
+        }
+        Some(tt.next_run)
+    }
+
     pub async fn update(
         id: i64,
         pid: Option<Option<u32>>,
         status: Option<Option<String>>,
-        code: Option<Option<i32>>,
+        exit: Option<ExitStatus>,
         restart: Option<bool>,
         from_status: Option<Vec<&str>>,
     ) -> Result<Response, Box<dyn Error>> {
@@ -217,6 +646,7 @@ pub mod global {
         let tp = tasks.get_mut(&id).unwrap();
         if let Some(pid) = pid {
             tp.task.pid = pid;
+            tp.task.pid_start_time = pid.and_then(process::start_time);
         }
         if let Some(status) = status {
             match status.clone() {
@@ -226,11 +656,13 @@ pub mod global {
                             tp.task.task_type = TaskType::Async(AsyncTask {
                                 max_restart: tmp.max_restart,
                                 has_restart: tmp.has_restart,
-                                started_at: tmp.started_at,
-                                stopped_at: SystemTime::now()
+                                started_at: SystemTime::now()
                                     .duration_since(UNIX_EPOCH)
                                     .expect("Failed to get timestamp")
                                     .as_secs(),
+                                stopped_at: tmp.stopped_at,
+                                watch_restart: tmp.watch_restart,
+                                last_change: tmp.last_change,
                             });
                         }
                         TaskType::Periodic(tmp) => {
@@ -238,13 +670,10 @@ pub mod global {
                                 .duration_since(UNIX_EPOCH)
                                 .expect("Failed to get timestamp")
                                 .as_secs();
-                            tp.task.task_type =
-                                TaskType::Periodic(crate::common::task::PeriodicTask {
-                                    interval: tmp.interval,
-                                    last_run: now,
-                                    started_after: tmp.started_after,
-                                    sync: tmp.sync,
-                                });
+                            tp.task.task_type = TaskType::Periodic(PeriodicTask {
+                                last_run: now,
+                                ..tmp
+                            });
                         }
                         _ => {}
                     },
@@ -284,19 +713,53 @@ pub mod global {
                         has_restart: has,
                         started_at: tmp.started_at,
                         stopped_at: tmp.stopped_at,
+                        watch_restart: tmp.watch_restart,
+                        last_change: tmp.last_change,
                     });
                 }
                 _ => {}
             }
         }
-        if let Some(code) = code {
-            tp.task.code = code;
+        if let Some(exit) = exit {
+            tp.task.code = exit.code();
+            tp.task.signal = exit.signal();
         }
         Ok(Response::success(None))
     }
 
-    pub async fn add(mut task: Task) -> Result<Response, Box<dyn Error>> {
+    /// Expand home directory of log files and arguments, tasks are saved and hashed after
+    /// they are normalised
+    fn normalize(task: &mut Task) {
+        for file in [&mut task.stdout, &mut task.stderr].into_iter().flatten() {
+            *file = get_with_home_path(file).to_str().unwrap().to_string();
+        }
+        for arg in task.args.iter_mut() {
+            *arg = get_with_home(arg);
+        }
+    }
+
+    pub async fn add(task: Task) -> Result<Response, Box<dyn Error>> {
+        Ok(insert(task).await?.0)
+    }
+
+    /// Add task, id and name are allocated when missing
+    ///
+    /// # return
+    ///
+    /// - response and id of the task
+    async fn insert(mut task: Task) -> Result<(Response, i64), Box<dyn Error>> {
+        normalize(&mut task);
+        // 记录定义的哈希，apply 时未改动的任务保持不变
+        if task.hash.is_none() {
+            task.hash = Some(task.definition_hash()?);
+        }
         let mut tasks = TASKS.write().await;
+        if task.id <= 0 {
+            task.id = next_id(&tasks);
+        }
+        if task.name.is_empty() {
+            task.name = unique_name(&tasks, &task);
+        }
         let id = task.id;
         if tasks.contains_key(&id) {
             return Err(Box::new(std::io::Error::new(
@@ -304,162 +767,763 @@ pub mod global {
                 format!("Task [{}] already exists", id),
             )));
         }
+        // 名称唯一，按名称操作任务时不会误选
+        if let Some(tp) = tasks.values().find(|tp| tp.task.name == task.name) {
+            return Ok((
+                Response::wrong(format!(
+                    "Task name [{}] already used by task [{}]",
+                    task.name, tp.task.id
+                )),
+                id,
+            ));
+        }
+        // 实例的名称同样唯一
+        for index in 0..task.instances.unwrap_or(0) {
+            let name = instance_name(&task.name, index);
+            if let Some(tp) = tasks.values().find(|tp| tp.task.name == name) {
+                return Ok((
+                    Response::wrong(format!(
+                        "Task [{}] instance name [{}] already used by task [{}]",
+                        task.name, name, tp.task.id
+                    )),
+                    id,
+                ));
+            }
+        }
+
+        // 添加时检查进程属性，避免启动时才失败
+        if let Err(e) = Attributes::from_task(&task) {
+            return Ok((Response::wrong(format!("Task [{}] {}", task.name, e)), id));
+        }
+        match Credentials::from_task(&task) {
+            Ok(credentials) => {
+                if let Err(e) = task.environment(credentials.as_ref()) {
+                    return Ok((Response::wrong(format!("Task [{}] {}", task.name, e)), id));
+                }
+            }
+            Err(e) => {
+                return Ok((Response::wrong(format!("Task [{}] {}", task.name, e)), id));
+            }
+        }
+
+        if let (Some(port), Some(instances)) = (task.port, task.instances) {
+            if port as u32 + instances.saturating_sub(1) > u16::MAX as u32 {
+                return Ok((
+                    Response::wrong(format!(
+                        "Task [{}] invalid port: {} + {} instances exceeds {}",
+                        task.name,
+                        port,
+                        instances,
+                        u16::MAX
+                    )),
+                    id,
+                ));
+            }
+        }
+
+        if let Some(signal) = &task.stop_signal {
+            if process::parse_signal(signal).is_none() {
+                return Ok((
+                    Response::wrong(format!(
+                        "Task [{}] invalid stop signal: {}",
+                        task.name, signal
+                    )),
+                    id,
+                ));
+            }
+        }
+
+        if let Some(backoff) = task.retry_backoff {
+            if backoff.is_nan() || backoff < 1.0 {
+                return Ok((
+                    Response::wrong(format!(
+                        "Task [{}] invalid retry backoff: {}, must be at least 1",
+                        task.name, backoff
+                    )),
+                    id,
+                ));
+            }
+        }
 
         match task.task_type {
+            // 多实例任务只保存定义，由实例运行
+            _ if task.instances.is_some() => {
+                task.status = Some("added".to_string());
+            }
             TaskType::Scheduled(_) => {
                 task.status = Some("waiting".to_string());
             }
-            _ => {
-                task.status = Some("added".to_string());
+            TaskType::Once(ref mut tt) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Failed to get timestamp")
+                    .as_secs();
+                tt.run_at = match (&tt.at, tt.after) {
+                    (Some(_), Some(_)) => {
+                        return Ok((
+                            Response::wrong(format!(
+                                "Task [{}] at and after cannot be both set",
+                                task.name
+                            )),
+                            id,
+                        ));
+                    }
+                    (Some(at), None) => match OnceTask::parse_at(at) {
+                        Ok(run_at) => run_at,
+                        Err(e) => {
+                            return Ok((Response::wrong(format!("Task [{}] {}", task.name, e)), id))
+                        }
+                    },
+                    (None, Some(after)) => now + after,
+                    (None, None) => now,
+                };
+                task.status = Some("waiting".to_string());
+            }
+            _ => {
+                task.status = Some("added".to_string());
+            }
+        }
+
+        for file in [&task.stdout, &task.stderr].into_iter().flatten() {
+            let parent = Path::new(file).parent().unwrap();
+            if parent.exists() {
+                std::fs::create_dir_all(parent).unwrap();
+            }
+        }
+
+        let tn = task.name.clone();
+        let instances = task.instances;
+        let parent = task.clone();
+        let tp = TaskProcess {
+            task,
+            joinhandle: None,
+            runs: HashMap::new(),
+            tx: None,
+        };
+        tasks.insert(id, tp);
+        if let Some(instances) = instances {
+            for index in 0..instances {
+                let instance = instance_task(&parent, index, next_id(&tasks));
+                tasks.insert(
+                    instance.id,
+                    TaskProcess {
+                        task: instance,
+                        joinhandle: None,
+                        runs: HashMap::new(),
+                        tx: None,
+                    },
+                );
+            }
+            cache().await?;
+            return Ok((
+                Response::success(Some(Data::String(format!(
+                    "Task [{}] added with {} instances",
+                    tn, instances
+                )))),
+                id,
+            ));
+        }
+        cache().await?;
+        Ok((
+            Response::success(Some(Data::String(format!("Task [{}] added", tn)))),
+            id,
+        ))
+    }
+
+    /// Next free task id, used by instances of multi-instance tasks
+    fn next_id(tasks: &HashMap<i64, TaskProcess>) -> i64 {
+        tasks.keys().max().map(|id| id + 1).unwrap_or(1).max(1)
+    }
+
+    /// Name for task added without name, command name and id, e.g. "python-12"
+    fn unique_name(tasks: &HashMap<i64, TaskProcess>, task: &Task) -> String {
+        let command = Path::new(&task.command)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("task".to_string());
+        let base = format!("{}-{}", command, task.id);
+        let mut name = base.clone();
+        let mut n = 1;
+        while tasks.values().any(|tp| tp.task.name == name) {
+            name = format!("{}-{}", base, n);
+            n += 1;
+        }
+        name
+    }
+
+    /// Name of instance of multi-instance task, e.g. "web-0"
+    fn instance_name(parent: &str, index: u32) -> String {
+        format!("{}-{}", parent, index)
+    }
+
+    /// Build instance from multi-instance task, runtime fields of the parent are not copied
+    ///
+    /// # params
+    ///
+    /// - `parent`: multi-instance task
+    /// - `index`: instance index
+    /// - `id`: id of instance
+    fn instance_task(parent: &Task, index: u32, id: i64) -> Task {
+        let mut task = parent.clone();
+        task.id = id;
+        task.name = instance_name(&parent.name, index);
+        task.instances = None;
+        task.parent = Some(parent.id);
+        task.instance = Some(index);
+        task.pid = None;
+        task.pid_start_time = None;
+        task.run_id = None;
+        task.runs = Vec::new();
+        task.queued = 0;
+        task.attempt = None;
+        task.retry_at = None;
+        task.code = None;
+        task.signal = None;
+        task.timed_out = None;
+        task.finished_at = None;
+        task.hash = None;
+        task.failed_at = None;
+        task.status = match task.task_type {
+            TaskType::Scheduled(_) => Some("waiting".to_string()),
+            _ => Some("added".to_string()),
+        };
+        task
+    }
+
+    /// Ids of instances belong to task, sorted by instance index
+    fn children(tasks: &HashMap<i64, TaskProcess>, id: i64) -> Vec<i64> {
+        let mut children: Vec<(u32, i64)> = tasks
+            .values()
+            .filter(|tp| tp.task.parent == Some(id))
+            .map(|tp| (tp.task.instance.unwrap_or(0), tp.task.id))
+            .collect();
+        children.sort();
+        children.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Ids of instances of multi-instance task, None if task is not a multi-instance task
+    pub async fn instances(id: i64) -> Option<Vec<i64>> {
+        let tasks = TASKS.read().await;
+        match tasks.get(&id) {
+            Some(tp) if tp.task.instances.is_some() => Some(children(&tasks, id)),
+            _ => None,
+        }
+    }
+
+    /// Apply operation to every instance of multi-instance task, or to the task itself
+    ///
+    /// # params
+    ///
+    /// - `tf`: task flag
+    /// - `f`: operation, e.g. start, stop
+    pub async fn each<F, Fut>(tf: TaskFlag, f: F) -> Result<Response, Box<dyn Error>>
+    where
+        F: Fn(TaskFlag) -> Fut,
+        Fut: Future<Output = Result<Response, Box<dyn Error>>>,
+    {
+        let ids = match instances(tf.id).await {
+            Some(ids) => ids,
+            None => return f(tf).await,
+        };
+        if ids.is_empty() {
+            return Ok(Response::wrong(format!(
+                "Task [{}] has no instances",
+                tf.id
+            )));
+        }
+        let mut code = 10000;
+        let mut msgs = Vec::new();
+        for id in ids {
+            let res = match f(TaskFlag {
+                id,
+                name: None,
+                group: None,
+                mat: false,
+                selector: None,
+            })
+            .await
+            {
+                Ok(res) => res,
+                Err(e) => Response::failed(e.to_string()),
+            };
+            code = code.max(res.code);
+            msgs.push(res.data.unwrap_or_default().to_string());
+        }
+        let msg = match code {
+            10000 => "Success",
+            40000 => "Wrong",
+            _ => "Failed",
+        };
+        Ok(Response::new(
+            code,
+            msg.to_string(),
+            Some(Data::String(msgs.join("; "))),
+        ))
+    }
+
+    /// Ids of tasks selected by task flag, sorted by id
+    ///
+    /// Tasks are selected by id, by name, or by group, name and group are regex patterns if
+    /// `mat` is set. Label selector narrows the selection, or selects from all tasks if it is
+    /// the only condition. Instances are left out if their multi-instance task is selected.
+    ///
+    /// # params
+    ///
+    /// - `tasks`: all tasks
+    /// - `tf`: task flag
+    fn resolve(
+        tasks: &HashMap<i64, TaskProcess>,
+        tf: &TaskFlag,
+    ) -> Result<Vec<i64>, Box<dyn Error>> {
+        let matched = |value: &str, pattern: &str| -> Result<bool, Box<dyn Error>> {
+            if tf.mat {
+                Ok(Regex::new(pattern)?.is_match(value))
+            } else {
+                Ok(value == pattern)
+            }
+        };
+        let mut selected = Vec::new();
+        if tf.id > 0 {
+            if tasks.contains_key(&tf.id) {
+                selected.push(tf.id);
+            }
+        } else if let Some(name) = tf.name.as_ref().filter(|name| !name.is_empty()) {
+            for tp in tasks.values() {
+                if matched(&tp.task.name, name)? {
+                    selected.push(tp.task.id);
+                }
+            }
+        } else if let Some(group) = &tf.group {
+            for tp in tasks.values() {
+                if let Some(g) = &tp.task.group {
+                    if matched(g, group)? {
+                        selected.push(tp.task.id);
+                    }
+                }
+            }
+        } else if tf.selector.is_some() {
+            selected.extend(tasks.keys());
+        }
+        if let Some(selector) = &tf.selector {
+            let selector = Selector::parse(selector)?;
+            selected.retain(|id| {
+                tasks
+                    .get(id)
+                    .is_some_and(|tp| selector.matches(&tp.task.labels))
+            });
+        }
+        let ids = selected.iter().copied().filter(|id| {
+            match tasks.get(id).and_then(|tp| tp.task.parent) {
+                Some(parent) => !selected.contains(&parent),
+                None => true,
+            }
+        });
+        let mut ids: Vec<i64> = ids.collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Apply operation to every task selected by task flag
+    ///
+    /// # params
+    ///
+    /// - `tf`: task flag, see `resolve`
+    /// - `f`: operation, called with task flag of each selected task
+    ///
+    /// # return
+    ///
+    /// - result of each task
+    pub async fn select<F, Fut>(tf: TaskFlag, f: F) -> Result<Response, Box<dyn Error>>
+    where
+        F: Fn(TaskFlag) -> Fut,
+        Fut: Future<Output = Result<Response, Box<dyn Error>>>,
+    {
+        if tf.id <= 0
+            && tf.name.as_deref().unwrap_or_default().is_empty()
+            && tf.group.is_none()
+            && tf.selector.is_none()
+        {
+            return Ok(Response::wrong(
+                "Task id or name or group or selector is required".to_string(),
+            ));
+        }
+        let ids = resolve(&*TASKS.read().await, &tf)?;
+        if ids.is_empty() {
+            let msg = if tf.id > 0 {
+                format!("Task [{}] not exists", tf.id)
+            } else if let Some(name) = tf.name.as_ref().filter(|name| !name.is_empty()) {
+                format!("Task [:{}] not exists", name)
+            } else if let Some(group) = tf.group {
+                format!("Task group [{}] not exists", group)
+            } else {
+                format!(
+                    "No task matches selector [{}]",
+                    tf.selector.unwrap_or_default()
+                )
+            };
+            return Ok(Response::wrong(msg));
+        }
+        let mut results = Vec::new();
+        for id in ids {
+            let res = match f(TaskFlag::new(id)).await {
+                Ok(res) => res,
+                Err(e) => Response::failed(e.to_string()),
+            };
+            results.push(res);
+        }
+        Ok(Response::results(results))
+    }
+
+    /// Change number of instances of multi-instance task, new instances are started if
+    /// other instances are running, removed instances are stopped
+    ///
+    /// # params
+    ///
+    /// - `tf`: task flag of multi-instance task
+    /// - `count`: number of instances
+    pub async fn scale(tf: TaskFlag, count: u32) -> Result<Response, Box<dyn Error>> {
+        let mut tasks = TASKS.write().await;
+        let id = match resolve(&tasks, &tf)?[..] {
+            [id] => id,
+            [] => {
+                return Ok(Response::wrong(format!(
+                    "Task [{}:{}] not exists",
+                    tf.id,
+                    tf.name.unwrap_or_default()
+                )))
+            }
+            _ => return Ok(Response::wrong("Task is not unique".to_string())),
+        };
+        let parent = match tasks.get(&id) {
+            Some(tp) if tp.task.instances.is_some() => tp.task.clone(),
+            Some(tp) => {
+                return Ok(Response::wrong(format!(
+                    "Task [{}:{}] is not a multi-instance task",
+                    id, tp.task.name
+                )))
+            }
+            None => return Ok(Response::wrong(format!("Task [{}] not exists", id))),
+        };
+        if let Some(port) = parent.port {
+            if port as u32 + count.saturating_sub(1) > u16::MAX as u32 {
+                return Ok(Response::wrong(format!(
+                    "Task [{}] invalid port: {} + {} instances exceeds {}",
+                    parent.name,
+                    port,
+                    count,
+                    u16::MAX
+                )));
             }
         }
 
-        if let Some(so) = &task.stdout {
-            let stdout = get_with_home_path(so);
-            let parent = stdout.parent().unwrap();
-            if parent.exists() {
-                std::fs::create_dir_all(parent).unwrap();
+        let current = children(&tasks, id);
+        let active = current.iter().any(|cid| {
+            matches!(
+                tasks.get(cid).and_then(|tp| tp.task.status.as_deref()),
+                Some("running" | "auto restart" | "interval" | "executing")
+            )
+        });
+        // 新增实例的名称不能被其他任务使用
+        let missing: Vec<u32> = (0..count)
+            .filter(|index| {
+                !current
+                    .iter()
+                    .any(|cid| tasks.get(cid).and_then(|tp| tp.task.instance) == Some(*index))
+            })
+            .collect();
+        for index in &missing {
+            let name = instance_name(&parent.name, *index);
+            if let Some(tp) = tasks.values().find(|tp| tp.task.name == name) {
+                return Ok(Response::wrong(format!(
+                    "Task [{}] instance name [{}] already used by task [{}]",
+                    parent.name, name, tp.task.id
+                )));
             }
-            task.stdout = Some(stdout.to_str().unwrap().to_string());
         }
-        if let Some(se) = &task.stderr {
-            let stderr = get_with_home_path(se);
-            let parent = stderr.parent().unwrap();
-            if parent.exists() {
-                std::fs::create_dir_all(parent).unwrap();
-            }
-            task.stderr = Some(stderr.to_str().unwrap().to_string());
+        let mut added = Vec::new();
+        for index in missing {
+            let instance = instance_task(&parent, index, next_id(&tasks));
+            added.push(instance.id);
+            tasks.insert(
+                instance.id,
+                TaskProcess {
+                    task: instance,
+                    joinhandle: None,
+                    runs: HashMap::new(),
+                    tx: None,
+                },
+            );
         }
+        let removed: Vec<i64> = current
+            .into_iter()
+            .filter(|cid| tasks.get(cid).and_then(|tp| tp.task.instance).unwrap_or(0) >= count)
+            .collect();
+        if let Some(tp) = tasks.get_mut(&id) {
+            tp.task.instances = Some(count);
+        }
+        drop(tasks);
 
-        let mut args = task.args.clone();
-        for i in 0..args.len() {
-            args[i] = get_with_home(&args[i]);
+        for rid in &removed {
+            let tf = TaskFlag {
+                id: *rid,
+                name: None,
+                group: None,
+                mat: false,
+                selector: None,
+            };
+            // 未运行的实例停止失败时直接移除
+            let _ = stop(tf, false).await;
+            let mut tasks = TASKS.write().await;
+            tasks.remove(rid);
+            watch::unregister(*rid);
+        }
+        if active {
+            for aid in &added {
+                start(TaskFlag {
+                    id: *aid,
+                    name: None,
+                    group: None,
+                    mat: false,
+                    selector: None,
+                })
+                .await?;
+            }
         }
-        task.args = args;
-        let tn = task.name.clone();
-        let tp = TaskProcess {
-            task,
-            joinhandle: None,
-            tx: None,
-        };
-        tasks.insert(id, tp);
         cache().await?;
         Ok(Response::success(Some(Data::String(format!(
-            "Task [{}] added",
-            tn
+            "Task [{}] scaled to {} instances, {} added, {} removed",
+            parent.name,
+            count,
+            added.len(),
+            removed.len()
         )))))
     }
 
     pub async fn reload(task: Task) -> Result<Response, Box<dyn Error>> {
-        remove(
+        // 未指定 id 时按名称替换
+        select(
             TaskFlag {
                 id: task.id,
-                name: None,
+                name: Some(task.name.clone()),
                 group: None,
                 mat: false,
+                selector: None,
             },
-            false,
+            |tf| remove(tf, false),
         )
         .await?;
         add(task).await
     }
 
-    pub async fn remove(tf: TaskFlag, to_cache: bool) -> Result<Response, Box<dyn Error>> {
-        let mut tasks = TASKS.write().await;
-        if tf.id > 0 {
-            if !tasks.contains_key(&tf.id) {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Task [{}] not exists", tf.id),
-                )));
-            }
-            let tp = tasks.get(&tf.id).unwrap();
-            if Some("running".to_string()) == tp.task.status {
-                return Ok(Response::wrong(
-                    "Task is running, please stop it first".to_string(),
-                ));
-            }
-            if let Some(removed) = tasks.remove(&tf.id) {
-                if to_cache {
-                    cache().await?;
-                }
-                Ok(Response::success(Some(Data::String(format!(
-                    "Task [{}:{}] removed",
-                    removed.task.id, removed.task.name
-                )))))
+    /// Reconcile tasks with desired tasks, new tasks are added, changed tasks are reloaded
+    /// and restarted if they were active, unchanged tasks are left alone
+    ///
+    /// # params
+    ///
+    /// - `desired`: desired tasks, matched with tasks by id, or by name if id is 0
+    /// - `prune`: remove tasks that are not desired, tasks loaded from include directories
+    ///   are kept
+    /// - `dry_run`: return the plan without changing tasks
+    pub async fn apply(
+        desired: Vec<Task>,
+        prune: bool,
+        dry_run: bool,
+    ) -> Result<Response, Box<dyn Error>> {
+        apply_with(desired, |t| prune && t.source.is_none(), dry_run).await
+    }
+
+    /// Reconcile tasks with desired tasks like `apply`, only tasks matched by `prune` are
+    /// removed if they are not desired
+    ///
+    /// # params
+    ///
+    /// - `desired`: desired tasks, matched with tasks by id, or by name if id is 0
+    /// - `prune`: whether a task that is not desired is removed
+    /// - `dry_run`: return the plan without changing tasks
+    pub async fn apply_with<P>(
+        desired: Vec<Task>,
+        prune: P,
+        dry_run: bool,
+    ) -> Result<Response, Box<dyn Error>>
+    where
+        P: Fn(&Task) -> bool,
+    {
+        let tasks = TASKS.read().await;
+        let mut plan: Vec<(Change, Option<Task>)> = Vec::new();
+        let mut declared = Vec::new();
+        for mut task in desired {
+            normalize(&mut task);
+            let hash = task.definition_hash()?;
+            let existing = if task.id > 0 {
+                tasks.get(&task.id)
             } else {
-                Ok(Response::wrong(format!("Task [{}:] not exists", tf.id)))
-            }
-        } else if let Some(name) = tf.name {
-            // find id by name
-            let id = tasks
-                .iter()
-                .find(|(_, tp)| tp.task.name == name)
-                .map(|(id, _)| *id);
-            if let Some(id) = id {
-                let tp = tasks.get(&id).unwrap();
-                if Some("running".to_string()) == tp.task.status {
-                    return Ok(Response::wrong(
-                        "Task is running, please stop it first".to_string(),
-                    ));
-                }
-                if let Some(removed) = tasks.remove(&id) {
-                    if to_cache {
-                        cache().await?;
+                tasks
+                    .values()
+                    .find(|tp| tp.task.parent.is_none() && tp.task.name == task.name)
+            };
+            let action = match existing {
+                Some(tp) => {
+                    declared.push(tp.task.id);
+                    // 保持 id 不变
+                    task.id = tp.task.id;
+                    if tp.task.hash.as_deref() == Some(hash.as_str()) {
+                        "unchanged"
+                    } else {
+                        "reload"
                     }
-                    Ok(Response::success(Some(Data::String(format!(
-                        "Task [{}:{}] removed",
-                        removed.task.id, removed.task.name
-                    )))))
-                } else {
-                    Ok(Response::wrong(format!(
-                        "Task [{}:{}] not exists",
-                        tf.id, name
-                    )))
                 }
-            } else {
-                return Ok(Response::wrong(format!("Task [:{}] not exists", name)));
-            }
-        } else if let Some(group) = &tf.group {
-            let ids = tasks
-                .iter()
-                .filter(|(_, tp)| {
-                    if let Some(g) = &tp.task.group {
-                        g == group
+                None => "add",
+            };
+            task.hash = Some(hash);
+            let change = Change {
+                action: action.to_string(),
+                id: task.id,
+                name: task.name.clone(),
+                result: None,
+            };
+            plan.push((change, Some(task)));
+        }
+        let mut pruned: Vec<&Task> = tasks
+            .values()
+            .map(|tp| &tp.task)
+            .filter(|t| t.parent.is_none() && !declared.contains(&t.id) && prune(t))
+            .collect();
+        pruned.sort_by_key(|t| t.id);
+        for t in pruned {
+            let change = Change {
+                action: "prune".to_string(),
+                id: t.id,
+                name: t.name.clone(),
+                result: None,
+            };
+            plan.push((change, None));
+        }
+        drop(tasks);
+
+        if dry_run {
+            let changes = plan.into_iter().map(|(change, _)| change).collect();
+            return Ok(Response::success(Some(Data::Plan(changes))));
+        }
+        let mut changes = Vec::new();
+        for (mut change, task) in plan {
+            let res = match (change.action.as_str(), task) {
+                ("add", Some(task)) => insert(task).await.map(|(res, id)| {
+                    change.id = id;
+                    res
+                }),
+                ("reload", Some(task)) => {
+                    let active = deactivate(task.id).await;
+                    let res = reload(task)
+                        .await
+                        .unwrap_or_else(|e| Response::failed(e.to_string()));
+                    if res.code == 10000 && active {
+                        each(TaskFlag::new(change.id), start).await
                     } else {
-                        false
+                        Ok(res)
                     }
-                })
-                .map(|(id, _)| *id)
-                .collect::<Vec<i64>>();
-            if ids.is_empty() {
-                return Ok(Response::wrong(format!(
-                    "Task group [{}] not exists",
-                    group
-                )));
-            }
-            let mut removed = vec![];
-            for id in ids {
-                let tp = tasks.get(&id).unwrap();
-                if Some("running".to_string()) == tp.task.status {
-                    return Ok(Response::wrong(
-                        "Task is running, please stop it first".to_string(),
-                    ));
                 }
-                if let Some(r) = tasks.remove(&id) {
-                    removed.push(format!("{}:{}", r.task.id, r.task.name));
+                ("prune", _) => {
+                    deactivate(change.id).await;
+                    remove(TaskFlag::new(change.id), true).await
+                }
+                ("unchanged", Some(task)) if task.source.is_some() => {
+                    // 来源不计入定义哈希，未变化的任务同样记录来源
+                    let mut tasks = TASKS.write().await;
+                    if let Some(tp) = tasks.get_mut(&task.id) {
+                        if tp.task.source != task.source {
+                            tp.task.source = task.source;
+                            cache().await?;
+                        }
+                    }
+                    changes.push(change);
+                    continue;
                 }
+                _ => {
+                    changes.push(change);
+                    continue;
+                }
+            };
+            change.result = Some(res.unwrap_or_else(|e| Response::failed(e.to_string())));
+            changes.push(change);
+        }
+        let mut res = Response::results(
+            changes
+                .iter()
+                .filter_map(|change| change.result.clone())
+                .collect(),
+        );
+        res.data = Some(Data::Plan(changes));
+        Ok(res)
+    }
+
+    /// Stop task and its instances if running, return whether any of them was active
+    async fn deactivate(id: i64) -> bool {
+        let tasks = TASKS.read().await;
+        let mut ids = children(&tasks, id);
+        ids.push(id);
+        let active = ids.iter().any(|id| {
+            matches!(
+                tasks.get(id).and_then(|tp| tp.task.status.as_deref()),
+                Some("running" | "auto restart" | "interval" | "executing")
+            )
+        });
+        drop(tasks);
+        if active {
+            // 未运行的实例停止失败，忽略
+            let _ = each(TaskFlag::new(id), |tf| stop(tf, false)).await;
+        }
+        active
+    }
+
+    pub async fn remove(tf: TaskFlag, to_cache: bool) -> Result<Response, Box<dyn Error>> {
+        let mut tasks = TASKS.write().await;
+        if !tasks.contains_key(&tf.id) {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Task [{}] not exists", tf.id),
+            )));
+        }
+        let tp = tasks.get(&tf.id).unwrap();
+        if Some("running".to_string()) == tp.task.status {
+            return Ok(Response::wrong(
+                "Task is running, please stop it first".to_string(),
+            ));
+        }
+        if let Some(res) = remove_instances(&mut tasks, tf.id) {
+            return Ok(res);
+        }
+        if let Some(removed) = tasks.remove(&tf.id) {
+            watch::unregister(tf.id);
+            drop(tasks);
+            if to_cache {
+                cache().await?;
             }
             Ok(Response::success(Some(Data::String(format!(
-                "Task group [{}({})] removed",
-                group,
-                removed.len()
+                "Task [{}:{}] removed",
+                removed.task.id, removed.task.name
             )))))
         } else {
-            Ok(Response::wrong(
-                "Task id or name or group is required".to_string(),
-            ))
+            Ok(Response::wrong(format!("Task [{}:] not exists", tf.id)))
+        }
+    }
+
+    /// Remove instances of multi-instance task, return response if any instance is running
+    fn remove_instances(tasks: &mut HashMap<i64, TaskProcess>, id: i64) -> Option<Response> {
+        let ids = children(tasks, id);
+        let running = ids.iter().any(|cid| {
+            tasks.get(cid).and_then(|tp| tp.task.status.clone()) == Some("running".to_string())
+        });
+        if running {
+            return Some(Response::wrong(
+                "Task instance is running, please stop it first".to_string(),
+            ));
+        }
+        for cid in ids {
+            tasks.remove(&cid);
+            watch::unregister(cid);
         }
+        None
     }
 
     pub async fn delete(tf: TaskFlag) -> Result<Response, Box<dyn Error>> {
@@ -477,8 +1541,12 @@ pub mod global {
         if let Some(jh) = &tp.joinhandle {
             jh.abort();
         }
+        for jh in tp.runs.values() {
+            jh.abort();
+        }
         let tn = tf.id.clone();
         tasks.remove(&tf.id);
+        watch::unregister(tf.id);
         Ok(Response::success(Some(Data::String(format!(
             "Task [{}] deleted",
             tn
@@ -486,6 +1554,21 @@ pub mod global {
     }
 
     pub async fn start(tf: TaskFlag) -> Result<Response, Box<dyn Error>> {
+        start_with(tf, Trigger::Manual, None).await
+    }
+
+    /// Start task, the run is recorded with what started it
+    ///
+    /// # params
+    ///
+    /// - `tf`: task flag
+    /// - `trigger`: what started the run
+    /// - `reason`: why the run was started
+    pub async fn start_with(
+        tf: TaskFlag,
+        trigger: Trigger,
+        reason: Option<String>,
+    ) -> Result<Response, Box<dyn Error>> {
         let mut tasks = TASKS.write().await;
         if !tasks.contains_key(&tf.id) {
             return Err(Box::new(std::io::Error::new(
@@ -493,55 +1576,194 @@ pub mod global {
                 format!("Task [{}] not exists", tf.id),
             )));
         }
-        let tp = tasks.get_mut(&tf.id).unwrap();
+        let mut tp = tasks.get_mut(&tf.id).unwrap();
 
         match &tp.task.task_type {
-            TaskType::Scheduled(_) => {
+            TaskType::Scheduled(_) | TaskType::Periodic(_) | TaskType::Once(_) => {
                 let id = tf.id;
                 let name = tf.name.clone();
+                let busy = match &tp.task.task_type {
+                    TaskType::Periodic(_) => "executing",
+                    _ => "processing",
+                };
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Failed to get timestamp");
+
+                // 首次启动的周期任务由监控在下次运行时间启动，抖动和对齐对第一次运行同样生效
+                let scheduled = matches!(tp.task.status.as_deref(), Some("interval" | "executing"));
+                if let TaskType::Periodic(tt) = &mut tp.task.task_type {
+                    if trigger == Trigger::Manual && !scheduled {
+                        let mut first = PeriodicTask {
+                            last_run: 0,
+                            ..tt.clone()
+                        };
+                        monitor::schedule(&mut first, now.as_secs());
+                        tt.slot = first.slot;
+                        tt.next_run = first.next_run;
+                        if tt.next_run > now.as_secs() {
+                            tp.task.status = Some("interval".to_string());
+                            cache().await?;
+                            return Ok(Response::success(Some(Data::String(format!(
+                                "Task [{}] started, first run in {}s",
+                                id,
+                                tt.next_run - now.as_secs()
+                            )))));
+                        }
+                    }
+                }
+
+                let signal = tp
+                    .task
+                    .stop_signal
+                    .as_deref()
+                    .and_then(process::parse_signal)
+                    .unwrap_or(libc::SIGTERM);
+
+                // 按并发策略处理仍在运行的实例，跳过的运行同样推迟下次运行时间
+                let live = tp.task.runs.len();
+                let max = tp.task.max_concurrent_runs.map(|max| max as usize);
+                let skipped = match tp.task.policy() {
+                    ConcurrencyPolicy::Forbid if live > 0 => Some(format!(
+                        "Task [{}] is {}, run skipped by concurrency policy forbid",
+                        id, busy
+                    )),
+                    ConcurrencyPolicy::Allow if max.is_some_and(|max| live >= max) => Some(
+                        format!("Task [{}] has {} runs in flight, run skipped", id, live),
+                    ),
+                    _ => None,
+                };
+                if let Some(msg) = skipped {
+                    if let TaskType::Periodic(tt) = &mut tp.task.task_type {
+                        monitor::advance(tt, trigger, now.as_secs());
+                        cache().await?;
+                    }
+                    return Ok(Response::wrong(msg));
+                }
+                match tp.task.policy() {
+                    ConcurrencyPolicy::Queue if live >= max.unwrap_or(1) => {
+                        tp.task.queued += 1;
+                        if let TaskType::Periodic(tt) = &mut tp.task.task_type {
+                            monitor::advance(tt, trigger, now.as_secs());
+                        }
+                        cache().await?;
+                        return Ok(Response::success(Some(Data::String(format!(
+                            "Task [{}] is {}, run queued",
+                            id, busy
+                        )))));
+                    }
+                    ConcurrencyPolicy::Replace if live > 0 => {
+                        let pids: Vec<(String, u32)> = tp
+                            .task
+                            .runs
+                            .iter()
+                            .filter_map(|run| match (run.pid, run.pid_start_time) {
+                                (Some(pid), Some(start_time))
+                                    if process::start_time(pid) == Some(start_time) =>
+                                {
+                                    Some((run.run_id.clone(), pid))
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                        // 发送信号期间不持有任务锁
+                        drop(tasks);
+                        for (run_id, pid) in pids {
+                            info!("Task [{}] run {} replaced", id, run_id);
+                            let mut child: Child = Command::new("kill")
+                                .arg(format!("-{}", signal))
+                                .arg(pid.to_string())
+                                .stdin(Stdio::null())
+                                .stdout(Stdio::null())
+                                .stderr(Stdio::null())
+                                .spawn()?;
+                            child.wait().await?;
+                        }
+                        tasks = TASKS.write().await;
+                        tp = match tasks.get_mut(&id) {
+                            Some(tp) => tp,
+                            None => {
+                                return Err(Box::new(std::io::Error::new(
+                                    std::io::ErrorKind::Other,
+                                    format!("Task [{}] not exists", id),
+                                )));
+                            }
+                        };
+                    }
+                    _ => {}
+                }
 
+                tp.task.run_id = Some(get_id().await);
+                tp.task.attempt = match (trigger, tp.task.attempt) {
+                    (Trigger::Retry, Some(attempt)) => Some(attempt + 1),
+                    _ => Some(1),
+                };
+                tp.task.retry_at = None;
+                let offsets = database::log_offsets(&tp.task);
                 let mut child = tp.task.start().await?;
+                database::started(&tp.task, trigger, reason.clone(), offsets);
+                let (run_id, stdout, stderr) = (
+                    tp.task.run_id.clone(),
+                    tp.task.stdout.clone(),
+                    tp.task.stderr.clone(),
+                );
+                let (timeout, kill_timeout) = (tp.task.timeout, tp.task.kill_timeout.unwrap_or(5));
+
+                // 每次运行单独记录，list 可以列出所有运行中的实例
                 let pid = child.id();
+                let run = LiveRun {
+                    run_id: run_id.clone().unwrap_or_default(),
+                    pid,
+                    pid_start_time: pid.and_then(process::start_time),
+                    started_at: now.as_millis() as u64,
+                };
+                tp.task.pid = run.pid;
+                tp.task.pid_start_time = run.pid_start_time;
+                tp.task.status = Some(busy.to_string());
+                if let TaskType::Periodic(tt) = &mut tp.task.task_type {
+                    monitor::advance(tt, trigger, now.as_secs());
+                }
+                tp.task.runs.push(run.clone());
+
+                let live_id = run.run_id.clone();
                 let jh: JoinHandle<Option<i32>> = tokio::spawn(async move {
-                    let res = child.wait().await.unwrap();
-                    let code = res.code();
-                    info!(
-                        "Task [{}:{}] exited with code: {:?}",
-                        id,
-                        name.unwrap_or(String::new()),
-                        code
+                    let started = std::time::Instant::now();
+                    let (res, timed_out) =
+                        wait_timeout(&mut child, timeout, signal, kill_timeout).await;
+                    let res = res.unwrap();
+                    database::finished(
+                        run_id.as_deref(),
+                        Some(&res),
+                        stdout.as_deref(),
+                        stderr.as_deref(),
                     );
+                    let code = res.code();
+                    let timed_out = if timed_out {
+                        database::timed_out(run_id.as_deref());
+                        info!(
+                            "Task [{}:{}] timed out after {}s",
+                            id,
+                            name.unwrap_or_default(),
+                            timeout.unwrap_or(0)
+                        );
+                        Some(started.elapsed().as_millis() as u64)
+                    } else {
+                        info!(
+                            "Task [{}:{}] exited with code: {:?}",
+                            id,
+                            name.unwrap_or_default(),
+                            code
+                        );
+                        None
+                    };
 
-                    update(
-                        tf.id,
-                        Some(None),
-                        Some(Some("waiting".to_string())),
-                        Some(res.code()),
-                        Some(false),
-                        Some(vec!["processing"]),
-                    )
-                    .await
-                    .unwrap();
-
-                    cache().await.unwrap();
+                    finish_run(id, &live_id, Some(res), timed_out).await;
 
-                    return res.code();
+                    code
                 });
+                tp.runs.insert(run.run_id, jh);
 
-                tp.joinhandle = Some(jh);
-
-                tokio::spawn(async move {
-                    update(
-                        id,
-                        Some(pid),
-                        Some(Some("processing".to_string())),
-                        None,
-                        None,
-                        None,
-                    )
-                    .await
-                    .unwrap();
-                });
+                cache().await?;
                 Ok(Response::success(Some(Data::String(format!(
                     "Task [{}] started",
                     id
@@ -556,7 +1778,15 @@ pub mod global {
                     )));
                 }
 
+                tp.task.run_id = Some(get_id().await);
+                let offsets = database::log_offsets(&tp.task);
                 let child = tp.task.start().await?;
+                database::started(&tp.task, trigger, reason.clone(), offsets);
+                let (run_id, stdout, stderr) = (
+                    tp.task.run_id.clone(),
+                    tp.task.stdout.clone(),
+                    tp.task.stderr.clone(),
+                );
 
                 let rx = if Some(true) == tp.task.stdin {
                     let (tx, rx) = mpsc::channel::<Vec<u8>>(CHANNEL_SIZE);
@@ -587,6 +1817,12 @@ pub mod global {
                     };
 
                     let res = child.wait().await.unwrap();
+                    database::finished(
+                        run_id.as_deref(),
+                        Some(&res),
+                        stdout.as_deref(),
+                        stderr.as_deref(),
+                    );
                     let code = res.code();
                     let exit = if let Some(max) = max {
                         if let Some(code) = code {
@@ -602,7 +1838,7 @@ pub mod global {
                             tf.id,
                             Some(None),
                             Some(Some("stopped".to_string())),
-                            Some(res.code()),
+                            Some(res),
                             None,
                             None,
                         )
@@ -618,7 +1854,7 @@ pub mod global {
                             tf.id,
                             Some(None),
                             Some(Some("auto restart".to_string())),
-                            Some(res.code()),
+                            Some(res),
                             Some(true),
                             None,
                         )
@@ -636,6 +1872,10 @@ pub mod global {
 
                 tp.joinhandle = Some(jh);
 
+                if let Some(watch) = tp.task.watch.clone() {
+                    watch::register(tf.id, watch, tp.task.dir.clone());
+                }
+
                 let id = tf.id;
                 tokio::spawn(async move {
                     update(id, Some(pid), Some(status), None, None, None)
@@ -649,56 +1889,6 @@ pub mod global {
                     id
                 )))))
             }
-            TaskType::Periodic(_) => {
-                let id = tf.id;
-                let name = tf.name.clone();
-
-                let mut child = tp.task.start().await?;
-                let pid = child.id();
-                let jh: JoinHandle<Option<i32>> = tokio::spawn(async move {
-                    let res = child.wait().await.unwrap();
-                    let code = res.code();
-                    info!(
-                        "Task [{}:{}] exited with code: {:?}",
-                        id,
-                        name.unwrap_or(String::new()),
-                        code
-                    );
-
-                    let _ = update(
-                        tf.id,
-                        Some(None),
-                        Some(Some("interval".to_string())),
-                        Some(res.code()),
-                        Some(false),
-                        Some(vec!["executing"]),
-                    )
-                    .await;
-
-                    cache().await.unwrap();
-
-                    return res.code();
-                });
-
-                tp.joinhandle = Some(jh);
-
-                tokio::spawn(async move {
-                    update(
-                        id,
-                        Some(pid),
-                        Some(Some("executing".to_string())),
-                        None,
-                        None,
-                        None,
-                    )
-                    .await
-                    .unwrap();
-                });
-                Ok(Response::success(Some(Data::String(format!(
-                    "Task [{}] started",
-                    id
-                )))))
-            }
             _ => Err(Box::new(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "Task type not supported",
@@ -711,18 +1901,51 @@ pub mod global {
         start(tf).await
     }
 
+    /// Restart async task because watched files changed, counted apart from crash restarts
+    ///
+    /// # params
+    ///
+    /// - `id`: task id
+    /// - `changed`: changed file that triggered the restart
+    pub async fn watch_restart(id: i64, changed: String) -> Result<Response, Box<dyn Error>> {
+        let tf = TaskFlag {
+            id,
+            name: None,
+            group: None,
+            mat: false,
+            selector: None,
+        };
+        stop(tf.clone(), false).await?;
+        let mut tasks = TASKS.write().await;
+        if let Some(tp) = tasks.get_mut(&id) {
+            if let TaskType::Async(tt) = &mut tp.task.task_type {
+                tt.watch_restart += 1;
+                tt.last_change = Some(changed.clone());
+            }
+        }
+        drop(tasks);
+        let reason = format!("file changed: {}", changed);
+        start_with(tf, Trigger::Restart, Some(reason)).await
+    }
+
     pub async fn run(task: Task) -> Result<Response, Box<dyn Error>> {
-        let id = task.id;
         let typ = task.task_type.clone();
-        let res = add(task).await?;
+        let (res, id) = insert(task).await?;
+        if res.code != 10000 {
+            return Ok(res);
+        }
         match typ {
             TaskType::Async(_) | TaskType::Periodic(_) => {
-                start(TaskFlag {
-                    id,
-                    name: None,
-                    group: None,
-                    mat: false,
-                })
+                each(
+                    TaskFlag {
+                        id,
+                        name: None,
+                        group: None,
+                        mat: false,
+                        selector: None,
+                    },
+                    start,
+                )
                 .await
             }
             _ => return Ok(res),
@@ -762,9 +1985,13 @@ pub mod global {
             child.wait().await?;
             tp.task.status = Some("stopped".to_string());
             tp.task.code = Some(9);
-            tp.joinhandle = None;
+            let jh = tp.joinhandle.take();
             tp.tx = None;
             drop(tasks);
+            // 等待监控协程处理完进程退出，避免覆盖之后启动的进程状态
+            if let Some(jh) = jh {
+                let _ = tokio::time::timeout(Duration::from_secs(5), jh).await;
+            }
             if to_cache {
                 cache().await?;
             }
@@ -806,53 +2033,86 @@ pub mod global {
     pub async fn list(condition: Option<TaskFlag>) -> Result<Response, Box<dyn Error>> {
         let tasks = TASKS.write().await;
 
-        let res = match condition {
-            Some(condition) => {
-                let mut status: Vec<Status> = Vec::new();
-                if condition.id > 0 {
-                    if tasks.contains_key(&condition.id) {
-                        let tp = tasks.get(&condition.id).unwrap();
-                        status.push(tp.task.clone().into());
-                    }
-                } else if condition.mat {
-                    let name = condition.name.unwrap_or(String::new());
-                    for (_id, tp) in tasks.iter() {
-                        let regex: Regex = Regex::new(&name)?;
-                        if regex.is_match(&tp.task.name) {
-                            status.push(tp.task.clone().into());
-                        }
-                    }
-                } else if condition.group.is_some() {
-                    let group = condition.group.unwrap_or(String::new());
-                    for (_id, tp) in tasks.iter() {
-                        let regex: Regex = Regex::new(&group)?;
-                        if tp.task.group.is_some()
-                            && regex.is_match(&tp.task.group.clone().unwrap())
-                        {
-                            status.push(tp.task.clone().into());
-                        }
-                    }
-                } else {
-                    let name = condition.name.unwrap_or(String::new());
-                    for (_id, tp) in tasks.iter() {
-                        if tp.task.name == name {
-                            status.push(tp.task.clone().into());
-                        }
-                    }
-                }
-                status
-            }
-            None => {
-                let mut status: Vec<Status> = Vec::new();
-                for (_, tp) in tasks.iter() {
-                    status.push(tp.task.clone().into());
+        let mut res: Vec<Status> = match condition {
+            Some(condition) => resolve(&tasks, &condition)?
+                .iter()
+                .filter_map(|id| tasks.get(id))
+                .map(|tp| tp.task.clone().into())
+                .collect(),
+            None => tasks.values().map(|tp| tp.task.clone().into()).collect(),
+        };
+        // 多实例任务同时列出其实例
+        let parents: Vec<i64> = res
+            .iter()
+            .filter(|s| s.instances.is_some())
+            .map(|s| s.id)
+            .collect();
+        for tp in tasks.values() {
+            if let Some(parent) = tp.task.parent {
+                if parents.contains(&parent) && !res.iter().any(|s| s.id == tp.task.id) {
+                    res.push(tp.task.clone().into());
                 }
-                status
             }
-        };
+        }
         Ok(Response::success(Some(Data::Status(res))))
     }
 
+    /// Definitions of tasks, instances are left out as they are created from their
+    /// multi-instance task
+    ///
+    /// # params
+    ///
+    /// - `condition`: task flag, all tasks if none
+    pub async fn export(condition: Option<TaskFlag>) -> Result<Response, Box<dyn Error>> {
+        let tasks = TASKS.read().await;
+        let ids = match condition {
+            Some(condition) => resolve(&tasks, &condition)?,
+            None => tasks.keys().copied().collect(),
+        };
+        let mut res: Vec<Task> = ids
+            .iter()
+            .filter_map(|id| tasks.get(id))
+            .filter(|tp| tp.task.parent.is_none())
+            .map(|tp| tp.task.clone())
+            .collect();
+        res.sort_by_key(|t| t.id);
+        Ok(Response::success(Some(Data::Tasks(res))))
+    }
+
+    /// Run history of task, newest first
+    ///
+    /// # params
+    ///
+    /// - `tf`: task flag, runs of removed tasks are found by name
+    /// - `page`: page number, starts from 1
+    /// - `size`: number of runs per page
+    pub async fn history(tf: TaskFlag, page: u64, size: u64) -> Result<Response, Box<dyn Error>> {
+        if !database::enabled() {
+            return Ok(Response::wrong(
+                "Run history requires sqlite backend".to_string(),
+            ));
+        }
+        // 现有任务按 id 和名称匹配，已删除的任务按名称或 id 匹配
+        let tasks = TASKS.read().await;
+        let (id, name) = if tf.id > 0 {
+            (tf.id, tasks.get(&tf.id).map(|tp| tp.task.name.clone()))
+        } else {
+            let name = tf.name.clone().unwrap_or_default();
+            let id = tasks
+                .values()
+                .find(|tp| tp.task.name == name)
+                .map(|tp| tp.task.id)
+                .unwrap_or(0);
+            (id, Some(name))
+        };
+        drop(tasks);
+        let (total, runs) = tokio::task::spawn_blocking(move || {
+            database::runs(id, name.as_deref(), page.max(1), size.max(1)).map_err(|e| e.to_string())
+        })
+        .await??;
+        Ok(Response::success(Some(Data::History(total, runs))))
+    }
+
     pub async fn pause(tf: TaskFlag) -> Result<Response, Box<dyn Error>> {
         let mut tasks = TASKS.write().await;
         if !tasks.contains_key(&tf.id) {
@@ -906,4 +2166,110 @@ pub mod global {
             tf.id
         )))))
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_instance_task() {
+            let parent = Task {
+                id: 7,
+                name: "web".to_string(),
+                command: "true".to_string(),
+                instances: Some(2),
+                pid: Some(42),
+                pid_start_time: Some(100),
+                run_id: Some("run".to_string()),
+                runs: vec![LiveRun {
+                    run_id: "run".to_string(),
+                    pid: Some(42),
+                    pid_start_time: Some(100),
+                    started_at: 0,
+                }],
+                queued: 3,
+                code: Some(1),
+                signal: Some(9),
+                timed_out: Some(1000),
+                hash: Some("hash".to_string()),
+                ..Task::default()
+            };
+            let task = instance_task(&parent, 1, 9);
+            assert_eq!((task.id, task.name.as_str()), (9, "web-1"));
+            assert_eq!(
+                (task.parent, task.instance, task.instances),
+                (Some(7), Some(1), None)
+            );
+            // 运行时字段不从所属任务复制
+            assert!(task.pid.is_none() && task.pid_start_time.is_none() && task.run_id.is_none());
+            assert!(task.runs.is_empty() && task.queued == 0);
+            assert!(task.code.is_none() && task.signal.is_none() && task.timed_out.is_none());
+            assert!(task.hash.is_none());
+            assert_eq!(task.status.as_deref(), Some("added"));
+        }
+
+        #[test]
+        fn test_idle_status() {
+            let task = |status: &str, task_type: TaskType| Task {
+                status: Some(status.to_string()),
+                task_type,
+                ..Task::default()
+            };
+            let periodic = TaskType::Periodic(PeriodicTask {
+                started_after: 0,
+                interval: 60,
+                last_run: 0,
+                sync: false,
+                jitter: 0,
+                align: false,
+                next_run: 0,
+                slot: 0,
+            });
+            let scheduled = TaskType::Scheduled(crate::common::task::ScheduledTask {
+                year: None,
+                month: None,
+                day: None,
+                hour: Some(3),
+                minute: Some(0),
+                second: Some(0),
+            });
+            let once = TaskType::Once(OnceTask {
+                at: None,
+                after: None,
+                run_at: 0,
+            });
+            // 重试用尽后周期任务和定时任务仍等待下次运行
+            assert_eq!(
+                idle_status(&task("executing", periodic.clone()), true),
+                Some("interval")
+            );
+            assert_eq!(
+                idle_status(&task("executing", periodic), false),
+                Some("interval")
+            );
+            assert_eq!(
+                idle_status(&task("processing", scheduled.clone()), true),
+                Some("waiting")
+            );
+            assert_eq!(
+                idle_status(&task("processing", scheduled), false),
+                Some("waiting")
+            );
+            // 一次性任务重试用尽后失败，等待重试时保持等待
+            assert_eq!(
+                idle_status(&task("processing", once.clone()), true),
+                Some("failed")
+            );
+            assert_eq!(
+                idle_status(&task("processing", once.clone()), false),
+                Some("completed")
+            );
+            let retrying = Task {
+                retry_at: Some(1),
+                ..task("processing", once.clone())
+            };
+            assert_eq!(idle_status(&retrying, false), Some("waiting"));
+            assert_eq!(idle_status(&task("stopped", once), false), None);
+        }
+    }
 }
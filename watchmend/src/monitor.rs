@@ -1,16 +1,144 @@
-use crate::common::task::TaskFlag;
+use crate::common::task::{PeriodicTask, TaskFlag, Trigger};
 use chrono::Datelike;
 use chrono::Timelike;
+use rand::Rng;
 use std::time::{Duration, SystemTime};
 use tokio::time;
 use tracing::{error, info};
 
-use crate::global::{get_all, start};
+use crate::global::{dequeue, expire, get_all, plan, start_with, take_retry};
+
+/// Nominal time of next run of periodic task before jitter, slots missed while daemon
+/// was down or the run was late are skipped
+///
+/// # params
+///
+/// - `tt`: periodic task
+/// - `now`: current time, u64: second
+pub fn next_slot(tt: &PeriodicTask, now: u64) -> u64 {
+    let interval = tt.interval.max(1);
+    if tt.align {
+        // 对齐到本地时间的 interval 整数倍
+        let after = match tt.last_run {
+            0 => now,
+            last_run => (last_run + 1).max(now),
+        }
+        .max(tt.started_after) as i64;
+        let offset = chrono::Local::now().offset().local_minus_utc() as i64;
+        let interval = interval as i64;
+        ((after + offset + interval - 1).div_euclid(interval) * interval - offset) as u64
+    } else {
+        match tt.last_run {
+            0 => now,
+            last_run => {
+                let next = last_run + interval;
+                if next < now {
+                    next + (now - next).div_ceil(interval) * interval
+                } else {
+                    next
+                }
+            }
+        }
+        .max(tt.started_after)
+    }
+}
+
+/// Schedule next run of periodic task, a random delay up to jitter is added to the slot
+///
+/// # params
+///
+/// - `tt`: periodic task
+/// - `now`: current time, u64: second
+pub fn schedule(tt: &mut PeriodicTask, now: u64) {
+    tt.slot = next_slot(tt, now);
+    tt.next_run = if tt.jitter > 0 {
+        tt.slot + rand::thread_rng().gen_range(0..=tt.jitter)
+    } else {
+        tt.slot
+    };
+}
+
+/// Advance periodic task past a run started or skipped at now. The nominal slot of the run
+/// is recorded as last run, so jitter and delay of monitor do not accumulate; a manual run
+/// restarts the schedule from now, and a queued or retried run leaves it unchanged
+///
+/// # params
+///
+/// - `tt`: periodic task
+/// - `trigger`: what started the run
+/// - `now`: current time, u64: second
+pub fn advance(tt: &mut PeriodicTask, trigger: Trigger, now: u64) {
+    tt.last_run = match trigger {
+        Trigger::Manual => now,
+        _ if tt.next_run > now => return,
+        _ if tt.slot > 0 && tt.slot <= now => tt.slot,
+        _ => now,
+    };
+    schedule(tt, now);
+}
 
 pub async fn rerun_tasks(delay: u64) -> Result<(), Box<dyn std::error::Error>> {
     let tasks = get_all().await?;
     let now = chrono::Local::now();
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("Failed to get timestamp")
+        .as_millis() as u64;
     for (id, task) in tasks {
+        // 结束的任务保留 ttl 后自动删除
+        if let Some(ttl) = task.ttl_after_finished {
+            if expire(id, ttl, millis / 1000).await?.is_some() {
+                continue;
+            }
+        }
+        // 失败的运行到期后重试
+        if task.retry_at.is_some() && take_retry(id, millis).await {
+            info!("Retry task: {}", id);
+            let reason = match task.timed_out {
+                Some(duration) => format!(
+                    "attempt {} timed out after {:.1}s",
+                    task.attempt.unwrap_or(1),
+                    duration as f64 / 1000.0
+                ),
+                None => format!(
+                    "attempt {} exited with code {}",
+                    task.attempt.unwrap_or(1),
+                    task.code.unwrap_or(0)
+                ),
+            };
+            start_with(
+                TaskFlag {
+                    id,
+                    name: None,
+                    group: None,
+                    mat: false,
+                    selector: None,
+                },
+                Trigger::Retry,
+                Some(reason),
+            )
+            .await?;
+        }
+        // 排队的运行在运行中的实例退出后启动
+        if task.queued > 0 && dequeue(id).await {
+            let trigger = match task.task_type {
+                crate::common::task::TaskType::Scheduled(_) => Trigger::Schedule,
+                _ => Trigger::Periodic,
+            };
+            info!("Execute queued run of task: {}", id);
+            start_with(
+                TaskFlag {
+                    id,
+                    name: None,
+                    group: None,
+                    mat: false,
+                    selector: None,
+                },
+                trigger,
+                Some("queued".to_string()),
+            )
+            .await?;
+        }
         match task.task_type {
             crate::common::task::TaskType::Scheduled(scheduled) => {
                 let nd = chrono::NaiveDate::from_ymd_opt(
@@ -37,12 +165,17 @@ pub async fn rerun_tasks(delay: u64) -> Result<(), Box<dyn std::error::Error>> {
                                         let mut interval =
                                             time::interval(Duration::from_secs(diff));
                                         interval.tick().await;
-                                        let _ = start(TaskFlag {
-                                            id,
-                                            name: None,
-                                            group: None,
-                                            mat: false,
-                                        })
+                                        let _ = start_with(
+                                            TaskFlag {
+                                                id,
+                                                name: None,
+                                                group: None,
+                                                mat: false,
+                                                selector: None,
+                                            },
+                                            Trigger::Schedule,
+                                            None,
+                                        )
                                         .await;
                                     });
                                 }
@@ -58,12 +191,21 @@ pub async fn rerun_tasks(delay: u64) -> Result<(), Box<dyn std::error::Error>> {
                 if let Some(status) = task.status {
                     if status == "auto restart" {
                         info!("Restart task: {}", id);
-                        start(TaskFlag {
-                            id,
-                            name: None,
-                            group: None,
-                            mat: false,
-                        })
+                        let reason = match task.code {
+                            Some(code) => format!("exited with code {}", code),
+                            None => "exited by signal".to_string(),
+                        };
+                        start_with(
+                            TaskFlag {
+                                id,
+                                name: None,
+                                group: None,
+                                mat: false,
+                                selector: None,
+                            },
+                            Trigger::Restart,
+                            Some(reason),
+                        )
                         .await?;
                     }
                 }
@@ -73,34 +215,59 @@ pub async fn rerun_tasks(delay: u64) -> Result<(), Box<dyn std::error::Error>> {
                     .duration_since(SystemTime::UNIX_EPOCH)
                     .expect("Failed to get timestamp")
                     .as_secs();
-                if now >= tt.started_after && now - tt.last_run >= tt.interval {
+                let due = match tt.next_run {
+                    0 => match plan(id, now).await {
+                        Some(next_run) => next_run,
+                        None => continue,
+                    },
+                    next_run => next_run,
+                };
+                if now >= due {
                     if let Some(status) = task.status {
-                        if tt.sync {
-                            if status == "interval" || status == "executing" {
-                                info!("Execute periodic task: {}", id);
-                                start(TaskFlag {
+                        // 上次运行未结束时由并发策略决定是否启动
+                        if status == "interval" || status == "executing" {
+                            info!("Execute periodic task: {}", id);
+                            start_with(
+                                TaskFlag {
                                     id,
                                     name: None,
                                     group: None,
                                     mat: false,
-                                })
-                                .await?;
-                            }
-                        } else {
-                            if status == "interval" {
-                                info!("Execute periodic task: {}", id);
-                                start(TaskFlag {
-                                    id,
-                                    name: None,
-                                    group: None,
-                                    mat: false,
-                                })
-                                .await?;
-                            }
+                                    selector: None,
+                                },
+                                Trigger::Periodic,
+                                None,
+                            )
+                            .await?;
                         }
                     }
                 }
             }
+            crate::common::task::TaskType::Once(tt) => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .expect("Failed to get timestamp")
+                    .as_secs();
+                // 等待重试时由重试启动
+                if task.status.as_deref() == Some("waiting")
+                    && task.retry_at.is_none()
+                    && now >= tt.run_at
+                {
+                    info!("Execute once task: {}", id);
+                    start_with(
+                        TaskFlag {
+                            id,
+                            name: None,
+                            group: None,
+                            mat: false,
+                            selector: None,
+                        },
+                        Trigger::Schedule,
+                        None,
+                    )
+                    .await?;
+                }
+            }
             crate::common::task::TaskType::None => {}
         }
     }
@@ -120,3 +287,107 @@ pub async fn run_monitor(delay: Option<u64>) -> Result<(), Box<dyn std::error::E
         interval.tick().await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn periodic(interval: u64, last_run: u64) -> PeriodicTask {
+        PeriodicTask {
+            started_after: 0,
+            interval,
+            last_run,
+            sync: false,
+            jitter: 0,
+            align: false,
+            next_run: 0,
+            slot: 0,
+        }
+    }
+
+    #[test]
+    fn test_next_slot() {
+        let now = 1_700_000_000;
+        assert_eq!(next_slot(&periodic(60, 0), now), now);
+        assert_eq!(next_slot(&periodic(60, now - 10), now), now + 50);
+        // 错过的运行被跳过，保持原有间隔
+        assert_eq!(next_slot(&periodic(60, now - 130), now), now + 50);
+        assert_eq!(next_slot(&periodic(60, now - 120), now), now);
+        let tt = PeriodicTask {
+            started_after: now + 3600,
+            ..periodic(60, 0)
+        };
+        assert_eq!(next_slot(&tt, now), now + 3600);
+    }
+
+    #[test]
+    fn test_next_slot_align() {
+        // 按本地时间对齐
+        let offset = chrono::Local::now().offset().local_minus_utc() as i64;
+        let aligned = |t: u64| (t as i64 + offset).rem_euclid(900) == 0;
+        let tt = PeriodicTask {
+            align: true,
+            ..periodic(900, 0)
+        };
+        let now = 1_700_000_123;
+        let next = next_slot(&tt, now);
+        assert!(aligned(next) && next >= now && next - now < 900);
+        // 运行在边界上时下次运行是下一个边界
+        let tt = PeriodicTask {
+            last_run: next,
+            ..tt
+        };
+        assert_eq!(next_slot(&tt, next), next + 900);
+        assert_eq!(next_slot(&tt, next + 2000), next + 2700);
+        let tt = PeriodicTask {
+            last_run: 0,
+            started_after: next + 1,
+            ..tt
+        };
+        assert_eq!(next_slot(&tt, now), next + 900);
+    }
+
+    #[test]
+    fn test_schedule_jitter() {
+        let now = 1_700_000_000;
+        let runs: Vec<u64> = (0..200)
+            .map(|_| {
+                let mut tt = PeriodicTask {
+                    jitter: 30,
+                    ..periodic(60, now)
+                };
+                schedule(&mut tt, now);
+                assert_eq!(tt.slot, now + 60);
+                tt.next_run
+            })
+            .collect();
+        assert!(runs.iter().all(|r| (now + 60..=now + 90).contains(r)));
+        assert!(runs.iter().any(|r| *r != runs[0]));
+    }
+
+    #[test]
+    fn test_advance_no_drift() {
+        let now = 1_700_000_000;
+        let mut tt = PeriodicTask {
+            jitter: 30,
+            ..periodic(60, 0)
+        };
+        schedule(&mut tt, now);
+        assert_eq!(tt.slot, now);
+        // 每次运行都在抖动后再晚监控的检查间隔才启动，下次运行仍按原定时间点计算
+        for cycle in 1..=20 {
+            let start = tt.next_run + 4;
+            advance(&mut tt, Trigger::Periodic, start);
+            assert_eq!(tt.last_run, now + (cycle - 1) * 60);
+            assert_eq!(tt.slot, now + cycle * 60);
+            assert!((tt.slot..=tt.slot + 30).contains(&tt.next_run));
+        }
+
+        // 排队或重试的运行不改变计划，手动运行从当前时间重新计算
+        let planned = (tt.slot, tt.next_run);
+        advance(&mut tt, Trigger::Retry, planned.1 - 1);
+        assert_eq!((tt.slot, tt.next_run), planned);
+        advance(&mut tt, Trigger::Manual, now + 1250);
+        assert_eq!(tt.slot, now + 1310);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, error::Error};
+
+/// Requirement on labels of task
+#[derive(Debug, Clone, PartialEq)]
+enum Requirement {
+    /// `key=value` or `key==value`
+    Equals(String, String),
+    /// `key!=value`, also matches tasks without the label
+    NotEquals(String, String),
+    /// `key in (a,b)`
+    In(String, Vec<String>),
+    /// `key notin (a,b)`, also matches tasks without the label
+    NotIn(String, Vec<String>),
+    /// `key`
+    Exists(String),
+    /// `!key`
+    NotExists(String),
+}
+
+/// Label selector, requirements are separated by comma and all must match,
+/// e.g. `env=prod,tier in (api,worker),!canary`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    requirements: Vec<Requirement>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Selector, Box<dyn Error>> {
+        let mut requirements = Vec::new();
+        for part in split(selector)? {
+            requirements.push(requirement(&part)?);
+        }
+        Ok(Selector { requirements })
+    }
+
+    /// Whether labels satisfy all requirements
+    ///
+    /// # params
+    ///
+    /// - `labels`: labels of task
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements.iter().all(|r| match r {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::In(key, values) => labels.get(key).is_some_and(|v| values.contains(v)),
+            Requirement::NotIn(key, values) => !labels.get(key).is_some_and(|v| values.contains(v)),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::NotExists(key) => !labels.contains_key(key),
+        })
+    }
+}
+
+/// Split selector by comma outside parentheses
+fn split(selector: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut depth = 0;
+    for c in selector.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(format!("Invalid selector: {}", selector).into()),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(part.trim().to_string());
+                part.clear();
+                continue;
+            }
+            _ => {}
+        }
+        part.push(c);
+    }
+    if depth != 0 {
+        return Err(format!("Invalid selector: {}", selector).into());
+    }
+    parts.push(part.trim().to_string());
+    if parts.iter().any(|p| p.is_empty()) {
+        return Err(format!("Invalid selector: {}", selector).into());
+    }
+    Ok(parts)
+}
+
+fn requirement(part: &str) -> Result<Requirement, Box<dyn Error>> {
+    let invalid = || -> Box<dyn Error> { format!("Invalid selector requirement: {}", part).into() };
+    let key = |key: &str| -> Result<String, Box<dyn Error>> {
+        let key = key.trim();
+        if key.is_empty() || key.contains(|c: char| c.is_whitespace() || "!=()".contains(c)) {
+            Err(invalid())
+        } else {
+            Ok(key.to_string())
+        }
+    };
+
+    if let Some(rest) = part.strip_prefix('!') {
+        if !rest.contains(['=', '(']) {
+            return Ok(Requirement::NotExists(key(rest)?));
+        }
+    }
+    if let Some((k, v)) = part.split_once("!=") {
+        return Ok(Requirement::NotEquals(key(k)?, v.trim().to_string()));
+    }
+    if let Some((k, v)) = part.split_once("==") {
+        return Ok(Requirement::Equals(key(k)?, v.trim().to_string()));
+    }
+    if let Some((k, v)) = part.split_once('=') {
+        return Ok(Requirement::Equals(key(k)?, v.trim().to_string()));
+    }
+    if let Some(open) = part.find('(') {
+        let values = part[open..]
+            .strip_prefix('(')
+            .and_then(|v| v.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+        if values.contains(['(', ')']) {
+            return Err(invalid());
+        }
+        let values: Vec<String> = values
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect();
+        if values.is_empty() {
+            return Err(invalid());
+        }
+        let mut words = part[..open].split_whitespace();
+        let (k, op) = match (words.next(), words.next(), words.next()) {
+            (Some(k), Some(op), None) => (k, op),
+            _ => return Err(invalid()),
+        };
+        return match op {
+            "in" => Ok(Requirement::In(key(k)?, values)),
+            "notin" => Ok(Requirement::NotIn(key(k)?, values)),
+            _ => Err(invalid()),
+        };
+    }
+    Ok(Requirement::Exists(key(part)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn requirements(selector: &str) -> Vec<Requirement> {
+        Selector::parse(selector).unwrap().requirements
+    }
+
+    #[test]
+    fn test_parse() {
+        use Requirement::*;
+        let s = |v: &str| v.to_string();
+        assert_eq!(
+            requirements("env=prod, tier == api ,zone!=a"),
+            vec![
+                Equals(s("env"), s("prod")),
+                Equals(s("tier"), s("api")),
+                NotEquals(s("zone"), s("a")),
+            ]
+        );
+        assert_eq!(
+            requirements("tier in (api, worker),zone notin (a),canary,!debug"),
+            vec![
+                In(s("tier"), vec![s("api"), s("worker")]),
+                NotIn(s("zone"), vec![s("a")]),
+                Exists(s("canary")),
+                NotExists(s("debug")),
+            ]
+        );
+        // !key 与 key!=value 不同
+        assert_eq!(requirements("!a"), vec![NotExists(s("a"))]);
+        assert_eq!(requirements("a!=b"), vec![NotEquals(s("a"), s("b"))]);
+        assert_eq!(requirements("a="), vec![Equals(s("a"), s(""))]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for selector in [
+            "",
+            "a=b,",
+            ",a=b",
+            "a=b,,c",
+            "!",
+            "!a=b",
+            "!a in (x)",
+            "=b",
+            "a b",
+            "a in (x",
+            "a in x)",
+            "a in ((x))",
+            "a in ()",
+            "a is (x)",
+            "in (x)",
+            "a in (x) b",
+        ] {
+            assert!(Selector::parse(selector).is_err(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let task = labels(&[("env", "prod"), ("tier", "api")]);
+        let matches = |selector: &str| Selector::parse(selector).unwrap().matches(&task);
+        assert!(matches("env=prod"));
+        assert!(matches("env=prod,tier in (api,worker)"));
+        assert!(!matches("env=prod,tier in (worker)"));
+        assert!(!matches("env!=prod"));
+        // 没有标签的任务满足 != 和 notin
+        assert!(matches("zone!=a"));
+        assert!(matches("zone notin (a)"));
+        assert!(!matches("tier notin (api)"));
+        assert!(matches("env"));
+        assert!(!matches("zone"));
+        assert!(matches("!zone"));
+        assert!(!matches("!env"));
+        assert!(!Selector::parse("env").unwrap().matches(&labels(&[])));
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{error, warn};
+
+use crate::common::task::Task;
+
+/// Current version of cache file schema
+///
+/// - `1`: task list, `[Task]`
+/// - `2`: `{ "version": 2, "tasks": [Task] }`
+pub const VERSION: u64 = 2;
+
+/// Default number of snapshots kept beside cache file
+pub const SNAPSHOTS: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct State {
+    pub version: u64,
+    pub tasks: Vec<Task>,
+}
+
+/// Path of the n-th snapshot of cache file, e.g. cache.json.1
+fn snapshot_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Migrate cache file content of any older version to current version
+///
+/// # params
+///
+/// - `value`: parsed cache file content
+pub fn migrate(mut value: Value) -> Result<State, Box<dyn Error>> {
+    let mut version = match &value {
+        Value::Array(_) => 1,
+        Value::Object(map) => match map.get("version").and_then(|v| v.as_u64()) {
+            Some(version) => version,
+            None => return Err("Cache version is missing".into()),
+        },
+        _ => return Err("Cache format is invalid".into()),
+    };
+    if version > VERSION {
+        return Err(format!(
+            "Cache version {} is newer than supported version {}",
+            version, VERSION
+        )
+        .into());
+    }
+    while version < VERSION {
+        value = match version {
+            // 1 -> 2: 任务列表放入 tasks 字段
+            1 => json!({ "version": 2, "tasks": value }),
+            _ => return Err(format!("Cache version {} cannot be migrated", version).into()),
+        };
+        version += 1;
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Parse cache file
+pub fn parse(path: &Path) -> Result<Vec<Task>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&content)?;
+    Ok(migrate(value)?.tasks)
+}
+
+/// Load tasks from cache file, fall back to the newest snapshot that can be parsed
+///
+/// # params
+///
+/// - `path`: cache file path
+/// - `snapshots`: number of snapshots kept beside cache file
+pub fn load(path: &Path, snapshots: usize) -> Result<Vec<Task>, Box<dyn Error>> {
+    let mut candidates = vec![path.to_path_buf()];
+    for n in 1..=snapshots {
+        candidates.push(snapshot_path(path, n));
+    }
+    if !candidates.iter().any(|p| p.is_file()) {
+        return Err(format!("Cache file [{}] is not valid", path.display()).into());
+    }
+    let mut errors = Vec::new();
+    for candidate in candidates.iter().filter(|p| p.is_file()) {
+        match parse(candidate) {
+            Ok(tasks) => {
+                if candidate != path {
+                    warn!(
+                        "Cache file [{}] is broken, recovered from snapshot [{}]",
+                        path.display(),
+                        candidate.display()
+                    );
+                    // 保留损坏的缓存文件用于排查
+                    if path.is_file() {
+                        let mut broken = path.as_os_str().to_os_string();
+                        broken.push(".broken");
+                        let _ = fs::rename(path, broken);
+                    }
+                }
+                return Ok(tasks);
+            }
+            Err(e) => {
+                error!("Cache file [{}] parse failed: {}", candidate.display(), e);
+                errors.push(format!("{}: {}", candidate.display(), e));
+            }
+        }
+    }
+    Err(format!("Cache file and snapshots are broken, {}", errors.join("; ")).into())
+}
+
+/// Save tasks to cache file atomically, the previous cache files are kept as snapshots
+///
+/// The content is written to a temporary file and synced before it is renamed to
+/// the cache file, so the cache file is either the old one or the new one.
+///
+/// # params
+///
+/// - `path`: cache file path
+/// - `tasks`: tasks to save
+/// - `snapshots`: number of snapshots kept beside cache file
+pub fn save(path: &Path, tasks: Vec<Task>, snapshots: usize) -> Result<(), Box<dyn Error>> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if !parent.exists() {
+        fs::create_dir_all(&parent)?;
+    }
+    let state = State {
+        version: VERSION,
+        tasks,
+    };
+    let content = serde_json::to_vec(&state)?;
+
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = File::create(&tmp)?;
+    file.write_all(&content)?;
+    file.sync_all()?;
+    drop(file);
+
+    // 轮换快照: cache.json.(n-1) -> cache.json.n, ..., cache.json -> cache.json.1
+    // 缓存文件以硬链接保存为快照，替换前缓存文件一直存在
+    if snapshots > 0 && path.is_file() {
+        for n in (1..snapshots).rev() {
+            let from = snapshot_path(path, n);
+            if from.is_file() {
+                fs::rename(&from, snapshot_path(path, n + 1))?;
+            }
+        }
+        let first = snapshot_path(path, 1);
+        if first.is_file() {
+            fs::remove_file(&first)?;
+        }
+        if fs::hard_link(path, &first).is_err() {
+            fs::copy(path, &first)?;
+        }
+    }
+    fs::rename(&tmp, path)?;
+
+    // 同步目录，确保重命名落盘
+    File::open(&parent)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i64, name: &str) -> Task {
+        Task {
+            id,
+            name: name.to_string(),
+            command: "true".to_string(),
+            ..Task::default()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("watchmen-state-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_migrate() {
+        let v1 = serde_json::to_value(vec![task(1, "a")]).unwrap();
+        let state = migrate(v1).unwrap();
+        assert_eq!(state.version, VERSION);
+        assert_eq!(names(&state.tasks), vec!["a"]);
+
+        let v2 = json!({ "version": 2, "tasks": [task(2, "b")] });
+        assert_eq!(names(&migrate(v2).unwrap().tasks), vec!["b"]);
+
+        assert!(migrate(json!({ "tasks": [] })).is_err());
+        assert!(migrate(json!({ "version": VERSION + 1, "tasks": [] })).is_err());
+        assert!(migrate(json!("tasks")).is_err());
+    }
+
+    #[test]
+    fn test_save_snapshots() {
+        let dir = temp_dir("save");
+        let path = dir.join("cache.json");
+        for name in ["a", "b", "c", "d"] {
+            save(&path, vec![task(1, name)], 2).unwrap();
+            // 保存后缓存文件总是存在
+            assert!(path.is_file());
+        }
+        assert_eq!(names(&parse(&path).unwrap()), vec!["d"]);
+        assert_eq!(names(&parse(&snapshot_path(&path, 1)).unwrap()), vec!["c"]);
+        assert_eq!(names(&parse(&snapshot_path(&path, 2)).unwrap()), vec!["b"]);
+        assert!(!snapshot_path(&path, 3).exists());
+        assert!(!dir.join("cache.json.tmp").exists());
+
+        // 没有快照时只保存缓存文件
+        save(&path, vec![task(1, "e")], 0).unwrap();
+        assert_eq!(names(&parse(&snapshot_path(&path, 1)).unwrap()), vec!["c"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_fallback() {
+        let dir = temp_dir("load");
+        let path = dir.join("cache.json");
+        assert!(load(&path, 3).is_err());
+
+        save(&path, vec![task(1, "a")], 3).unwrap();
+        save(&path, vec![task(1, "b")], 3).unwrap();
+        assert_eq!(names(&load(&path, 3).unwrap()), vec!["b"]);
+
+        // 损坏的缓存文件从最新的快照恢复，并保留损坏的文件
+        fs::write(&path, "{\"version\": 2, \"tasks\": [").unwrap();
+        assert_eq!(names(&load(&path, 3).unwrap()), vec!["a"]);
+        assert!(dir.join("cache.json.broken").is_file());
+
+        // 损坏的快照被跳过
+        fs::remove_file(dir.join("cache.json.broken")).unwrap();
+        save(&path, vec![task(1, "c")], 3).unwrap();
+        save(&path, vec![task(1, "d")], 3).unwrap();
+        fs::write(&path, "broken").unwrap();
+        fs::write(snapshot_path(&path, 1), "broken").unwrap();
+        assert_eq!(names(&load(&path, 3).unwrap()), vec!["a"]);
+
+        for n in 1..=3 {
+            let _ = fs::write(snapshot_path(&path, n), "broken");
+        }
+        fs::write(&path, "broken").unwrap();
+        assert!(load(&path, 3).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
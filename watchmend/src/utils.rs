@@ -17,3 +17,73 @@ pub async fn get_millis() -> u128 {
         .unwrap()
         .as_millis()
 }
+
+/// Wait for a process which is not a child of daemon to exit
+///
+/// Uses pidfd when supported by kernel, otherwise polls the process every second.
+///
+/// # params
+///
+/// - `pid`: process id
+/// - `start_time`: start time of process, see `process::start_time`
+pub async fn wait_exit(pid: u32, start_time: u64) {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tokio::io::{unix::AsyncFd, Interest};
+
+    use crate::common::process;
+
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd >= 0 {
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        // 打开 pidfd 前进程可能已退出且 pid 被复用
+        if process::start_time(pid) != Some(start_time) {
+            return;
+        }
+        if let Ok(fd) = AsyncFd::with_interest(fd, Interest::READABLE) {
+            let _ = fd.readable().await;
+            return;
+        }
+    }
+    while process::start_time(pid) == Some(start_time) {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+/// Wait for child process to exit within timeout, the process is sent `signal` on
+/// timeout and killed if it is still alive after `kill_timeout`
+///
+/// # params
+///
+/// - `child`: child process
+/// - `timeout`: timeout of process, second, none to wait forever
+/// - `signal`: signal sent on timeout
+/// - `kill_timeout`: time to wait for process to exit after signal, second
+///
+/// # return
+///
+/// - exit status of process and whether the process timed out
+pub async fn wait_timeout(
+    child: &mut tokio::process::Child,
+    timeout: Option<u64>,
+    signal: libc::c_int,
+    kill_timeout: u64,
+) -> (std::io::Result<std::process::ExitStatus>, bool) {
+    use std::time::Duration;
+
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return (child.wait().await, false),
+    };
+    if let Ok(res) = tokio::time::timeout(Duration::from_secs(timeout), child.wait()).await {
+        return (res, false);
+    }
+    if let Some(pid) = child.id() {
+        unsafe { libc::kill(pid as libc::pid_t, signal) };
+    }
+    // 发送信号后仍未退出时强制结束
+    if let Ok(res) = tokio::time::timeout(Duration::from_secs(kill_timeout), child.wait()).await {
+        return (res, true);
+    }
+    let _ = child.start_kill();
+    (child.wait().await, true)
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::StreamExt;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask, Watches};
+use lazy_static::lazy_static;
+use tokio::{task::JoinHandle, time};
+use tracing::{error, info};
+
+use crate::common::{
+    config::get_with_home,
+    task::{TaskType, Watch},
+};
+use crate::global;
+
+lazy_static! {
+    static ref WATCHERS: Mutex<HashMap<i64, JoinHandle<()>>> = Mutex::new(HashMap::new());
+}
+
+fn mask() -> WatchMask {
+    WatchMask::MODIFY
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::MOVED_TO
+        | WatchMask::MOVED_FROM
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, Box<dyn Error>> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Add inotify watches for path, directories are watched recursively
+///
+/// # params
+///
+/// - `watches`: inotify watches
+/// - `path`: file or directory
+/// - `dirs`: watch descriptor to watched path
+pub fn add_watches(
+    watches: &mut Watches,
+    path: &Path,
+    dirs: &mut HashMap<WatchDescriptor, PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let wd = watches.add(path, mask())?;
+    dirs.insert(wd, path.to_path_buf());
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)?.flatten() {
+            let p = entry.path();
+            if p.is_dir() {
+                add_watches(watches, &p, dirs)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Start watching files of task, do nothing if task is already watched
+///
+/// # params
+///
+/// - `id`: task id
+/// - `watch`: watch config of task
+/// - `dir`: task working directory, relative watch paths are based on it
+pub fn register(id: i64, watch: Watch, dir: Option<String>) {
+    let mut watchers = WATCHERS.lock().unwrap();
+    if let Some(jh) = watchers.get(&id) {
+        if !jh.is_finished() {
+            return;
+        }
+    }
+    let jh = tokio::spawn(async move {
+        if let Err(e) = run(id, watch, dir).await {
+            error!("Task [{}] watch error: {}", id, e);
+        }
+    });
+    watchers.insert(id, jh);
+}
+
+/// Stop watching files of task
+pub fn unregister(id: i64) {
+    let mut watchers = WATCHERS.lock().unwrap();
+    if let Some(jh) = watchers.remove(&id) {
+        jh.abort();
+    }
+}
+
+async fn run(id: i64, watch: Watch, dir: Option<String>) -> Result<(), Box<dyn Error>> {
+    let include = build_globset(&watch.include)?;
+    let exclude = build_globset(&watch.exclude)?;
+    let debounce = Duration::from_millis(watch.debounce.unwrap_or(500));
+    let ignore_starting = watch.ignore_starting.unwrap_or(0);
+
+    let inotify = Inotify::init()?;
+    let mut watches = inotify.watches();
+    let mut dirs: HashMap<WatchDescriptor, PathBuf> = HashMap::new();
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for path in &watch.paths {
+        let mut path = PathBuf::from(get_with_home(path));
+        if path.is_relative() {
+            if let Some(dir) = &dir {
+                path = Path::new(dir).join(path);
+            }
+        }
+        add_watches(&mut watches, &path, &mut dirs)?;
+        info!("Task [{}] watching {}", id, path.display());
+        roots.push(path);
+    }
+
+    let mut stream = inotify.into_event_stream([0; 4096])?;
+    loop {
+        // 等待第一个匹配的变更，之后在防抖时间内合并后续变更
+        let mut changed: Option<String> = None;
+        loop {
+            let event = if changed.is_none() {
+                match stream.next().await {
+                    Some(event) => event?,
+                    None => return Ok(()),
+                }
+            } else {
+                match time::timeout(debounce, stream.next()).await {
+                    Ok(Some(event)) => event?,
+                    Ok(None) => return Ok(()),
+                    Err(_) => break,
+                }
+            };
+            let base = match dirs.get(&event.wd) {
+                Some(base) => base.clone(),
+                None => continue,
+            };
+            let path = match &event.name {
+                Some(name) => base.join(name),
+                None => base.clone(),
+            };
+            // 新建的目录也需要监听
+            if event.mask.contains(EventMask::CREATE | EventMask::ISDIR) {
+                let _ = add_watches(&mut watches, &path, &mut dirs);
+            }
+            let relative = roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .map(|p| p.to_path_buf())
+                .unwrap_or(path.clone());
+            let relative = if relative.as_os_str().is_empty() {
+                path.file_name().map(PathBuf::from).unwrap_or(path.clone())
+            } else {
+                relative
+            };
+            if (!watch.include.is_empty() && !include.is_match(&relative))
+                || exclude.is_match(&relative)
+            {
+                continue;
+            }
+            changed = Some(relative.to_string_lossy().to_string());
+        }
+
+        let changed = changed.unwrap();
+        let tasks = global::get_all().await?;
+        let task = match tasks.get(&id) {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        if task.status != Some("running".to_string()) {
+            info!("Task [{}] file changed: {}, task not running", id, changed);
+            continue;
+        }
+        if let TaskType::Async(tt) = &task.task_type {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Failed to get timestamp")
+                .as_secs();
+            if now < tt.started_at + ignore_starting {
+                info!("Task [{}] file changed: {}, task is starting", id, changed);
+                continue;
+            }
+        }
+        info!("Task [{}] file changed: {}, restarting", id, changed);
+        if let Err(e) = global::watch_restart(id, changed).await {
+            error!("Task [{}] watch restart failed: {}", id, e);
+        }
+    }
+}
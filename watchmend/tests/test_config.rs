@@ -0,0 +1,34 @@
+#[cfg(test)]
+mod tests {
+    use watchmend::common::config::Config;
+
+    #[test]
+    fn test_config_layers() {
+        let path = std::env::temp_dir().join("watchmen-test-config.toml");
+        std::fs::write(
+            &path,
+            "[socket]\nport = 2000\n\n[redis]\nqueue_name = \"tasks\"\n",
+        )
+        .unwrap();
+        std::env::set_var("WATCHMEN_SOCKET_PORT", "2001");
+        std::env::set_var("WATCHMEN_REDIS_PASSWORD", "123");
+        let config = Config::init(Some(path.display().to_string()));
+        std::env::remove_var("WATCHMEN_SOCKET_PORT");
+        std::env::remove_var("WATCHMEN_REDIS_PASSWORD");
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        // 缺少的段使用默认值
+        assert_eq!(config.sock.path, "/tmp/watchmen.sock");
+        assert_eq!(config.sources["sock.path"], "default");
+        assert_eq!(config.redis.queue_name, "tasks");
+        assert_eq!(
+            config.sources["redis.queue_name"],
+            path.display().to_string()
+        );
+        // 环境变量覆盖配置文件，字符串不按 TOML 值解析
+        assert_eq!(config.socket.port, 2001);
+        assert_eq!(config.sources["socket.port"], "env WATCHMEN_SOCKET_PORT");
+        assert_eq!(config.redis.password, "123");
+    }
+}
@@ -0,0 +1,24 @@
+#[cfg(test)]
+mod tests {
+    use watchmend::common::schema;
+
+    #[test]
+    fn test_task_schema() {
+        let schema = format!("{}\n", schema::task().unwrap());
+        assert_eq!(
+            schema,
+            include_str!("../../schema/task.schema.json"),
+            "Task schema is outdated, run `watchmen schema task > schema/task.schema.json`"
+        );
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = format!("{}\n", schema::config().unwrap());
+        assert_eq!(
+            schema,
+            include_str!("../../schema/config.schema.json"),
+            "Config schema is outdated, run `watchmen schema config > schema/config.schema.json`"
+        );
+    }
+}
@@ -26,6 +26,8 @@ mod tests {
             has_restart: 0,
             started_at: 0,
             stopped_at: 0,
+            watch_restart: 0,
+            last_change: None,
         });
 
         let request = Request {
@@ -48,6 +50,7 @@ mod tests {
                 name: Some(task.name),
                 group: task.group,
                 mat: false,
+                selector: None,
             }),
         };
 
@@ -71,6 +74,7 @@ mod tests {
                 name: Some("Default".to_string()),
                 group: None,
                 mat: false,
+                selector: None,
             }),
         };
         let buf = serde_json::to_vec(&[request]).unwrap();
@@ -94,6 +98,7 @@ mod tests {
                     name: Some("Default".to_string()),
                     group: None,
                     mat: false,
+                    selector: None,
                 },
                 "{\"key\": \"value\"}\n".to_string(),
             ),